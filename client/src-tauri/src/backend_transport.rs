@@ -0,0 +1,197 @@
+// Transport to the local PocketPaw backend.
+//
+// On Unix the client launches the backend with a Unix domain socket, in an
+// owner-only folder under ~/.pocketpaw/, next to its TCP port. The webview
+// talks to the TCP port; health checks and the HTTP proxy speak plain
+// HTTP/1.1 over the socket when it is live, and fall back to
+// 127.0.0.1:<port> otherwise.
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+const SOCKET_DIR: &str = "run";
+const SOCKET_FILE: &str = "backend.sock";
+
+/// A minimal HTTP response returned by the socket transport.
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: String,
+}
+
+/// Path of the backend's Unix socket: `~/.pocketpaw/run/backend.sock`.
+pub fn socket_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Could not determine home directory")?;
+    Ok(home.join(".pocketpaw").join(SOCKET_DIR).join(SOCKET_FILE))
+}
+
+/// Returns true when the backend socket exists and accepts connections.
+#[cfg(unix)]
+pub fn socket_alive() -> bool {
+    match socket_path() {
+        Ok(path) => path.exists() && UnixStream::connect(&path).is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn socket_alive() -> bool {
+    false
+}
+
+/// Remove a stale socket file left behind by a crashed backend so the next
+/// launch can bind to it.
+#[cfg(unix)]
+pub fn remove_stale_socket() {
+    if let Ok(path) = socket_path() {
+        if path.exists() && UnixStream::connect(&path).is_err() {
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
+
+/// Create the socket's folder with owner-only access (0700) and return the
+/// socket path. uvicorn makes the socket itself world-writable, so the folder
+/// is what keeps other local users out, from the moment the socket exists.
+#[cfg(unix)]
+pub fn prepare_socket_dir() -> Result<PathBuf, String> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let path = socket_path()?;
+    let dir = path.parent().ok_or("Invalid socket path")?;
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .and_then(|()| std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700)))
+        .map_err(|e| format!("Failed to create socket dir: {}", e))?;
+    Ok(path)
+}
+
+/// Send a request over the backend socket. `path_and_query` is the request
+/// target (e.g. `/api/v1/version`).
+#[cfg(unix)]
+pub fn socket_request(
+    method: &str,
+    path_and_query: &str,
+    body: Option<&[u8]>,
+    timeout: Duration,
+) -> Result<HttpResponse, String> {
+    let path = socket_path()?;
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| format!("Failed to connect to backend socket: {}", e))?;
+    let _ = stream.set_read_timeout(Some(timeout));
+    let _ = stream.set_write_timeout(Some(timeout));
+
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nAccept: */*\r\n",
+        method, path_and_query
+    );
+    if let Some(data) = body {
+        request.push_str("Content-Type: application/json\r\n");
+        request.push_str(&format!("Content-Length: {}\r\n", data.len()));
+    }
+    request.push_str("\r\n");
+
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("Request failed: {}", e))?;
+    if let Some(data) = body {
+        stream
+            .write_all(data)
+            .map_err(|e| format!("Request failed: {}", e))?;
+    }
+    stream
+        .flush()
+        .map_err(|e| format!("Request failed: {}", e))?;
+
+    read_response(BufReader::new(stream))
+}
+
+#[cfg(not(unix))]
+pub fn socket_request(
+    _method: &str,
+    _path_and_query: &str,
+    _body: Option<&[u8]>,
+    _timeout: Duration,
+) -> Result<HttpResponse, String> {
+    Err("Unix sockets are not supported on this platform".to_string())
+}
+
+/// Parse an HTTP/1.1 response: status line, headers, then a body framed by
+/// Content-Length, chunked encoding, or connection close.
+#[cfg(unix)]
+fn read_response<R: BufRead>(mut reader: R) -> Result<HttpResponse, String> {
+    let mut status_line = String::new();
+    reader
+        .read_line(&mut status_line)
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|s| s.parse::<u16>().ok())
+        .ok_or_else(|| format!("Malformed status line: {}", status_line.trim()))?;
+
+    let mut content_length: Option<usize> = None;
+    let mut chunked = false;
+    loop {
+        let mut line = String::new();
+        let n = reader
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+        let line = line.trim_end();
+        if n == 0 || line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim().to_ascii_lowercase();
+            let value = value.trim();
+            if name == "content-length" {
+                content_length = value.parse().ok();
+            } else if name == "transfer-encoding" && value.eq_ignore_ascii_case("chunked") {
+                chunked = true;
+            }
+        }
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            let mut size_line = String::new();
+            reader
+                .read_line(&mut size_line)
+                .map_err(|e| format!("Failed to read response: {}", e))?;
+            let size_str = size_line.trim().split(';').next().unwrap_or("");
+            let size = usize::from_str_radix(size_str, 16)
+                .map_err(|_| format!("Malformed chunk size: {}", size_line.trim()))?;
+            if size == 0 {
+                break;
+            }
+            let mut chunk = vec![0u8; size];
+            reader
+                .read_exact(&mut chunk)
+                .map_err(|e| format!("Failed to read response: {}", e))?;
+            body.extend_from_slice(&chunk);
+            let mut crlf = String::new();
+            let _ = reader.read_line(&mut crlf);
+        }
+    } else if let Some(len) = content_length {
+        body.resize(len, 0);
+        reader
+            .read_exact(&mut body)
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+    } else {
+        reader
+            .read_to_end(&mut body)
+            .map_err(|e| format!("Failed to read response: {}", e))?;
+    }
+
+    Ok(HttpResponse {
+        status,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
@@ -16,11 +16,14 @@ use std::io::{BufRead, BufReader};
 use std::net::TcpStream;
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::sync::Mutex;
 use std::time::Duration;
 
 use regex::Regex;
 use serde::Serialize;
-use tauri::{AppHandle, Emitter};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::backend_transport;
 
 /// Augment the current PATH with common binary locations that macOS GUI apps miss.
 /// Tauri apps launched from Finder/Dock don't source .zshrc/.bashrc, so they get
@@ -112,9 +115,13 @@ pub fn get_pocketpaw_config_dir() -> Result<String, String> {
     Ok(config_dir.to_string_lossy().to_string())
 }
 
-/// Check if a backend is running on the given port
+/// Check if a backend is running — over the Unix socket when it is live,
+/// otherwise on the given TCP port.
 #[tauri::command]
 pub fn check_backend_running(port: u16) -> Result<bool, String> {
+    if backend_transport::socket_alive() {
+        return Ok(true);
+    }
     let addr = format!("127.0.0.1:{}", port);
     match TcpStream::connect_timeout(
         &addr.parse().map_err(|e| format!("Invalid address: {}", e))?,
@@ -125,10 +132,22 @@ pub fn check_backend_running(port: u16) -> Result<bool, String> {
     }
 }
 
-/// Check if the backend on the given port is actually PocketPaw by hitting /api/v1/version.
+/// Check if the backend is actually PocketPaw by hitting /api/v1/version.
+/// Prefers the Unix socket and falls back to the given TCP port.
 /// Done from Rust to avoid CORS/mixed-content issues in the Tauri webview.
 #[tauri::command]
 pub fn check_pocketpaw_version(port: u16) -> Result<Option<String>, String> {
+    if backend_transport::socket_alive() {
+        if let Ok(response) = backend_transport::socket_request(
+            "GET",
+            "/api/v1/version",
+            None,
+            Duration::from_secs(5),
+        ) {
+            return Ok(_parse_version(&response.body));
+        }
+    }
+
     let url = format!("http://127.0.0.1:{}/api/v1/version", port);
     let client = std::net::TcpStream::connect_timeout(
         &format!("127.0.0.1:{}", port)
@@ -152,24 +171,22 @@ pub fn check_pocketpaw_version(port: u16) -> Result<Option<String>, String> {
                 .into_body()
                 .read_to_string()
                 .unwrap_or_default();
-            // Parse JSON to extract "version" field
-            if let Some(start) = body.find("\"version\"") {
-                if let Some(colon) = body[start..].find(':') {
-                    let after_colon = &body[start + colon + 1..];
-                    let trimmed = after_colon.trim_start();
-                    if trimmed.starts_with('"') {
-                        if let Some(end) = trimmed[1..].find('"') {
-                            return Ok(Some(trimmed[1..1 + end].to_string()));
-                        }
-                    }
-                }
-            }
-            Ok(None)
+            Ok(_parse_version(&body))
         }
         Err(_) => Ok(None),
     }
 }
 
+/// Extract the "version" string field from a /api/v1/version JSON body.
+fn _parse_version(body: &str) -> Option<String> {
+    let start = body.find("\"version\"")?;
+    let colon = body[start..].find(':')?;
+    let after_colon = &body[start + colon + 1..];
+    let value = after_colon.trim_start().strip_prefix('"')?;
+    let end = value.find('"')?;
+    Some(value[..end].to_string())
+}
+
 #[derive(Serialize, Clone)]
 pub struct InstallStatus {
     pub installed: bool,
@@ -751,9 +768,12 @@ fn _backend_cmd(program: &str) -> Command {
 /// 2. `pocketpaw serve` (direct binary in PATH)
 /// 3. `uv run --no-project pocketpaw serve` (uv-managed)
 /// 4. `python -m pocketpaw serve` / `python3 -m pocketpaw serve` (system Python)
+///
+/// When `uds` is set the backend is asked to bind to that Unix socket instead of TCP.
 /// Returns (Child, strategy_name) on success, or a combined error message.
 fn _try_spawn_backend(
     port_str: &str,
+    uds: Option<&str>,
     #[cfg(windows)] flags: u32,
 ) -> Result<(std::process::Child, &'static str), String> {
     let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("/tmp"));
@@ -798,6 +818,9 @@ fn _try_spawn_backend(
             cmd.arg(arg);
         }
         cmd.args(["serve", "--port", port_str]);
+        if let Some(socket) = uds {
+            cmd.args(["--uds", socket]);
+        }
         cmd.stdout(Stdio::null())
             .stderr(Stdio::null())
             .stdin(Stdio::null());
@@ -825,29 +848,27 @@ fn _try_spawn_backend(
 /// backend survives if the Tauri app exits. DETACHED_PROCESS is avoided because
 /// it conflicts with CREATE_NO_WINDOW and can spawn a visible console for child processes.
 #[cfg(windows)]
-fn _spawn_backend(port_str: &str) -> Result<std::process::Child, String> {
+fn _spawn_backend(port_str: &str, uds: Option<&str>) -> Result<std::process::Child, String> {
     const CREATE_NO_WINDOW: u32 = 0x08000000;
     const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
     let flags = CREATE_NO_WINDOW | CREATE_NEW_PROCESS_GROUP;
 
-    _try_spawn_backend(port_str, flags).map(|(child, _)| child)
+    _try_spawn_backend(port_str, uds, flags).map(|(child, _)| child)
 }
 
 #[cfg(not(windows))]
-fn _spawn_backend(port_str: &str) -> Result<std::process::Child, String> {
-    _try_spawn_backend(port_str).map(|(child, _)| child)
+fn _spawn_backend(port_str: &str, uds: Option<&str>) -> Result<std::process::Child, String> {
+    _try_spawn_backend(port_str, uds).map(|(child, _)| child)
 }
 
-/// Start the PocketPaw backend as a detached background process on the given port.
-/// Returns immediately — frontend should poll check_backend_running to confirm.
-/// After spawning, waits briefly and checks if the process exited immediately
-/// (e.g. due to missing dependencies or config errors).
-#[tauri::command]
-pub fn start_pocketpaw_backend(port: u16) -> Result<bool, String> {
-    let port_str = port.to_string();
-
-    let mut child = _spawn_backend(&port_str)?;
+/// Managed state holding the backend process started by this app, so it can
+/// be stopped or restarted later (e.g. from the tray).
+#[derive(Default)]
+pub struct BackendProcess(pub Mutex<Option<std::process::Child>>);
 
+/// Give a freshly spawned backend a moment to crash, then report whether it
+/// is still alive (or exited cleanly).
+fn _check_spawned_backend(child: &mut std::process::Child) -> Result<bool, String> {
     // Give the process a moment to crash if it's going to
     std::thread::sleep(Duration::from_millis(500));
 
@@ -875,3 +896,147 @@ pub fn start_pocketpaw_backend(port: u16) -> Result<bool, String> {
         }
     }
 }
+
+/// How long a backend started on the Unix socket gets to answer there.
+#[cfg(unix)]
+const BACKEND_SOCKET_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Held for the whole of `start_pocketpaw_backend`, so two starts can't both
+/// spawn a backend.
+static BACKEND_START: Mutex<()> = Mutex::new(());
+
+/// Wait until a freshly spawned backend answers on its Unix socket. Fails if
+/// the process exits first (e.g. a backend too old to know `--uds`) or
+/// doesn't answer within `timeout`.
+#[cfg(unix)]
+fn _wait_for_socket(child: &mut std::process::Child, timeout: Duration) -> Result<(), String> {
+    let deadline = std::time::Instant::now() + timeout;
+    loop {
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(format!(
+                "Backend process exited with code {}",
+                status.code().unwrap_or(-1)
+            ));
+        }
+        if backend_transport::socket_alive()
+            && backend_transport::socket_request(
+                "GET",
+                "/api/v1/version",
+                None,
+                Duration::from_secs(2),
+            )
+            .is_ok()
+        {
+            return Ok(());
+        }
+        if std::time::Instant::now() >= deadline {
+            return Err("Backend did not answer on its socket in time".to_string());
+        }
+        std::thread::sleep(Duration::from_millis(250));
+    }
+}
+
+/// Start the PocketPaw backend as a detached background process.
+/// On Unix the backend also listens on `~/.pocketpaw/run/backend.sock`, inside
+/// an owner-only folder, which the app's own requests prefer; the webview
+/// keeps using the TCP port. This waits until the socket answers. If the
+/// backend exits or never answers (e.g. one without `--uds`), this falls back
+/// to a TCP-only backend on the given port.
+/// For TCP this returns without waiting — the frontend should poll
+/// check_backend_running to confirm. It does wait briefly and checks if the
+/// process exited immediately (e.g. due to missing dependencies or config
+/// errors).
+/// If the backend this app started earlier is still running, it is reused
+/// and nothing new is spawned.
+#[tauri::command(async)]
+pub fn start_pocketpaw_backend(app: AppHandle, port: u16) -> Result<bool, String> {
+    let _starting = BACKEND_START.lock().unwrap();
+    if managed_backend_alive(&app) {
+        return Ok(true);
+    }
+    let port_str = port.to_string();
+
+    #[cfg(unix)]
+    {
+        let socket = backend_transport::prepare_socket_dir()?;
+        backend_transport::remove_stale_socket();
+        let socket_str = socket.to_string_lossy().to_string();
+
+        let mut child = _spawn_backend(&port_str, Some(&socket_str))?;
+        match _wait_for_socket(&mut child, BACKEND_SOCKET_TIMEOUT) {
+            Ok(()) => {
+                *app.state::<BackendProcess>().0.lock().unwrap() = Some(child);
+                return Ok(true);
+            }
+            Err(e) => {
+                log::warn!(
+                    "Backend did not start on Unix socket, falling back to TCP: {}",
+                    e
+                );
+                let _ = child.kill();
+                let _ = child.wait();
+                backend_transport::remove_stale_socket();
+            }
+        }
+    }
+
+    let mut child = _spawn_backend(&port_str, None)?;
+    let started = _check_spawned_backend(&mut child)?;
+    *app.state::<BackendProcess>().0.lock().unwrap() = Some(child);
+    Ok(started)
+}
+
+/// Stop the backend process started by this app.
+/// Returns false if no backend was started from here.
+#[tauri::command]
+pub fn stop_pocketpaw_backend(app: AppHandle) -> Result<bool, String> {
+    let child = app.state::<BackendProcess>().0.lock().unwrap().take();
+    match child {
+        Some(mut child) => {
+            child
+                .kill()
+                .map_err(|e| format!("Failed to stop backend: {}", e))?;
+            let _ = child.wait();
+            #[cfg(unix)]
+            backend_transport::remove_stale_socket();
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Restart the backend started by this app. A backend started elsewhere
+/// (e.g. `pocketpaw serve` in a terminal) may have no restart route, so it is
+/// left to whoever runs it.
+#[tauri::command]
+pub fn restart_pocketpaw_backend(app: AppHandle, port: u16) -> Result<bool, String> {
+    if !backend_is_managed(&app) {
+        return Err(
+            "This backend wasn't started by PocketPaw; restart it where it's running".to_string(),
+        );
+    }
+    stop_pocketpaw_backend(app.clone())?;
+    start_pocketpaw_backend(app, port)
+}
+
+/// Whether the backend started by this app is still running. One that has
+/// exited is forgotten.
+fn managed_backend_alive(app: &AppHandle) -> bool {
+    let state = app.state::<BackendProcess>();
+    let mut child = state.0.lock().unwrap();
+    match child.as_mut().map(|c| c.try_wait()) {
+        Some(Ok(Some(_))) => {
+            *child = None;
+            false
+        }
+        // If its status can't be read, assume it still runs rather than
+        // risk starting a second one
+        Some(Ok(None) | Err(_)) => true,
+        None => false,
+    }
+}
+
+/// Whether the backend is a process started by this app.
+pub fn backend_is_managed(app: &AppHandle) -> bool {
+    app.state::<BackendProcess>().0.lock().unwrap().is_some()
+}
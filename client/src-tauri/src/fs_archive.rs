@@ -0,0 +1,1326 @@
+// Browsing, extracting and creating zip, tar, tar.gz and tar.zst archives.
+//
+// `fs_archive_list` returns one folder level of an archive as `FileEntry`
+// rows whose paths continue below the archive's own path
+// (`/x/project.zip/src/main.rs`), so the explorer can browse into it like a
+// folder. Listings are cached per archive until the file changes, since a
+// compressed tarball has to be read to the end to list it.
+//
+// Extraction and creation are background jobs like `fs_transfer`: the calling
+// window gets throttled `fs-archive-progress` events and one
+// `fs-archive-done` event with the report, all tagged with the caller-chosen
+// job id, which `fs_archive_cancel` also takes.
+//
+// Entry names that would land outside the destination (absolute paths, "..")
+// are refused, symlinks are only created if they resolve inside it and only
+// after everything else is written, and extraction stops once the output
+// outgrows the archive by more than `MAX_RATIO` or exceeds
+// `MAX_EXTRACT_BYTES`, whatever the archive claims about its sizes.
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+
+use crate::fs_commands::FileEntry;
+use crate::fs_scope;
+use crate::fs_transfer::TransferFailure;
+use crate::fs_write;
+
+/// Extraction stops past this many bytes written.
+const MAX_EXTRACT_BYTES: u64 = 16 * 1024 * 1024 * 1024;
+/// Extraction stops once the output is this many times the archive's size...
+const MAX_RATIO: u64 = 200;
+/// ...but only past this many bytes, since small files compress very well.
+const RATIO_FLOOR: u64 = 64 * 1024 * 1024;
+const MAX_ENTRIES: usize = 500_000;
+const COPY_BUFFER_BYTES: usize = 256 * 1024;
+/// Progress events are sent at most this often.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    fn of(path: &Path) -> Result<Self, String> {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        if name.ends_with(".zip") {
+            Ok(Self::Zip)
+        } else if name.ends_with(".tar") {
+            Ok(Self::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(Self::TarGz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Ok(Self::TarZst)
+        } else {
+            Err(format!("Unsupported archive type: {}", path.display()))
+        }
+    }
+}
+
+/// Payload of `fs-archive-progress`. For tarballs extraction counts
+/// compressed bytes read, as the unpacked size isn't known up front.
+#[derive(Debug, Serialize, Clone)]
+pub struct ArchiveProgress {
+    pub job_id: String,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub entries_done: u64,
+    pub current_path: String,
+}
+
+/// Payload of `fs-archive-done`.
+#[derive(Debug, Serialize, Clone)]
+pub struct ArchiveReport {
+    pub job_id: String,
+    pub entries_done: u64,
+    pub bytes_done: u64,
+    /// Entries left alone because something already existed there.
+    pub skipped: u64,
+    pub failures: Vec<TransferFailure>,
+    pub cancelled: bool,
+    /// Why the job stopped early, if it did.
+    pub error: Option<String>,
+    /// The folder extracted into, or the archive created.
+    pub destination: String,
+}
+
+/// An entry of an archive, with its normalized relative path.
+#[derive(Debug, Clone)]
+struct Listed {
+    path: String,
+    is_dir: bool,
+    size: u64,
+    modified: u64,
+}
+
+/// The running jobs, by job id.
+fn jobs() -> &'static Mutex<HashMap<String, Arc<AtomicBool>>> {
+    static JOBS: OnceLock<Mutex<HashMap<String, Arc<AtomicBool>>>> = OnceLock::new();
+    JOBS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// `name` as a relative path with "/" separators, or `None` if it would
+/// leave the folder it's extracted into.
+fn normalize(name: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for part in name.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => return None,
+            part => {
+                // Drive letters and other prefixes on Windows
+                let mut components = Path::new(part).components();
+                if !matches!(
+                    (components.next(), components.next()),
+                    (Some(Component::Normal(_)), None)
+                ) {
+                    return None;
+                }
+                parts.push(part);
+            }
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// Whether a symlink at `path` pointing to `target` stays inside the folder
+/// it's extracted into.
+fn link_stays_inside(path: &str, target: &str) -> bool {
+    if target.is_empty() || target.starts_with(['/', '\\']) || Path::new(target).has_root() {
+        return false;
+    }
+    let mut depth = path.matches('/').count();
+    for part in target.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => match depth.checked_sub(1) {
+                Some(up) => depth = up,
+                None => return false,
+            },
+            part if normalize(part).is_none() => return false,
+            _ => depth += 1,
+        }
+    }
+    true
+}
+
+/// Zip timestamps are local time without a zone.
+fn zip_time_secs(time: Option<zip::DateTime>) -> u64 {
+    time.and_then(|t| NaiveDateTime::try_from(t).ok())
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+        .map(|t| t.timestamp().max(0) as u64)
+        .unwrap_or(0)
+}
+
+fn zip_time(time: SystemTime) -> zip::DateTime {
+    let local: chrono::DateTime<Local> = time.into();
+    zip::DateTime::try_from(local.naive_local()).unwrap_or_default()
+}
+
+fn mtime_secs(meta: &fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn open_zip(path: &Path) -> Result<zip::ZipArchive<fs::File>, String> {
+    let file = fs::File::open(path).map_err(|e| format!("Failed to open archive: {}", e))?;
+    zip::ZipArchive::new(file).map_err(|e| format!("Failed to read archive: {}", e))
+}
+
+/// A reader for the tar stream inside a possibly compressed tarball.
+fn open_tar<R: Read + 'static>(format: ArchiveFormat, input: R) -> Result<Box<dyn Read>, String> {
+    Ok(match format {
+        ArchiveFormat::TarGz => Box::new(flate2::read::GzDecoder::new(input)),
+        ArchiveFormat::TarZst => Box::new(
+            zstd::stream::read::Decoder::new(input)
+                .map_err(|e| format!("Failed to read archive: {}", e))?,
+        ),
+        _ => Box::new(input),
+    })
+}
+
+fn read_listing(archive: &Path) -> Result<Vec<Listed>, String> {
+    let format = ArchiveFormat::of(archive)?;
+    let mut listing = Vec::new();
+    if format == ArchiveFormat::Zip {
+        let mut zip = open_zip(archive)?;
+        for i in 0..zip.len() {
+            let entry = zip
+                .by_index_raw(i)
+                .map_err(|e| format!("Failed to read archive: {}", e))?;
+            if let Some(path) = normalize(entry.name()) {
+                listing.push(Listed {
+                    path,
+                    is_dir: entry.is_dir(),
+                    size: entry.size(),
+                    modified: zip_time_secs(entry.last_modified()),
+                });
+            }
+        }
+    } else {
+        let file = fs::File::open(archive).map_err(|e| format!("Failed to open archive: {}", e))?;
+        let mut tar = tar::Archive::new(open_tar(format, io::BufReader::new(file))?);
+        let entries = tar
+            .entries()
+            .map_err(|e| format!("Failed to read archive: {}", e))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Failed to read archive: {}", e))?;
+            let header = entry.header();
+            if let Some(path) = normalize(&String::from_utf8_lossy(&entry.path_bytes())) {
+                listing.push(Listed {
+                    path,
+                    is_dir: header.entry_type().is_dir(),
+                    size: header.size().unwrap_or(0),
+                    modified: header.mtime().unwrap_or(0),
+                });
+            }
+        }
+    }
+    Ok(listing)
+}
+
+/// The listing of `archive`, read again only when the file has changed.
+fn listing(archive: &Path) -> Result<Arc<Vec<Listed>>, String> {
+    type Cached = (PathBuf, u64, SystemTime, Arc<Vec<Listed>>);
+    static CACHE: OnceLock<Mutex<Option<Cached>>> = OnceLock::new();
+
+    let meta = fs::metadata(archive).map_err(|e| format!("Failed to read archive: {}", e))?;
+    let modified = meta.modified().unwrap_or(UNIX_EPOCH);
+    let cache = CACHE.get_or_init(|| Mutex::new(None));
+    if let Some((path, len, time, listing)) = cache.lock().unwrap().as_ref() {
+        if path == archive && *len == meta.len() && *time == modified {
+            return Ok(listing.clone());
+        }
+    }
+    let listing = Arc::new(read_listing(archive)?);
+    *cache.lock().unwrap() = Some((archive.to_path_buf(), meta.len(), modified, listing.clone()));
+    Ok(listing)
+}
+
+/// List the entries directly inside `dir` (a "/"-separated path inside the
+/// archive; empty or absent for the top level). Folders that only exist
+/// implicitly, as part of a file's path, are listed too.
+#[tauri::command]
+pub fn fs_archive_list(path: String, dir: Option<String>) -> Result<Vec<FileEntry>, String> {
+    fs_scope::check(&path)?;
+    let archive = Path::new(&path);
+    let listing = listing(archive)?;
+    let dir = dir.as_deref().and_then(normalize).unwrap_or_default();
+    let prefix = if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", dir)
+    };
+    let base = dir
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .fold(archive.to_path_buf(), |base, part| base.join(part));
+
+    let mut children: BTreeMap<&str, FileEntry> = BTreeMap::new();
+    let mut found = dir.is_empty();
+    for item in listing.iter() {
+        if item.path == dir && item.is_dir {
+            found = true;
+        }
+        let Some(rest) = item.path.strip_prefix(&prefix) else {
+            continue;
+        };
+        found = true;
+        let (name, nested) = match rest.split_once('/') {
+            Some((name, _)) => (name, true),
+            None => (rest, false),
+        };
+        let is_dir = nested || item.is_dir;
+        // An explicit folder entry has the better metadata
+        if nested && children.contains_key(name) {
+            continue;
+        }
+        children.insert(
+            name,
+            FileEntry {
+                name: name.to_string(),
+                path: base.join(name).to_string_lossy().to_string(),
+                is_dir,
+                size: if is_dir { 0 } else { item.size },
+                modified: if nested { 0 } else { item.modified },
+                extension: if is_dir {
+                    String::new()
+                } else {
+                    Path::new(name)
+                        .extension()
+                        .map(|e| e.to_string_lossy().to_lowercase())
+                        .unwrap_or_default()
+                },
+            },
+        );
+    }
+    if !found {
+        return Err(format!("Not found in {}: {}", archive.display(), dir));
+    }
+    Ok(children.into_values().collect())
+}
+
+/// Progress reporting shared by extraction and creation.
+struct Job {
+    job_id: String,
+    cancelled: Arc<AtomicBool>,
+    /// Window the events go to; without it the job runs silently.
+    target: Option<(AppHandle, String)>,
+    bytes_done: u64,
+    bytes_total: u64,
+    entries_done: u64,
+    current: String,
+    last_progress: Option<Instant>,
+}
+
+impl Job {
+    fn new(job_id: String, cancelled: Arc<AtomicBool>) -> Self {
+        Self {
+            job_id,
+            cancelled,
+            target: None,
+            bytes_done: 0,
+            bytes_total: 0,
+            entries_done: 0,
+            current: String::new(),
+            last_progress: None,
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn progress(&mut self, force: bool) {
+        if !force
+            && self
+                .last_progress
+                .is_some_and(|at| at.elapsed() < PROGRESS_INTERVAL)
+        {
+            return;
+        }
+        self.last_progress = Some(Instant::now());
+        if let Some((app, label)) = &self.target {
+            let _ = app.emit_to(
+                label.as_str(),
+                "fs-archive-progress",
+                ArchiveProgress {
+                    job_id: self.job_id.clone(),
+                    bytes_done: self.bytes_done,
+                    bytes_total: self.bytes_total,
+                    entries_done: self.entries_done,
+                    current_path: self.current.clone(),
+                },
+            );
+        }
+    }
+}
+
+fn cancelled_error() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "Cancelled")
+}
+
+/// Counts the bytes read through it into `count`, for progress over a
+/// compressed stream.
+struct CountingReader<R> {
+    inner: R,
+    count: Arc<std::sync::atomic::AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// Where `path` goes, relative to the destination, when only `selection`
+/// (entries and their subtrees) is extracted; `None` if it isn't selected.
+/// Selected entries land directly in the destination.
+fn selected_path(path: &str, selection: &[String]) -> Option<String> {
+    if selection.is_empty() {
+        return Some(path.to_string());
+    }
+    selection.iter().find_map(|selected| {
+        let inside = path == selected
+            || path
+                .strip_prefix(selected.as_str())
+                .is_some_and(|rest| rest.starts_with('/'));
+        inside.then(|| match selected.rfind('/') {
+            Some(slash) => path[slash + 1..].to_string(),
+            None => path.to_string(),
+        })
+    })
+}
+
+struct Extractor {
+    job: Job,
+    dest: PathBuf,
+    /// `dest`, resolved, to check folders against before writing into them.
+    dest_resolved: PathBuf,
+    overwrite: bool,
+    archive_len: u64,
+    written: u64,
+    skipped: u64,
+    failures: Vec<TransferFailure>,
+    /// Symlinks and hard links, made once everything else is written.
+    links: Vec<(String, String, bool)>,
+    /// Regular files this job wrote, the only ones hard links may copy.
+    extracted: HashSet<PathBuf>,
+    checked_dirs: Vec<PathBuf>,
+    buffer: Vec<u8>,
+}
+
+impl Extractor {
+    fn new(job: Job, dest: &Path, overwrite: bool) -> Self {
+        Self {
+            job,
+            dest: dest.to_path_buf(),
+            dest_resolved: PathBuf::new(),
+            overwrite,
+            archive_len: 0,
+            written: 0,
+            skipped: 0,
+            failures: Vec::new(),
+            links: Vec::new(),
+            extracted: HashSet::new(),
+            checked_dirs: Vec::new(),
+            buffer: Vec::new(),
+        }
+    }
+
+    fn run(&mut self, archive: &Path, selection: &[String]) -> Result<(), String> {
+        let format = ArchiveFormat::of(archive)?;
+        self.archive_len = fs::metadata(archive)
+            .map_err(|e| format!("Failed to open archive: {}", e))?
+            .len();
+        self.dest_resolved = fs::create_dir_all(&self.dest)
+            .and_then(|()| fs::canonicalize(&self.dest))
+            .map_err(|e| format!("Failed to create dir: {}", e))?;
+        self.job.progress(true);
+        match format {
+            ArchiveFormat::Zip => extract_zip(self, archive, selection)?,
+            _ => extract_tar(self, archive, format, selection)?,
+        }
+        self.make_links(selection);
+        Ok(())
+    }
+
+    fn fail(&mut self, path: &str, error: String) {
+        self.failures.push(TransferFailure {
+            path: path.to_string(),
+            error,
+        });
+    }
+
+    /// Stop once the output grows suspiciously large.
+    fn guard(&self) -> Result<(), String> {
+        if self.written > MAX_EXTRACT_BYTES
+            || (self.written > RATIO_FLOOR
+                && self.written > self.archive_len.saturating_mul(MAX_RATIO))
+        {
+            return Err(
+                "Stopped extracting: the archive expands far beyond its size and may be a zip bomb"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Create the folders for `path` and make sure nothing already there
+    /// redirects them outside the destination.
+    fn prepare_parent(&mut self, path: &Path) -> Result<(), String> {
+        let Some(parent) = path.parent() else {
+            return Ok(());
+        };
+        if self.checked_dirs.iter().any(|dir| dir == parent) {
+            return Ok(());
+        }
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
+        let resolved =
+            fs::canonicalize(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
+        if !resolved.starts_with(&self.dest_resolved) {
+            return Err("Refusing to write through a link that leaves the destination".to_string());
+        }
+        self.checked_dirs.push(parent.to_path_buf());
+        Ok(())
+    }
+
+    /// Make room for a new entry at `path`, a symlink to `link` if given.
+    /// Returns false if it should be skipped.
+    fn make_room(&mut self, path: &Path, link: Option<&str>) -> Result<bool, String> {
+        match fs::symlink_metadata(path) {
+            Err(_) => Ok(true),
+            Ok(_) if !self.overwrite => {
+                self.skipped += 1;
+                Ok(false)
+            }
+            Ok(meta) if meta.is_dir() => Err("A folder is in the way".to_string()),
+            // Links already checked may resolve through this one, so it's
+            // never swapped for something else
+            Ok(meta) if meta.is_symlink() => {
+                if link.is_some_and(|link| fs::read_link(path).is_ok_and(|t| t == Path::new(link)))
+                {
+                    self.skipped += 1;
+                    Ok(false)
+                } else {
+                    Err("A link is in the way".to_string())
+                }
+            }
+            // Removed rather than truncated so a link there isn't followed
+            Ok(_) => fs::remove_file(path)
+                .map(|()| true)
+                .map_err(|e| format!("Failed to replace: {}", e)),
+        }
+    }
+
+    fn write_file(
+        &mut self,
+        reader: &mut dyn Read,
+        path: &Path,
+        mode: Option<u32>,
+        modified: Option<SystemTime>,
+    ) -> Result<(), String> {
+        self.prepare_parent(path)?;
+        if !self.make_room(path, None)? {
+            return Ok(());
+        }
+        let mut output = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| format!("Failed to create file: {}", e))?;
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.resize(COPY_BUFFER_BYTES, 0);
+        let written = loop {
+            if self.job.is_cancelled() {
+                break Err("Cancelled".to_string());
+            }
+            let n = match reader.read(&mut buffer) {
+                Ok(0) => break Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => break Err(format!("Failed to read archive: {}", e)),
+            };
+            if let Err(e) = output.write_all(&buffer[..n]) {
+                break Err(format!("Failed to write file: {}", e));
+            }
+            self.written += n as u64;
+            if let Err(e) = self.guard() {
+                break Err(e);
+            }
+        };
+        self.buffer = buffer;
+        if let Err(e) = written {
+            drop(output);
+            let _ = fs::remove_file(path);
+            return Err(e);
+        }
+
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+            // No setuid, setgid or sticky bits from an archive
+            let _ = output.set_permissions(fs::Permissions::from_mode(mode & 0o777));
+        }
+        #[cfg(not(unix))]
+        let _ = mode;
+        if let Some(modified) = modified {
+            let _ = output.set_times(fs::FileTimes::new().set_modified(modified));
+        }
+        self.extracted.insert(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Extract one entry given its name in the archive.
+    fn entry(
+        &mut self,
+        name: &str,
+        kind: EntryKind,
+        reader: &mut dyn Read,
+        mode: Option<u32>,
+        modified: Option<SystemTime>,
+        selection: &[String],
+    ) -> Result<(), String> {
+        let Some(path) = normalize(name).and_then(|path| selected_path(&path, selection)) else {
+            if normalize(name).is_none() && !name.trim_matches(['/', '.']).is_empty() {
+                self.fail(
+                    name,
+                    "Refusing to extract outside the destination".to_string(),
+                );
+            }
+            return Ok(());
+        };
+        self.job.current = path.clone();
+        let target = self.dest.join(&path);
+        let result = match kind {
+            EntryKind::Dir => self.prepare_parent(&target.join(".")),
+            EntryKind::File => self.write_file(reader, &target, mode, modified),
+            EntryKind::Symlink(link) => {
+                self.links.push((path.clone(), link, false));
+                Ok(())
+            }
+            EntryKind::HardLink(link) => {
+                self.links.push((path.clone(), link, true));
+                Ok(())
+            }
+            EntryKind::Other => Ok(()),
+        };
+        match result {
+            Ok(()) => self.job.entries_done += 1,
+            // Fatal: the rest of the archive would be just as bad
+            Err(e) if self.guard().is_err() || self.job.is_cancelled() => return Err(e),
+            Err(e) => self.fail(&path, e),
+        }
+        self.job.progress(false);
+        Ok(())
+    }
+
+    /// Make the hard links, then the symlinks, so copying a hard link's file
+    /// never goes through a link from the archive.
+    fn make_links(&mut self, selection: &[String]) {
+        let (hard, soft): (Vec<_>, Vec<_>) = std::mem::take(&mut self.links)
+            .into_iter()
+            .partition(|(_, _, hard)| *hard);
+        for (path, link, hard) in hard.into_iter().chain(soft) {
+            if self.job.is_cancelled() {
+                return;
+            }
+            let target = self.dest.join(&path);
+            let made = if hard {
+                self.copy_hard_link(&target, &link, selection)
+            } else {
+                self.create_symlink(&path, &target, &link)
+            };
+            match made {
+                Ok(()) => self.job.entries_done += 1,
+                Err(e) => self.fail(&path, e),
+            }
+        }
+    }
+
+    /// A hard link names another entry of the archive; copy that file, as
+    /// long as this job wrote it and it's still a plain file.
+    fn copy_hard_link(
+        &mut self,
+        target: &Path,
+        link: &str,
+        selection: &[String],
+    ) -> Result<(), String> {
+        let source = normalize(link)
+            .and_then(|link| selected_path(&link, selection))
+            .map(|source| self.dest.join(source))
+            .filter(|source| self.extracted.contains(source))
+            .ok_or_else(|| "Link target is not a file from this archive".to_string())?;
+        let plain_file = fs::symlink_metadata(&source).is_ok_and(|meta| meta.is_file())
+            && fs::canonicalize(&source).is_ok_and(|real| real.starts_with(&self.dest_resolved));
+        if !plain_file {
+            return Err("Link target is not a file from this archive".to_string());
+        }
+        self.prepare_parent(target)?;
+        if !self.make_room(target, None)? {
+            return Ok(());
+        }
+        fs::copy(&source, target).map_err(|e| format!("Failed to copy link: {}", e))?;
+        self.extracted.insert(target.to_path_buf());
+        Ok(())
+    }
+
+    fn create_symlink(&mut self, path: &str, target: &Path, link: &str) -> Result<(), String> {
+        let outside = || "Link target is outside the destination".to_string();
+        if !link_stays_inside(path, link) {
+            return Err(outside());
+        }
+        self.prepare_parent(target)?;
+        // The text alone isn't enough: earlier links may lead elsewhere
+        let resolved = target
+            .parent()
+            .and_then(|parent| resolve_link(parent, link))
+            .ok_or_else(outside)?;
+        if !resolved.starts_with(&self.dest_resolved) {
+            return Err(outside());
+        }
+        if !self.make_room(target, Some(link))? {
+            return Ok(());
+        }
+        make_symlink(link, target)
+    }
+}
+
+enum EntryKind {
+    Dir,
+    File,
+    Symlink(String),
+    HardLink(String),
+    Other,
+}
+
+/// Where a symlink in `dir` pointing to `link` leads on disk right now. Any
+/// part of the target that doesn't exist yet must be plain names, so links
+/// made there later can't take it anywhere their own checks wouldn't allow.
+fn resolve_link(dir: &Path, link: &str) -> Option<PathBuf> {
+    let dir = fs::canonicalize(dir).ok()?;
+    if let Ok(resolved) = fs::canonicalize(dir.join(link)) {
+        return Some(resolved);
+    }
+    let parts: Vec<&str> = link
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+    (0..parts.len()).rev().find_map(|existing| {
+        let rest = &parts[existing..];
+        if rest.iter().any(|part| normalize(part).is_none()) {
+            return None;
+        }
+        let base = fs::canonicalize(dir.join(parts[..existing].join("/"))).ok()?;
+        Some(rest.iter().fold(base, |path, part| path.join(part)))
+    })
+}
+
+#[cfg(unix)]
+fn make_symlink(link: &str, target: &Path) -> Result<(), String> {
+    std::os::unix::fs::symlink(link, target).map_err(|e| format!("Failed to create link: {}", e))
+}
+
+#[cfg(not(unix))]
+fn make_symlink(_link: &str, _target: &Path) -> Result<(), String> {
+    Err("Symbolic links are not supported here".to_string())
+}
+
+fn extract_zip(ex: &mut Extractor, archive: &Path, selection: &[String]) -> Result<(), String> {
+    let mut zip = open_zip(archive)?;
+    if zip.len() > MAX_ENTRIES {
+        return Err(format!("Too many entries in archive ({})", zip.len()));
+    }
+    // Check what the archive claims before writing anything; `guard` checks
+    // what actually comes out
+    let mut declared = 0u64;
+    for i in 0..zip.len() {
+        let entry = zip
+            .by_index_raw(i)
+            .map_err(|e| format!("Failed to read archive: {}", e))?;
+        if normalize(entry.name()).is_some_and(|path| selected_path(&path, selection).is_some()) {
+            declared = declared.saturating_add(entry.size());
+        }
+    }
+    if declared > MAX_EXTRACT_BYTES
+        || (declared > RATIO_FLOOR && declared > ex.archive_len.saturating_mul(MAX_RATIO))
+    {
+        return Err(
+            "The archive expands far beyond its size and may be a zip bomb; not extracting"
+                .to_string(),
+        );
+    }
+    ex.job.bytes_total = declared;
+
+    for i in 0..zip.len() {
+        if ex.job.is_cancelled() {
+            break;
+        }
+        let mut entry = zip
+            .by_index(i)
+            .map_err(|e| format!("Failed to read archive: {}", e))?;
+        let name = entry.name().to_string();
+        let kind = if entry.is_dir() {
+            EntryKind::Dir
+        } else if entry.is_symlink() {
+            let mut link = String::new();
+            entry
+                .by_ref()
+                .take(4096)
+                .read_to_string(&mut link)
+                .map_err(|e| format!("Failed to read archive: {}", e))?;
+            EntryKind::Symlink(link)
+        } else {
+            EntryKind::File
+        };
+        let mode = entry.unix_mode();
+        let modified = match zip_time_secs(entry.last_modified()) {
+            0 => None,
+            secs => Some(UNIX_EPOCH + Duration::from_secs(secs)),
+        };
+        let before = ex.written;
+        ex.entry(&name, kind, &mut entry, mode, modified, selection)?;
+        ex.job.bytes_done += ex.written - before;
+    }
+    Ok(())
+}
+
+fn extract_tar(
+    ex: &mut Extractor,
+    archive: &Path,
+    format: ArchiveFormat,
+    selection: &[String],
+) -> Result<(), String> {
+    let file = fs::File::open(archive).map_err(|e| format!("Failed to open archive: {}", e))?;
+    let read = Arc::new(std::sync::atomic::AtomicU64::new(0));
+    let input = CountingReader {
+        inner: io::BufReader::new(file),
+        count: read.clone(),
+    };
+    ex.job.bytes_total = ex.archive_len;
+    let mut tar = tar::Archive::new(open_tar(format, input)?);
+    let entries = tar
+        .entries()
+        .map_err(|e| format!("Failed to read archive: {}", e))?;
+    for (count, entry) in entries.enumerate() {
+        if ex.job.is_cancelled() {
+            break;
+        }
+        if count >= MAX_ENTRIES {
+            return Err(format!(
+                "Too many entries in archive (over {})",
+                MAX_ENTRIES
+            ));
+        }
+        let mut entry = entry.map_err(|e| format!("Failed to read archive: {}", e))?;
+        let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+        let header = entry.header();
+        let link = || {
+            header
+                .link_name_bytes()
+                .map(|l| String::from_utf8_lossy(&l).to_string())
+                .unwrap_or_default()
+        };
+        let kind = match header.entry_type() {
+            t if t.is_dir() => EntryKind::Dir,
+            t if t.is_file() || t.is_contiguous() => EntryKind::File,
+            t if t.is_symlink() => EntryKind::Symlink(link()),
+            t if t.is_hard_link() => EntryKind::HardLink(link()),
+            _ => EntryKind::Other,
+        };
+        let mode = header.mode().ok();
+        let modified = header
+            .mtime()
+            .ok()
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        ex.entry(&name, kind, &mut entry, mode, modified, selection)?;
+        ex.job.bytes_done = read.load(Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+fn extract(
+    job: Job,
+    archive: &Path,
+    dest: &Path,
+    selection: &[String],
+    overwrite: bool,
+) -> ArchiveReport {
+    let mut ex = Extractor::new(job, dest, overwrite);
+    let error = ex.run(archive, selection).err();
+    let cancelled = ex.job.is_cancelled();
+    ex.job.progress(true);
+    ArchiveReport {
+        job_id: ex.job.job_id,
+        entries_done: ex.job.entries_done,
+        bytes_done: ex.job.bytes_done,
+        skipped: ex.skipped,
+        failures: ex.failures,
+        cancelled,
+        error: error.filter(|_| !cancelled),
+        destination: dest.to_string_lossy().to_string(),
+    }
+}
+
+/// A file or folder to add, with its name in the archive.
+struct Source {
+    path: PathBuf,
+    name: String,
+    meta: fs::Metadata,
+}
+
+/// Everything below each of `sources`, parents first, named relative to the
+/// folder the source is in. Links aren't followed.
+fn collect(sources: &[String], skip: &Path) -> Result<Vec<Source>, String> {
+    fn visit(
+        path: PathBuf,
+        name: String,
+        skip: &Path,
+        out: &mut Vec<Source>,
+    ) -> Result<(), String> {
+        if path == skip {
+            return Ok(());
+        }
+        let meta = fs::symlink_metadata(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let is_dir = meta.is_dir();
+        out.push(Source {
+            path: path.clone(),
+            name: name.clone(),
+            meta,
+        });
+        if is_dir {
+            let mut entries: Vec<_> = fs::read_dir(&path)
+                .map_err(|e| format!("Failed to read dir: {}", e))?
+                .flatten()
+                .collect();
+            entries.sort_by_key(|entry| entry.file_name());
+            for entry in entries {
+                let child = format!("{}/{}", name, entry.file_name().to_string_lossy());
+                visit(entry.path(), child, skip, out)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut out = Vec::new();
+    for source in sources {
+        let path = PathBuf::from(source);
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| format!("Cannot archive {}", source))?;
+        visit(path, name, skip, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Reads a source file, counting progress and stopping when cancelled.
+struct SourceReader<'a> {
+    file: fs::File,
+    job: &'a mut Job,
+}
+
+impl Read for SourceReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.job.is_cancelled() {
+            return Err(cancelled_error());
+        }
+        let n = self.file.read(buf)?;
+        self.job.bytes_done += n as u64;
+        self.job.progress(false);
+        Ok(n)
+    }
+}
+
+#[cfg(unix)]
+fn unix_mode(meta: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    meta.permissions().mode() & 0o777
+}
+
+#[cfg(not(unix))]
+fn unix_mode(meta: &fs::Metadata) -> u32 {
+    match (meta.is_dir(), meta.permissions().readonly()) {
+        (true, _) => 0o755,
+        (false, true) => 0o444,
+        (false, false) => 0o644,
+    }
+}
+
+fn write_zip<W: Write + Seek>(output: W, sources: &[Source], job: &mut Job) -> io::Result<W> {
+    let mut zip = zip::ZipWriter::new(output);
+    for source in sources {
+        if job.is_cancelled() {
+            return Err(cancelled_error());
+        }
+        job.current = source.name.clone();
+        let mut options = zip::write::SimpleFileOptions::default()
+            .unix_permissions(unix_mode(&source.meta))
+            .large_file(source.meta.len() >= u32::MAX as u64);
+        if let Ok(modified) = source.meta.modified() {
+            options = options.last_modified_time(zip_time(modified));
+        }
+        if source.meta.is_symlink() {
+            let link = fs::read_link(&source.path)?;
+            zip.add_symlink(&source.name, link.to_string_lossy(), options)?;
+        } else if source.meta.is_dir() {
+            zip.add_directory(format!("{}/", source.name), options)?;
+        } else {
+            zip.start_file(
+                &source.name,
+                options.compression_method(zip::CompressionMethod::Deflated),
+            )?;
+            let file = fs::File::open(&source.path)?;
+            io::copy(&mut SourceReader { file, job }, &mut zip)?;
+        }
+        job.entries_done += 1;
+    }
+    Ok(zip.finish()?)
+}
+
+fn write_tar<W: Write>(output: W, sources: &[Source], job: &mut Job) -> io::Result<W> {
+    let mut tar = tar::Builder::new(output);
+    tar.follow_symlinks(false);
+    for source in sources {
+        if job.is_cancelled() {
+            return Err(cancelled_error());
+        }
+        job.current = source.name.clone();
+        let mut header = tar::Header::new_gnu();
+        header.set_metadata_in_mode(&source.meta, tar::HeaderMode::Complete);
+        header.set_mtime(mtime_secs(&source.meta));
+        header.set_mode(unix_mode(&source.meta));
+        if source.meta.is_symlink() {
+            let link = fs::read_link(&source.path)?;
+            header.set_size(0);
+            tar.append_link(&mut header, &source.name, link)?;
+        } else if source.meta.is_dir() {
+            header.set_size(0);
+            tar.append_data(&mut header, format!("{}/", source.name), io::empty())?;
+        } else {
+            let file = fs::File::open(&source.path)?;
+            tar.append_data(&mut header, &source.name, SourceReader { file, job })?;
+        }
+        job.entries_done += 1;
+    }
+    tar.into_inner()
+}
+
+fn create(mut job: Job, sources: &[String], dest: &Path) -> ArchiveReport {
+    let run = |job: &mut Job| -> Result<(), String> {
+        let format = ArchiveFormat::of(dest)?;
+        let tmp = fs_write::temp_path(dest)?;
+        let sources = collect(sources, dest)?;
+        job.bytes_total = sources
+            .iter()
+            .filter(|s| s.meta.is_file())
+            .map(|s| s.meta.len())
+            .sum();
+        job.progress(true);
+
+        let written = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)
+            .and_then(|file| {
+                let output = io::BufWriter::new(file);
+                let output = match format {
+                    ArchiveFormat::Zip => write_zip(output, &sources, job)?,
+                    ArchiveFormat::Tar => write_tar(output, &sources, job)?,
+                    ArchiveFormat::TarGz => {
+                        let gz =
+                            flate2::write::GzEncoder::new(output, flate2::Compression::default());
+                        write_tar(gz, &sources, job)?.finish()?
+                    }
+                    ArchiveFormat::TarZst => {
+                        let zst = zstd::stream::write::Encoder::new(output, 0)?;
+                        write_tar(zst, &sources, job)?.finish()?
+                    }
+                };
+                output.into_inner().map_err(|e| e.into_error())?.sync_all()
+            })
+            .and_then(|()| {
+                // Never replace something that appeared meanwhile
+                if dest.exists() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("{} already exists", dest.display()),
+                    ));
+                }
+                fs::rename(&tmp, dest)
+            });
+        if let Err(e) = written {
+            let _ = fs::remove_file(&tmp);
+            return Err(format!("Failed to create archive: {}", e));
+        }
+        Ok(())
+    };
+    let error = run(&mut job).err();
+    let cancelled = job.is_cancelled();
+    job.progress(true);
+    ArchiveReport {
+        job_id: job.job_id,
+        entries_done: job.entries_done,
+        bytes_done: job.bytes_done,
+        skipped: 0,
+        failures: Vec::new(),
+        cancelled,
+        error: error.filter(|_| !cancelled),
+        destination: dest.to_string_lossy().to_string(),
+    }
+}
+
+/// Run `work` on a background thread as job `job_id`, sending its report to
+/// the calling window when done.
+fn spawn(
+    app: AppHandle,
+    webview: tauri::Webview,
+    job_id: String,
+    work: impl FnOnce(Job) -> ArchiveReport + Send + 'static,
+) {
+    let cancelled = Arc::new(AtomicBool::new(false));
+    if let Some(previous) = jobs()
+        .lock()
+        .unwrap()
+        .insert(job_id.clone(), cancelled.clone())
+    {
+        previous.store(true, Ordering::SeqCst);
+    }
+    let label = webview.label().to_string();
+    let mut job = Job::new(job_id.clone(), cancelled.clone());
+    job.target = Some((app.clone(), label.clone()));
+    std::thread::spawn(move || {
+        let report = work(job);
+        {
+            let mut jobs = jobs().lock().unwrap();
+            if jobs
+                .get(&job_id)
+                .is_some_and(|current| Arc::ptr_eq(current, &cancelled))
+            {
+                jobs.remove(&job_id);
+            }
+        }
+        let _ = app.emit_to(label.as_str(), "fs-archive-done", report);
+    });
+}
+
+/// Extract `path` into `dest_dir`, creating it if needed. `entries` limits
+/// extraction to those entries (and what's inside them), which then land
+/// directly in `dest_dir`. Existing files are skipped unless `overwrite`.
+#[tauri::command]
+pub fn fs_archive_extract(
+    app: AppHandle,
+    webview: tauri::Webview,
+    job_id: String,
+    path: String,
+    dest_dir: String,
+    entries: Option<Vec<String>>,
+    overwrite: Option<bool>,
+) -> Result<(), String> {
+    fs_scope::check(&path)?;
+    fs_scope::check(&dest_dir)?;
+    ArchiveFormat::of(Path::new(&path))?;
+    let selection: Vec<String> = entries
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| normalize(entry))
+        .collect();
+    let overwrite = overwrite.unwrap_or(false);
+    spawn(app, webview, job_id, move |job| {
+        extract(
+            job,
+            Path::new(&path),
+            Path::new(&dest_dir),
+            &selection,
+            overwrite,
+        )
+    });
+    Ok(())
+}
+
+/// Pack `sources` into a new archive at `dest`, whose name picks the format:
+/// .zip, .tar, .tar.gz/.tgz or .tar.zst/.tzst.
+#[tauri::command]
+pub fn fs_archive_create(
+    app: AppHandle,
+    webview: tauri::Webview,
+    job_id: String,
+    sources: Vec<String>,
+    dest: String,
+) -> Result<(), String> {
+    if sources.is_empty() {
+        return Err("Nothing to archive".to_string());
+    }
+    for source in &sources {
+        fs_scope::check_tree(source)?;
+    }
+    fs_scope::check(&dest)?;
+    ArchiveFormat::of(Path::new(&dest))?;
+    if Path::new(&dest).exists() {
+        return Err(format!("Already exists: {}", dest));
+    }
+    spawn(app, webview, job_id, move |job| {
+        create(job, &sources, Path::new(&dest))
+    });
+    Ok(())
+}
+
+/// Cancel a running extraction or creation. Returns false if no job with
+/// that id is running.
+#[tauri::command]
+pub fn fs_archive_cancel(job_id: String) -> bool {
+    match jobs().lock().unwrap().remove(&job_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("pocketpaw-archive-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn link_header(kind: tar::EntryType, link: &str) -> tar::Header {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(kind);
+        header.set_size(0);
+        header.set_mode(0o777);
+        header.set_link_name(link).unwrap();
+        header
+    }
+
+    #[test]
+    fn normalize_keeps_names_inside() {
+        assert_eq!(normalize("a/b/c.txt").as_deref(), Some("a/b/c.txt"));
+        assert_eq!(normalize("./a//b/").as_deref(), Some("a/b"));
+        assert_eq!(normalize("a\\b").as_deref(), Some("a/b"));
+        assert_eq!(normalize("/etc/passwd").as_deref(), Some("etc/passwd"));
+        assert_eq!(normalize("a/../../b"), None);
+        assert_eq!(normalize(".."), None);
+        assert_eq!(normalize("./"), None);
+    }
+
+    #[test]
+    fn link_stays_inside_checks_the_text() {
+        assert!(link_stays_inside("a/link", "b"));
+        assert!(link_stays_inside("a/link", "../b"));
+        assert!(link_stays_inside("link", "."));
+        assert!(!link_stays_inside("link", ".."));
+        assert!(!link_stays_inside("a/link", "../../b"));
+        assert!(!link_stays_inside("link", "/etc/passwd"));
+        assert!(!link_stays_inside("link", ""));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn chained_links_cannot_leave_the_destination() {
+        let root = temp_dir("chain");
+        let home = root.join("home");
+        fs::create_dir_all(home.join(".ssh")).unwrap();
+        fs::write(home.join(".ssh/id_rsa"), "secret").unwrap();
+        let dest = home.join("out/x");
+
+        let archive = root.join("evil.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&archive).unwrap());
+        for (name, link) in [("s", "."), ("b", "s/.."), ("c", "b/..")] {
+            let mut header = link_header(tar::EntryType::Symlink, link);
+            builder.append_data(&mut header, name, io::empty()).unwrap();
+        }
+        let mut header = link_header(tar::EntryType::Link, "c/.ssh/id_rsa");
+        builder
+            .append_data(&mut header, "stolen", io::empty())
+            .unwrap();
+        builder.finish().unwrap();
+        drop(builder);
+
+        let job = Job::new("test".to_string(), Arc::new(AtomicBool::new(false)));
+        let report = extract(job, &archive, &dest, &[], false);
+        assert!(report.error.is_none());
+        assert!(fs::symlink_metadata(dest.join("s")).unwrap().is_symlink());
+        assert!(fs::symlink_metadata(dest.join("b")).is_err());
+        assert!(fs::symlink_metadata(dest.join("c")).is_err());
+        assert!(fs::symlink_metadata(dest.join("stolen")).is_err());
+        assert_eq!(report.failures.len(), 3);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dangling_links_cannot_be_redirected_later() {
+        let root = temp_dir("dangling");
+        let dest = root.join("out");
+        let archive = root.join("evil.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&archive).unwrap());
+        // `a` first, while `x` doesn't exist, then `x` pointing at the
+        // destination itself
+        for (name, link) in [("a", "x/.."), ("x", ".")] {
+            let mut header = link_header(tar::EntryType::Symlink, link);
+            builder.append_data(&mut header, name, io::empty()).unwrap();
+        }
+        builder.finish().unwrap();
+        drop(builder);
+
+        let job = Job::new("test".to_string(), Arc::new(AtomicBool::new(false)));
+        let report = extract(job, &archive, &dest, &[], false);
+        assert!(fs::symlink_metadata(dest.join("a")).is_err());
+        assert!(fs::symlink_metadata(dest.join("x")).unwrap().is_symlink());
+        assert_eq!(report.failures.len(), 1);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn hard_links_copy_extracted_files() {
+        let root = temp_dir("hard");
+        let dest = root.join("out");
+        let archive = root.join("ok.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&archive).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "dir/file", &b"hello"[..])
+            .unwrap();
+        let mut header = link_header(tar::EntryType::Link, "dir/file");
+        builder
+            .append_data(&mut header, "copy", io::empty())
+            .unwrap();
+        builder.finish().unwrap();
+        drop(builder);
+
+        let job = Job::new("test".to_string(), Arc::new(AtomicBool::new(false)));
+        let report = extract(job, &archive, &dest, &[], false);
+        assert!(report.failures.is_empty());
+        assert_eq!(fs::read_to_string(dest.join("copy")).unwrap(), "hello");
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -1,5 +1,10 @@
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD as BASE64;
+use crate::fs_journal;
+use crate::fs_read;
+use crate::fs_scope;
+use crate::fs_transfer;
+use crate::fs_walk::{self, WalkOptions};
 use serde::Serialize;
 use std::fs;
 use std::path::Path;
@@ -69,6 +74,7 @@ fn build_entry(path: &Path) -> Result<FileEntry, String> {
 
 #[tauri::command]
 pub fn fs_read_dir(path: String) -> Result<Vec<FileEntry>, String> {
+    fs_scope::check(&path)?;
     let entries = fs::read_dir(&path).map_err(|e| format!("Failed to read dir: {}", e))?;
     let mut result = Vec::new();
     for entry in entries {
@@ -88,52 +94,64 @@ pub fn fs_read_dir(path: String) -> Result<Vec<FileEntry>, String> {
     Ok(result)
 }
 
+/// Read a file as strict UTF-8, so saving it back with `fs_write_file` keeps
+/// its bytes. Other encodings go through `fs_read_file_decoded`.
 #[tauri::command]
 pub fn fs_read_file_text(path: String) -> Result<String, String> {
+    fs_scope::check(&path)?;
     fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e))
 }
 
 #[tauri::command]
-pub fn fs_write_file(path: String, content: String) -> Result<(), String> {
-    fs::write(&path, content).map_err(|e| format!("Failed to write file: {}", e))
-}
-
-#[tauri::command]
-pub fn fs_delete(path: String, recursive: bool) -> Result<(), String> {
-    let p = Path::new(&path);
-    if p.is_dir() {
-        if recursive {
-            fs::remove_dir_all(p).map_err(|e| format!("Failed to delete dir: {}", e))
-        } else {
-            fs::remove_dir(p).map_err(|e| format!("Failed to delete dir: {}", e))
-        }
-    } else {
-        fs::remove_file(p).map_err(|e| format!("Failed to delete file: {}", e))
-    }
+pub fn fs_rename(old_path: String, new_path: String) -> Result<(), String> {
+    fs_scope::check_tree(&old_path)?;
+    fs_scope::check(&new_path)?;
+    fs::rename(&old_path, &new_path).map_err(|e| format!("Failed to rename: {}", e))?;
+    fs_journal::record_rename(Path::new(&old_path), Path::new(&new_path));
+    Ok(())
 }
 
+/// Move a file or directory, falling back to copy, verify and delete when
+/// `new_path` is on another filesystem.
 #[tauri::command]
-pub fn fs_rename(old_path: String, new_path: String) -> Result<(), String> {
-    fs::rename(&old_path, &new_path).map_err(|e| format!("Failed to rename: {}", e))
+pub fn fs_move(old_path: String, new_path: String) -> Result<(), String> {
+    fs_scope::check_tree(&old_path)?;
+    fs_scope::check(&new_path)?;
+    fs_transfer::move_path(Path::new(&old_path), Path::new(&new_path))?;
+    fs_journal::record_rename(Path::new(&old_path), Path::new(&new_path));
+    Ok(())
 }
 
 #[tauri::command]
 pub fn fs_stat(path: String) -> Result<FileEntry, String> {
+    fs_scope::check(&path)?;
     build_entry(Path::new(&path))
 }
 
 #[tauri::command]
 pub fn fs_create_dir(path: String) -> Result<(), String> {
-    fs::create_dir_all(&path).map_err(|e| format!("Failed to create dir: {}", e))
+    fs_scope::check(&path)?;
+    // Remember which levels are new so undo removes only those
+    let mut created: Vec<std::path::PathBuf> = Path::new(&path)
+        .ancestors()
+        .take_while(|p| !p.as_os_str().is_empty() && !p.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    created.reverse();
+    fs::create_dir_all(&path).map_err(|e| format!("Failed to create dir: {}", e))?;
+    fs_journal::record_create_dirs(created);
+    Ok(())
 }
 
 #[tauri::command]
 pub fn fs_exists(path: String) -> Result<bool, String> {
+    fs_scope::check(&path)?;
     Ok(Path::new(&path).exists())
 }
 
 #[tauri::command]
 pub fn fs_read_file_base64(path: String) -> Result<String, String> {
+    fs_scope::check(&path)?;
     let p = Path::new(&path);
     let data = fs::read(p).map_err(|e| format!("Failed to read file: {}", e))?;
     let ext = p
@@ -156,17 +174,19 @@ pub fn fs_read_file_base64(path: String) -> Result<String, String> {
     Ok(format!("data:{};base64,{}", mime, b64))
 }
 
-/// Read up to `max_bytes` from the beginning of a file and return as UTF-8 text.
-/// Invalid UTF-8 sequences are replaced with the Unicode replacement character.
+/// Read up to `max_bytes` from the beginning of a file and return it as text,
+/// decoded with the detected encoding. Invalid sequences are replaced with the
+/// Unicode replacement character.
 #[tauri::command]
 pub fn fs_read_file_head(path: String, max_bytes: usize) -> Result<String, String> {
     use std::io::Read;
+    fs_scope::check(&path)?;
     let file = fs::File::open(&path).map_err(|e| format!("Failed to open file: {}", e))?;
     let mut reader = std::io::BufReader::new(file);
     let mut buf = vec![0u8; max_bytes];
     let n = reader.read(&mut buf).map_err(|e| format!("Failed to read file: {}", e))?;
     buf.truncate(n);
-    Ok(String::from_utf8_lossy(&buf).into_owned())
+    fs_read::decode(&buf, None).map(|decoded| decoded.text)
 }
 
 /// Resolve a (possibly relative) path against an optional base directory.
@@ -238,17 +258,25 @@ pub fn fs_get_default_dirs() -> Result<DefaultDirs, String> {
 
 #[tauri::command]
 pub fn fs_copy_file(src: String, dest: String) -> Result<(), String> {
+    fs_scope::check(&src)?;
+    fs_scope::check(&dest)?;
+    let dest_path = Path::new(&dest);
+    let before = fs_journal::capture(dest_path);
     fs::copy(&src, &dest)
         .map(|_| ())
-        .map_err(|e| format!("Failed to copy file: {}", e))
+        .map_err(|e| format!("Failed to copy file: {}", e))?;
+    fs_journal::record_replace("copy", dest_path, before, fs_journal::capture(dest_path));
+    Ok(())
 }
 
-fn copy_dir_recursive(src: &Path, dest: &Path) -> Result<(), String> {
+pub(crate) fn copy_dir_recursive(src: &Path, dest: &Path) -> Result<(), String> {
     fs::create_dir_all(dest).map_err(|e| format!("Failed to create dir {}: {}", dest.display(), e))?;
     for entry in fs::read_dir(src).map_err(|e| format!("Failed to read dir: {}", e))? {
         let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
         let entry_path = entry.path();
         let dest_path = dest.join(entry.file_name());
+        // Symlinks inside the tree may point outside the scope
+        fs_scope::check(&entry_path)?;
         if entry_path.is_dir() {
             copy_dir_recursive(&entry_path, &dest_path)?;
         } else {
@@ -261,11 +289,22 @@ fn copy_dir_recursive(src: &Path, dest: &Path) -> Result<(), String> {
 
 #[tauri::command]
 pub fn fs_copy_dir(src: String, dest: String) -> Result<(), String> {
-    copy_dir_recursive(Path::new(&src), Path::new(&dest))
+    fs_scope::check_tree(&src)?;
+    fs_scope::check(&dest)?;
+    let (src, dest) = (Path::new(&src), Path::new(&dest));
+    // Copying into an existing directory merges, which can't be undone
+    // by removing the destination
+    let fresh = !dest.exists();
+    copy_dir_recursive(src, dest)?;
+    if fresh {
+        fs_journal::record_copy_dir(src, dest);
+    }
+    Ok(())
 }
 
 #[tauri::command]
 pub fn fs_stat_extended(path: String) -> Result<FileStatExtended, String> {
+    fs_scope::check(&path)?;
     let p = Path::new(&path);
     let symlink_meta = fs::symlink_metadata(p)
         .map_err(|e| format!("Failed to stat {}: {}", p.display(), e))?;
@@ -317,43 +356,38 @@ pub struct RecursiveSearchResult {
     pub truncated: bool,
 }
 
+/// Find entries whose name contains `query` under `root_path`. Honors
+/// .gitignore and friends unless `respect_ignore` is false, and skips hidden
+/// entries unless `include_hidden` is true.
 #[tauri::command]
 pub fn fs_search_recursive(
     root_path: String,
     query: String,
     max_results: Option<usize>,
     max_depth: Option<usize>,
+    respect_ignore: Option<bool>,
+    include_hidden: Option<bool>,
 ) -> Result<RecursiveSearchResult, String> {
-    use walkdir::WalkDir;
-
+    fs_scope::check(&root_path)?;
     let max_results = max_results.unwrap_or(500);
-    let max_depth = max_depth.unwrap_or(10);
     let query_lower = query.to_lowercase();
-
-    static SKIP_DIRS: &[&str] = &[
-        ".git", "node_modules", "target", "__pycache__", ".venv",
-        ".next", ".nuxt", "dist", "build", ".cache", ".svelte-kit",
-    ];
+    let options = WalkOptions {
+        respect_ignore: respect_ignore.unwrap_or(true),
+        include_hidden: include_hidden.unwrap_or(false),
+        max_depth: Some(max_depth.unwrap_or(10)),
+    };
 
     let mut entries = Vec::with_capacity(256);
     let mut total_scanned: u64 = 0;
     let mut truncated = false;
 
-    let walker = WalkDir::new(&root_path)
-        .max_depth(max_depth)
-        .follow_links(false)
-        .into_iter();
-
-    for entry in walker.filter_entry(|e| {
-        if e.file_type().is_dir() {
-            if let Some(name) = e.file_name().to_str() {
-                if name.starts_with('.') || SKIP_DIRS.contains(&name) {
-                    return false;
-                }
-            }
-        }
-        true
-    }) {
+    // Denied folders (~/.ssh, ...) are pruned; denied files are left out below
+    let walker = fs_walk::builder(Path::new(&root_path), &options, |e| {
+        !e.file_type().is_some_and(|t| t.is_dir()) || fs_scope::allows(e.path())
+    })
+    .build();
+
+    for entry in walker {
         let entry = match entry {
             Ok(e) => e,
             Err(_) => continue,
@@ -367,12 +401,9 @@ pub fn fs_search_recursive(
 
         let name_os = entry.file_name();
         let name = name_os.to_string_lossy();
-        if name.starts_with('.') {
-            continue;
-        }
 
-        if name.to_lowercase().contains(&query_lower) {
-            // Use walkdir's cached metadata instead of a second stat() syscall
+        if name.to_lowercase().contains(&query_lower) && fs_scope::allows(entry.path()) {
+            // Use the walker's cached metadata instead of a second stat() syscall
             let meta = match entry.metadata() {
                 Ok(m) => m,
                 Err(_) => continue,
@@ -414,6 +445,7 @@ pub fn fs_search_recursive(
 
 #[tauri::command]
 pub fn fs_open_in_terminal(path: String) -> Result<(), String> {
+    fs_scope::check(&path)?;
     let p = Path::new(&path);
     let dir = if p.is_dir() { p } else { p.parent().unwrap_or(p) };
     let dir_str = dir.to_string_lossy().to_string();
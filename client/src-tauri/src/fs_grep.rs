@@ -0,0 +1,377 @@
+// Full-text search across the files under a directory.
+//
+// `fs_grep` validates the options, then searches on a background thread and
+// returns straight away. Files with matches are emitted to the calling window
+// as `fs-grep-result` events as they are found, followed by one `fs-grep-done`
+// event. Every event carries the caller-chosen search id, which is also what
+// `fs_grep_cancel` takes; starting a search with an id that is still running
+// cancels the old one.
+//
+// Files are decoded like `fs_read_file_text` (so UTF-16 and legacy encodings
+// are searched too); files with NUL bytes in their first 8 KiB are treated as
+// binary and skipped.
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{AppHandle, Emitter};
+
+use crate::fs_read;
+use crate::fs_scope;
+use crate::fs_walk::{self, WalkOptions};
+
+/// Bytes checked for NULs to tell binary files apart.
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+/// Matched lines are cut to this many characters.
+const MAX_LINE_CHARS: usize = 1000;
+const MAX_CONTEXT_LINES: usize = 10;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct GrepOptions {
+    pub root_path: String,
+    pub query: String,
+    /// Treat `query` as a regular expression rather than literal text.
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Only search files whose path relative to the root matches one of these
+    /// globs, e.g. `**/*.rs`. A pattern without `/` matches file names.
+    pub include: Vec<String>,
+    /// Skip files and directories matching one of these globs.
+    pub exclude: Vec<String>,
+    /// Skip what .gitignore / .ignore / git excludes ignore.
+    pub respect_ignore: bool,
+    /// Also search dot files and dot directories.
+    pub include_hidden: bool,
+    /// Files larger than this are skipped.
+    pub max_file_size: u64,
+    /// Stop after this many matching lines in total.
+    pub max_results: usize,
+    /// Lines of context before and after each match.
+    pub context: usize,
+}
+
+impl Default for GrepOptions {
+    fn default() -> Self {
+        Self {
+            root_path: String::new(),
+            query: String::new(),
+            regex: false,
+            case_sensitive: false,
+            whole_word: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            respect_ignore: true,
+            include_hidden: false,
+            max_file_size: 10 * 1024 * 1024,
+            max_results: 2000,
+            context: 0,
+        }
+    }
+}
+
+/// A matching line. Match ranges are in UTF-16 code units, ready for
+/// `String.prototype.slice` in the webview.
+#[derive(Debug, Serialize, Clone)]
+pub struct GrepMatch {
+    /// 1-based.
+    pub line_number: usize,
+    pub line: String,
+    pub ranges: Vec<(usize, usize)>,
+    pub context_before: Vec<String>,
+    pub context_after: Vec<String>,
+}
+
+/// Payload of `fs-grep-result`: the matches in one file.
+#[derive(Debug, Serialize, Clone)]
+pub struct GrepFileResult {
+    pub search_id: String,
+    pub path: String,
+    pub matches: Vec<GrepMatch>,
+}
+
+/// Payload of `fs-grep-done`.
+#[derive(Debug, Serialize, Clone)]
+pub struct GrepDone {
+    pub search_id: String,
+    pub files_searched: u64,
+    pub files_matched: u64,
+    pub matches: usize,
+    /// Stopped at `max_results`.
+    pub truncated: bool,
+    pub cancelled: bool,
+}
+
+/// Cancel flags of the running searches, by search id.
+fn searches() -> &'static Mutex<HashMap<String, Arc<AtomicBool>>> {
+    static SEARCHES: OnceLock<Mutex<HashMap<String, Arc<AtomicBool>>>> = OnceLock::new();
+    SEARCHES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn build_regex(options: &GrepOptions) -> Result<Regex, String> {
+    if options.query.is_empty() {
+        return Err("Search query is empty".to_string());
+    }
+    let mut pattern = if options.regex {
+        options.query.clone()
+    } else {
+        regex::escape(&options.query)
+    };
+    if options.whole_word {
+        pattern = format!(r"\b(?:{})\b", pattern);
+    }
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid search pattern: {}", e))
+}
+
+/// Patterns without a `/` apply to the file name at any depth.
+fn build_globs(patterns: &[String]) -> Result<Option<GlobSet>, String> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let pattern = if pattern.contains('/') {
+            pattern.clone()
+        } else {
+            format!("**/{}", pattern)
+        };
+        let glob = Glob::new(&pattern).map_err(|e| format!("Invalid glob {}: {}", pattern, e))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| format!("Invalid glob: {}", e))
+}
+
+fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(BINARY_SNIFF_BYTES)];
+    let (encoding, _) = fs_read::detect(sample, sample.len() == bytes.len());
+    // UTF-16 text is full of NULs
+    encoding != encoding_rs::UTF_16LE && encoding != encoding_rs::UTF_16BE && sample.contains(&0)
+}
+
+fn utf16_len(s: &str) -> usize {
+    s.chars().map(char::len_utf16).sum()
+}
+
+/// `line` cut to `MAX_LINE_CHARS` characters.
+fn clip(line: &str) -> &str {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((end, _)) => &line[..end],
+        None => line,
+    }
+}
+
+/// Matching lines of `text`, at most `limit` of them.
+fn search_text(text: &str, re: &Regex, context: usize, limit: usize) -> Vec<GrepMatch> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut matches = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if matches.len() >= limit {
+            break;
+        }
+        let shown = clip(line);
+        let ranges: Vec<(usize, usize)> = re
+            .find_iter(line)
+            .filter(|m| m.end() <= shown.len())
+            .map(|m| (utf16_len(&line[..m.start()]), utf16_len(&line[..m.end()])))
+            .collect();
+        if ranges.is_empty() && !re.is_match(line) {
+            continue;
+        }
+        let before = i.saturating_sub(context);
+        let after = (i + 1 + context).min(lines.len());
+        matches.push(GrepMatch {
+            line_number: i + 1,
+            line: shown.to_string(),
+            ranges,
+            context_before: lines[before..i]
+                .iter()
+                .map(|l| clip(l).to_string())
+                .collect(),
+            context_after: lines[i + 1..after]
+                .iter()
+                .map(|l| clip(l).to_string())
+                .collect(),
+        });
+    }
+    matches
+}
+
+/// A search running on its own thread.
+struct Search {
+    id: String,
+    /// Label of the window the events go to.
+    target: String,
+    options: GrepOptions,
+    re: Regex,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Search {
+    fn run(&self, app: &AppHandle) -> GrepDone {
+        let Search {
+            id: search_id,
+            target,
+            options,
+            re,
+            include,
+            exclude,
+            cancelled,
+        } = self;
+        let (include, exclude) = (include.as_ref(), exclude.as_ref());
+        let root = Path::new(&options.root_path);
+        let mut done = GrepDone {
+            search_id: search_id.clone(),
+            files_searched: 0,
+            files_matched: 0,
+            matches: 0,
+            truncated: false,
+            cancelled: false,
+        };
+        let relative = |path: &Path| path.strip_prefix(root).unwrap_or(path).to_path_buf();
+
+        let walk_options = WalkOptions {
+            respect_ignore: options.respect_ignore,
+            include_hidden: options.include_hidden,
+            max_depth: None,
+        };
+        let filter_root = root.to_path_buf();
+        let filter_exclude = exclude.cloned();
+        let walker = fs_walk::builder(root, &walk_options, move |e| {
+            e.depth() == 0
+                || !filter_exclude.as_ref().is_some_and(|set| {
+                    set.is_match(e.path().strip_prefix(&filter_root).unwrap_or(e.path()))
+                })
+        })
+        .build();
+        for entry in walker {
+            if cancelled.load(Ordering::SeqCst) {
+                done.cancelled = true;
+                break;
+            }
+            let Ok(entry) = entry else { continue };
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let path = entry.path();
+            if include.is_some_and(|set| !set.is_match(relative(path))) {
+                continue;
+            }
+            if entry
+                .metadata()
+                .map_or(true, |m| m.len() > options.max_file_size)
+            {
+                continue;
+            }
+            if !fs_scope::allows(path) {
+                continue;
+            }
+            let Ok(bytes) = fs::read(path) else { continue };
+            done.files_searched += 1;
+            if is_binary(&bytes) {
+                continue;
+            }
+            let Ok(decoded) = fs_read::decode(&bytes, None) else {
+                continue;
+            };
+
+            let limit = options.max_results - done.matches;
+            let matches = search_text(&decoded.text, re, options.context, limit);
+            if matches.is_empty() {
+                continue;
+            }
+            done.files_matched += 1;
+            done.matches += matches.len();
+            let _ = app.emit_to(
+                target.as_str(),
+                "fs-grep-result",
+                GrepFileResult {
+                    search_id: search_id.clone(),
+                    path: path.to_string_lossy().to_string(),
+                    matches,
+                },
+            );
+            if done.matches >= options.max_results {
+                done.truncated = true;
+                break;
+            }
+        }
+        done
+    }
+}
+
+/// Start searching file contents under `options.root_path`. Results arrive as
+/// `fs-grep-result` / `fs-grep-done` events tagged with `search_id`. Invalid
+/// options are reported here rather than as events.
+#[tauri::command]
+pub fn fs_grep(
+    app: AppHandle,
+    webview: tauri::Webview,
+    search_id: String,
+    mut options: GrepOptions,
+) -> Result<(), String> {
+    fs_scope::check(&options.root_path)?;
+    let re = build_regex(&options)?;
+    let include = build_globs(&options.include)?;
+    let exclude = build_globs(&options.exclude)?;
+    options.context = options.context.min(MAX_CONTEXT_LINES);
+    options.max_results = options.max_results.max(1);
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    if let Some(previous) = searches()
+        .lock()
+        .unwrap()
+        .insert(search_id.clone(), cancelled.clone())
+    {
+        previous.store(true, Ordering::SeqCst);
+    }
+
+    let search = Search {
+        id: search_id,
+        target: webview.label().to_string(),
+        options,
+        re,
+        include,
+        exclude,
+        cancelled,
+    };
+    std::thread::spawn(move || {
+        let done = search.run(&app);
+        {
+            let mut searches = searches().lock().unwrap();
+            if searches
+                .get(&search.id)
+                .is_some_and(|c| Arc::ptr_eq(c, &search.cancelled))
+            {
+                searches.remove(&search.id);
+            }
+        }
+        let _ = app.emit_to(search.target.as_str(), "fs-grep-done", done);
+    });
+    Ok(())
+}
+
+/// Cancel a running search. Returns false if no search with that id is
+/// running.
+#[tauri::command]
+pub fn fs_grep_cancel(search_id: String) -> bool {
+    match searches().lock().unwrap().remove(&search_id) {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,815 @@
+// Persistent file index for instant fuzzy file search.
+//
+// Each indexed root keeps the relative path, size, mtime and kind of every
+// entry below it, walked like the other recursive commands (so .gitignored and
+// hidden entries are left out). The entries are held in memory sorted by path
+// and saved under ~/.pocketpaw/cache/index/, so after a restart queries are
+// answered from the saved list straight away while a background rescan
+// catches up. Between rescans a notify watcher keeps the index current; if
+// the root can't be watched (inotify limits, network filesystems) it is
+// rescanned periodically instead.
+//
+// `fs_index_query` scores every entry with nucleo's fzf-style matcher on its
+// relative path, adds the file name's own score when the name matches, and
+// boosts files recently opened through `fs_index_record_open`. Scoring is
+// split across threads; a 500k entry index answers in milliseconds.
+//
+// Indexed roots are listed in ~/.pocketpaw/client_fs_index.json. Without that
+// file the home directory is indexed.
+use notify::{RecursiveMode, Watcher};
+use nucleo_matcher::pattern::{CaseMatching, Normalization, Pattern};
+use nucleo_matcher::{Config, Matcher, Utf32Str};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::fs_scope;
+use crate::fs_walk::{self, WalkOptions};
+use crate::fs_watcher;
+use crate::fs_write;
+
+const ROOTS_FILE: &str = "client_fs_index.json";
+const RECENT_FILE: &str = "client_recent_files.json";
+/// First line of a saved index; bump when the format changes.
+const INDEX_HEADER: &str = "pocketpaw-index 1";
+/// Recently opened files remembered for the ranking boost.
+const MAX_RECENT: usize = 500;
+/// Changes are saved at most this often.
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+/// Events arriving within this window are applied together.
+const BATCH_WINDOW: Duration = Duration::from_millis(500);
+/// Rescan interval for roots that can't be watched.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_LIMIT: usize = 50;
+
+struct Entry {
+    /// Relative to the root, with native separators.
+    path: Box<str>,
+    size: u64,
+    mtime: u64,
+    is_dir: bool,
+}
+
+impl Entry {
+    fn name(&self) -> &str {
+        self.path
+            .rsplit(MAIN_SEPARATOR)
+            .next()
+            .unwrap_or(&self.path)
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexState {
+    /// Scanning; queries use the saved (possibly stale) entries meanwhile.
+    Building,
+    Ready,
+    Error,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct IndexStatus {
+    pub root: String,
+    pub state: IndexState,
+    pub entries: usize,
+    /// Unix seconds of the last completed scan, 0 if none yet.
+    pub scanned_at: u64,
+    pub error: Option<String>,
+}
+
+/// A query result. `indices` are the matched positions in `relative_path`, in
+/// UTF-16 code units.
+#[derive(Debug, Serialize)]
+pub struct IndexHit {
+    pub path: String,
+    pub relative_path: String,
+    pub root: String,
+    pub name: String,
+    pub extension: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: u64,
+    pub score: u32,
+    pub indices: Vec<u32>,
+}
+
+struct Root {
+    path: PathBuf,
+    /// Sorted by `path`.
+    entries: RwLock<Vec<Entry>>,
+    status: Mutex<IndexStatus>,
+    stop: AtomicBool,
+}
+
+impl Root {
+    fn set_state(&self, state: IndexState, error: Option<String>) {
+        let mut status = self.status.lock().unwrap();
+        status.state = state;
+        status.error = error;
+        status.entries = self.entries.read().unwrap().len();
+        if state == IndexState::Ready {
+            status.scanned_at = now_secs();
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RootsConfig {
+    roots: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct RecentFile {
+    last_opened: u64,
+    count: u32,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn mtime_secs(meta: &fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn pocketpaw_dir() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Could not determine home directory")?;
+    Ok(home.join(".pocketpaw"))
+}
+
+fn roots() -> &'static Mutex<Vec<Arc<Root>>> {
+    static ROOTS: OnceLock<Mutex<Vec<Arc<Root>>>> = OnceLock::new();
+    ROOTS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+fn recent() -> &'static Mutex<HashMap<String, RecentFile>> {
+    static RECENT: OnceLock<Mutex<HashMap<String, RecentFile>>> = OnceLock::new();
+    RECENT.get_or_init(|| {
+        let recent = pocketpaw_dir()
+            .and_then(|dir| fs::read_to_string(dir.join(RECENT_FILE)).map_err(|e| e.to_string()))
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        Mutex::new(recent)
+    })
+}
+
+fn save_json<T: Serialize>(name: &str, value: &T) -> Result<(), String> {
+    let dir = pocketpaw_dir()?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create dir: {}", e))?;
+    let data =
+        serde_json::to_vec_pretty(value).map_err(|e| format!("Failed to serialize: {}", e))?;
+    fs_write::write_atomic(&dir.join(name), &data)
+}
+
+fn save_roots() -> Result<(), String> {
+    let config = RootsConfig {
+        roots: roots()
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|root| root.path.to_string_lossy().to_string())
+            .collect(),
+    };
+    save_json(ROOTS_FILE, &config)
+}
+
+fn index_file(root: &Path) -> Result<PathBuf, String> {
+    let hash = fs_write::sha256_hex(root.to_string_lossy().as_bytes());
+    Ok(pocketpaw_dir()?
+        .join("cache")
+        .join("index")
+        .join(format!("{}.idx", &hash[..16])))
+}
+
+/// Write the entries as `<d|f>\t<size>\t<mtime>\t<path>` lines.
+fn save_index(root: &Root) -> Result<(), String> {
+    let file = index_file(&root.path)?;
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
+    }
+    let tmp = fs_write::temp_path(&file)?;
+    let written = fs::File::create(&tmp).and_then(|out| {
+        let mut out = BufWriter::new(out);
+        writeln!(out, "{}\t{}", INDEX_HEADER, root.path.to_string_lossy())?;
+        for entry in root.entries.read().unwrap().iter() {
+            if entry.path.contains('\n') {
+                continue;
+            }
+            let kind = if entry.is_dir { 'd' } else { 'f' };
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}",
+                kind, entry.size, entry.mtime, entry.path
+            )?;
+        }
+        out.into_inner()?.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("Failed to save index: {}", e));
+    }
+    fs_write::replace_with(&tmp, &file)
+}
+
+fn load_index(root: &Path) -> Option<Vec<Entry>> {
+    let file = fs::File::open(index_file(root).ok()?).ok()?;
+    let mut lines = BufReader::new(file).lines();
+    let header = lines.next()?.ok()?;
+    if header != format!("{}\t{}", INDEX_HEADER, root.to_string_lossy()) {
+        return None;
+    }
+    let mut entries = Vec::new();
+    for line in lines {
+        let line = line.ok()?;
+        let mut fields = line.splitn(4, '\t');
+        let (kind, size, mtime, path) = (
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+            fields.next()?,
+        );
+        entries.push(Entry {
+            path: path.into(),
+            size: size.parse().ok()?,
+            mtime: mtime.parse().ok()?,
+            is_dir: kind == "d",
+        });
+    }
+    entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    Some(entries)
+}
+
+fn entry_for(root: &Path, path: &Path, meta: &fs::Metadata) -> Option<Entry> {
+    let relative = path.strip_prefix(root).ok()?;
+    if relative.as_os_str().is_empty() {
+        return None;
+    }
+    Some(Entry {
+        path: relative.to_string_lossy().into(),
+        size: if meta.is_dir() { 0 } else { meta.len() },
+        mtime: mtime_secs(meta),
+        is_dir: meta.is_dir(),
+    })
+}
+
+/// Walk `dir` (the root or a directory inside it) down to `max_depth` and
+/// return its entries. Directories outside the fs scope are skipped. Returns
+/// `None` if stopped.
+fn walk(root: &Root, dir: &Path, max_depth: Option<usize>) -> Option<Vec<Entry>> {
+    let options = WalkOptions {
+        max_depth,
+        ..WalkOptions::default()
+    };
+    let walker = fs_walk::builder(dir, &options, |e| {
+        !e.file_type().is_some_and(|t| t.is_dir()) || fs_scope::allows(e.path())
+    })
+    .build();
+    let mut entries = Vec::new();
+    for entry in walker.flatten() {
+        if root.stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        if let Ok(meta) = entry.metadata() {
+            entries.extend(entry_for(&root.path, entry.path(), &meta));
+        }
+    }
+    Some(entries)
+}
+
+fn scan(root: &Root) {
+    root.set_state(IndexState::Building, None);
+    if let Err(e) = fs_scope::check(&root.path) {
+        root.set_state(IndexState::Error, Some(e.to_string()));
+        return;
+    }
+    let Some(mut entries) = walk(root, &root.path, None) else {
+        return;
+    };
+    entries.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+    *root.entries.write().unwrap() = entries;
+    root.set_state(IndexState::Ready, None);
+    if let Err(e) = save_index(root) {
+        log::warn!(
+            "Failed to save file index for {}: {}",
+            root.path.display(),
+            e
+        );
+    }
+}
+
+/// Cheap check for event paths in hidden directories, which the walker
+/// skips anyway.
+fn hidden(relative: &Path) -> bool {
+    relative
+        .components()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+}
+
+fn remove_subtree(entries: &mut Vec<Entry>, relative: &str) {
+    if let Ok(i) = entries.binary_search_by(|e| e.path.as_ref().cmp(relative)) {
+        entries.remove(i);
+    }
+    let prefix = format!("{}{}", relative, MAIN_SEPARATOR);
+    let start = entries.partition_point(|e| e.path.as_ref() < prefix.as_str());
+    let end = start
+        + entries[start..]
+            .iter()
+            .take_while(|e| e.path.starts_with(&prefix))
+            .count();
+    entries.drain(start..end);
+}
+
+/// Insert or update an entry; returns true if it is new.
+fn upsert(entries: &mut Vec<Entry>, entry: Entry) -> bool {
+    match entries.binary_search_by(|e| e.path.cmp(&entry.path)) {
+        Ok(i) => {
+            entries[i] = entry;
+            false
+        }
+        Err(i) => {
+            entries.insert(i, entry);
+            true
+        }
+    }
+}
+
+/// Bring the entries for `paths` up to date with the disk. Paths not yet in
+/// the index are picked up by listing their directory with the walker, so
+/// ignore files and the fs scope apply to them as in a full scan.
+fn apply_changes(root: &Root, paths: HashSet<PathBuf>) {
+    let mut listed = HashSet::new();
+    for path in paths {
+        let Ok(relative) = path.strip_prefix(&root.path) else {
+            continue;
+        };
+        if relative.as_os_str().is_empty() || hidden(relative) {
+            continue;
+        }
+        let key = relative.to_string_lossy();
+        let mut entries = root.entries.write().unwrap();
+        let found = entries.binary_search_by(|e| e.path.as_ref().cmp(&key));
+        match (fs::symlink_metadata(&path), found) {
+            (Err(_), _) => remove_subtree(&mut entries, &key),
+            (Ok(meta), Ok(i)) => {
+                entries[i].size = if meta.is_dir() { 0 } else { meta.len() };
+                entries[i].mtime = mtime_secs(&meta);
+            }
+            (Ok(_), Err(_)) => {
+                // Only the root and indexed directories can gain entries
+                let parent = relative.parent().map(|p| p.to_string_lossy());
+                let indexed = match &parent {
+                    Some(parent) if !parent.is_empty() => entries
+                        .binary_search_by(|e| e.path.as_ref().cmp(parent))
+                        .is_ok(),
+                    _ => true,
+                };
+                if indexed {
+                    listed.extend(path.parent().map(Path::to_path_buf));
+                }
+            }
+        }
+    }
+
+    for dir in listed {
+        let Some(children) = walk(root, &dir, Some(1)) else {
+            return;
+        };
+        for child in children {
+            let path = root.path.join(child.path.as_ref());
+            let is_dir = child.is_dir;
+            let is_new = upsert(&mut root.entries.write().unwrap(), child);
+            // A directory moved or copied in arrives as a single event
+            if is_new && is_dir {
+                let Some(below) = walk(root, &path, None) else {
+                    return;
+                };
+                let mut entries = root.entries.write().unwrap();
+                for entry in below {
+                    upsert(&mut entries, entry);
+                }
+            }
+        }
+    }
+    root.status.lock().unwrap().entries = root.entries.read().unwrap().len();
+}
+
+/// Background thread of one root: load, scan, then follow changes.
+fn run(root: Arc<Root>) {
+    if let Some(entries) = load_index(&root.path) {
+        *root.entries.write().unwrap() = entries;
+        root.status.lock().unwrap().entries = root.entries.read().unwrap().len();
+    }
+
+    let (tx, rx) = mpsc::channel::<Vec<PathBuf>>();
+    let watcher = notify::recommended_watcher(move |res: Result<notify::Event, notify::Error>| {
+        // Reads, including the index's own scans, don't change anything
+        if let Some(event) = res.ok().filter(|e| !e.kind.is_access()) {
+            let _ = tx.send(event.paths);
+        }
+    });
+    let watching = match (watcher, fs_watcher::remote_filesystem(&root.path)) {
+        // Native events never arrive there
+        (Ok(_), Some(filesystem)) => {
+            log::info!(
+                "{} is on {}, rescanning periodically",
+                root.path.display(),
+                filesystem
+            );
+            None
+        }
+        (Ok(mut watcher), None) => match watcher.watch(&root.path, RecursiveMode::Recursive) {
+            Ok(()) => Some(watcher),
+            Err(e) => {
+                log::warn!(
+                    "Cannot watch {}, rescanning periodically: {}",
+                    root.path.display(),
+                    e
+                );
+                None
+            }
+        },
+        (Err(e), _) => {
+            log::warn!("Failed to create index watcher: {}", e);
+            None
+        }
+    };
+
+    scan(&root);
+    let mut last_scan = Instant::now();
+    let mut last_save = Instant::now();
+    let mut dirty = false;
+
+    while !root.stop.load(Ordering::Relaxed) {
+        match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(paths) => {
+                let mut batch: HashSet<PathBuf> = paths.into_iter().collect();
+                let deadline = Instant::now() + BATCH_WINDOW;
+                while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                    match rx.recv_timeout(left) {
+                        Ok(paths) => batch.extend(paths),
+                        Err(_) => break,
+                    }
+                }
+                apply_changes(&root, batch);
+                dirty = true;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) if watching.is_some() => break,
+            Err(RecvTimeoutError::Disconnected) => {
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        }
+        if watching.is_none() && last_scan.elapsed() > RESCAN_INTERVAL {
+            scan(&root);
+            last_scan = Instant::now();
+            dirty = false;
+        }
+        if dirty && last_save.elapsed() > SAVE_INTERVAL {
+            if let Err(e) = save_index(&root) {
+                log::warn!(
+                    "Failed to save file index for {}: {}",
+                    root.path.display(),
+                    e
+                );
+            }
+            last_save = Instant::now();
+            dirty = false;
+        }
+    }
+}
+
+fn spawn_root(path: PathBuf) -> Arc<Root> {
+    let root = Arc::new(Root {
+        status: Mutex::new(IndexStatus {
+            root: path.to_string_lossy().to_string(),
+            state: IndexState::Building,
+            entries: 0,
+            scanned_at: 0,
+            error: None,
+        }),
+        path,
+        entries: RwLock::new(Vec::new()),
+        stop: AtomicBool::new(false),
+    });
+    let thread_root = root.clone();
+    std::thread::spawn(move || run(thread_root));
+    root
+}
+
+/// Start indexing the configured roots. Called once at startup.
+pub fn start() {
+    let configured = pocketpaw_dir()
+        .and_then(|dir| fs::read_to_string(dir.join(ROOTS_FILE)).map_err(|e| e.to_string()))
+        .ok()
+        .and_then(|data| serde_json::from_str::<RootsConfig>(&data).ok());
+    let paths: Vec<PathBuf> = match configured {
+        Some(config) => config.roots.into_iter().map(PathBuf::from).collect(),
+        None => dirs::home_dir().into_iter().collect(),
+    };
+    let mut roots = roots().lock().unwrap();
+    for path in paths {
+        roots.push(spawn_root(path));
+    }
+}
+
+/// Boost for a recently opened file: up to 100 for frequent opens plus up to
+/// 100 for recency, halving with each day since the last open.
+fn recent_boost(file: &RecentFile, now: u64) -> u32 {
+    let frequency = file.count.min(10) * 10;
+    let days = now.saturating_sub(file.last_opened) / 86_400;
+    frequency + (100u32 >> days.min(31))
+}
+
+fn utf16_indices(text: &str, char_indices: &mut [u32]) -> Vec<u32> {
+    char_indices.sort_unstable();
+    let mut out = Vec::with_capacity(char_indices.len());
+    let mut offset = 0u32;
+    let mut wanted = char_indices.iter().peekable();
+    for (i, c) in text.chars().enumerate() {
+        while wanted.next_if(|&&w| w as usize == i).is_some() {
+            if out.last() != Some(&offset) {
+                out.push(offset);
+            }
+        }
+        offset += c.len_utf16() as u32;
+    }
+    out
+}
+
+/// Score `entries` against `pattern`; returns (score, index) of every match.
+fn score_entries(
+    entries: &[Entry],
+    first_index: usize,
+    pattern: &Pattern,
+    boosts: &HashMap<&str, u32>,
+) -> Vec<(u32, usize)> {
+    let mut matcher = Matcher::new(Config::DEFAULT.match_paths());
+    let mut buf = Vec::new();
+    let mut hits = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let Some(mut score) = pattern.score(Utf32Str::new(&entry.path, &mut buf), &mut matcher)
+        else {
+            continue;
+        };
+        if let Some(name_score) = pattern.score(Utf32Str::new(entry.name(), &mut buf), &mut matcher)
+        {
+            score += name_score;
+        }
+        if let Some(boost) = boosts.get(entry.path.as_ref()) {
+            score += boost;
+        }
+        hits.push((score, first_index + i));
+    }
+    hits
+}
+
+/// The part of `root`'s entries below `within`, or `None` if `within` is
+/// outside the root.
+fn entries_within(root: &Root, entries: &[Entry], within: Option<&Path>) -> Option<(usize, usize)> {
+    let Some(within) = within else {
+        return Some((0, entries.len()));
+    };
+    if root.path.starts_with(within) {
+        return Some((0, entries.len()));
+    }
+    let relative = within.strip_prefix(&root.path).ok()?;
+    let prefix = format!("{}{}", relative.to_string_lossy(), MAIN_SEPARATOR);
+    let start = entries.partition_point(|e| e.path.as_ref() < prefix.as_str());
+    let len = entries[start..]
+        .iter()
+        .take_while(|e| e.path.starts_with(&prefix))
+        .count();
+    Some((start, start + len))
+}
+
+fn hit(root: &Root, entry: &Entry, score: u32, indices: Vec<u32>) -> IndexHit {
+    let path = root.path.join(entry.path.as_ref());
+    IndexHit {
+        extension: path
+            .extension()
+            .map(|e| e.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: path.to_string_lossy().to_string(),
+        relative_path: entry.path.to_string(),
+        root: root.path.to_string_lossy().to_string(),
+        name: entry.name().to_string(),
+        is_dir: entry.is_dir,
+        size: entry.size,
+        modified: entry.mtime,
+        score,
+        indices,
+    }
+}
+
+/// Recently opened files, newest first, when there is no query yet.
+fn recent_hits(roots: &[Arc<Root>], within: Option<&Path>, limit: usize) -> Vec<IndexHit> {
+    let mut files: Vec<(String, RecentFile)> = recent()
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(path, file)| (path.clone(), *file))
+        .collect();
+    files.sort_by_key(|(_, file)| std::cmp::Reverse(file.last_opened));
+    let mut hits = Vec::new();
+    for (path, _) in files {
+        let path = Path::new(&path);
+        if within.is_some_and(|w| !path.starts_with(w)) {
+            continue;
+        }
+        for root in roots {
+            let Ok(relative) = path.strip_prefix(&root.path) else {
+                continue;
+            };
+            let entries = root.entries.read().unwrap();
+            let key = relative.to_string_lossy();
+            if let Ok(i) = entries.binary_search_by(|e| e.path.as_ref().cmp(&key)) {
+                hits.push(hit(root, &entries[i], 0, Vec::new()));
+                break;
+            }
+        }
+        if hits.len() >= limit {
+            break;
+        }
+    }
+    hits
+}
+
+/// Fuzzy-search the index. `within` limits results to one directory; an
+/// empty query returns recently opened files.
+#[tauri::command]
+pub fn fs_index_query(
+    query: String,
+    limit: Option<usize>,
+    within: Option<String>,
+) -> Result<Vec<IndexHit>, String> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+    let within = within.map(PathBuf::from);
+    if let Some(within) = &within {
+        fs_scope::check(within)?;
+    }
+    let roots: Vec<Arc<Root>> = roots().lock().unwrap().clone();
+    if query.trim().is_empty() {
+        return Ok(recent_hits(&roots, within.as_deref(), limit));
+    }
+
+    let pattern = Pattern::parse(&query, CaseMatching::Smart, Normalization::Smart);
+    let now = now_secs();
+    let recent_files = recent().lock().unwrap().clone();
+    let threads = std::thread::available_parallelism().map_or(4, |n| n.get().min(8));
+
+    let mut results: Vec<(u32, usize, usize)> = Vec::new();
+    let guards: Vec<_> = roots
+        .iter()
+        .map(|root| root.entries.read().unwrap())
+        .collect();
+    for (r, (root, entries)) in roots.iter().zip(&guards).enumerate() {
+        let Some((start, end)) = entries_within(root, entries, within.as_deref()) else {
+            continue;
+        };
+        let boosts: HashMap<&str, u32> = recent_files
+            .iter()
+            .filter_map(|(path, file)| {
+                let relative = Path::new(path).strip_prefix(&root.path).ok()?;
+                Some((relative.to_str()?, recent_boost(file, now)))
+            })
+            .collect();
+        let slice = &entries[start..end];
+        let chunk = slice.len().div_ceil(threads).max(1);
+        let hits: Vec<(u32, usize)> = std::thread::scope(|s| {
+            let handles: Vec<_> = slice
+                .chunks(chunk)
+                .enumerate()
+                .map(|(n, part)| {
+                    let (pattern, boosts) = (&pattern, &boosts);
+                    s.spawn(move || score_entries(part, start + n * chunk, pattern, boosts))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().unwrap_or_default())
+                .collect()
+        });
+        results.extend(hits.into_iter().map(|(score, i)| (score, r, i)));
+    }
+
+    // Best first; shorter paths win ties
+    let path_len = |&(_, r, i): &(u32, usize, usize)| guards[r][i].path.len();
+    if results.len() > limit {
+        results.select_nth_unstable_by(limit, |a, b| b.0.cmp(&a.0));
+        results.truncate(limit);
+    }
+    results.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| path_len(a).cmp(&path_len(b))));
+
+    let mut matcher = Matcher::new(Config::DEFAULT.match_paths());
+    let mut buf = Vec::new();
+    Ok(results
+        .into_iter()
+        .map(|(score, r, i)| {
+            let entry = &guards[r][i];
+            let mut indices = Vec::new();
+            pattern.indices(
+                Utf32Str::new(&entry.path, &mut buf),
+                &mut matcher,
+                &mut indices,
+            );
+            hit(
+                &roots[r],
+                entry,
+                score,
+                utf16_indices(&entry.path, &mut indices),
+            )
+        })
+        .collect())
+}
+
+/// Remember that a file was opened, for the ranking boost.
+#[tauri::command]
+pub fn fs_index_record_open(path: String) -> Result<(), String> {
+    fs_scope::check(&path)?;
+    let snapshot = {
+        let mut recent = recent().lock().unwrap();
+        let file = recent.entry(path).or_insert(RecentFile {
+            last_opened: 0,
+            count: 0,
+        });
+        file.last_opened = now_secs();
+        file.count = file.count.saturating_add(1);
+        if recent.len() > MAX_RECENT {
+            let mut by_age: Vec<(String, u64)> = recent
+                .iter()
+                .map(|(path, file)| (path.clone(), file.last_opened))
+                .collect();
+            by_age.sort_by_key(|(_, last_opened)| *last_opened);
+            for (path, _) in by_age.into_iter().take(recent.len() - MAX_RECENT) {
+                recent.remove(&path);
+            }
+        }
+        recent.clone()
+    };
+    save_json(RECENT_FILE, &snapshot)
+}
+
+#[tauri::command]
+pub fn fs_index_roots() -> Vec<IndexStatus> {
+    roots()
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|root| root.status.lock().unwrap().clone())
+        .collect()
+}
+
+/// Add a directory to the index. Directories inside an indexed root are
+/// already covered.
+#[tauri::command]
+pub fn fs_index_add_root(path: String) -> Result<(), String> {
+    let resolved = fs_scope::check(&path)?;
+    if !resolved.is_dir() {
+        return Err(format!("Not a directory: {}", path));
+    }
+    {
+        let mut roots = roots().lock().unwrap();
+        if let Some(existing) = roots.iter().find(|r| resolved.starts_with(&r.path)) {
+            return Err(format!(
+                "{} is already indexed as part of {}",
+                path,
+                existing.path.display()
+            ));
+        }
+        roots.push(spawn_root(resolved));
+    }
+    save_roots()
+}
+
+/// Stop indexing a root and delete its saved index.
+#[tauri::command]
+pub fn fs_index_remove_root(path: String) -> Result<(), String> {
+    let removed = {
+        let mut roots = roots().lock().unwrap();
+        let position = roots
+            .iter()
+            .position(|r| r.path == Path::new(&path))
+            .ok_or_else(|| format!("Not an indexed root: {}", path))?;
+        roots.remove(position)
+    };
+    removed.stop.store(true, Ordering::Relaxed);
+    if let Ok(file) = index_file(&removed.path) {
+        let _ = fs::remove_file(file);
+    }
+    save_roots()
+}
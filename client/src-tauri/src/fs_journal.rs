@@ -0,0 +1,663 @@
+// Undo journal for the mutating fs_* commands.
+//
+// Each successful write, copy, rename, create or trash records how to reverse
+// it. Overwritten content is kept as a snapshot in ~/.pocketpaw/cache/undo
+// (content-addressed, so repeated saves of the same text share a file). The
+// journal is in memory and shared by all windows, so an edit applied on the
+// agent's behalf can be undone from the explorer like any other.
+//
+// Before undoing or redoing, the current state on disk is compared with what
+// the operation left behind; if the file changed since, the entry is dropped
+// instead of clobbering the newer content.
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::fs_commands;
+use crate::fs_scope;
+use crate::fs_transfer;
+use crate::fs_trash;
+use crate::fs_write::{self, sha256_hex};
+
+/// Entries kept on the undo stack.
+const MAX_ENTRIES: usize = 100;
+/// Files larger than this are not snapshotted, so writes to them can't be
+/// undone.
+const MAX_SNAPSHOT_BYTES: u64 = 50 * 1024 * 1024;
+/// Snapshot storage limit; the oldest entries are dropped beyond it.
+const MAX_TOTAL_BYTES: u64 = 500 * 1024 * 1024;
+/// Unreferenced snapshots younger than this are kept: they may belong to an
+/// operation that hasn't been recorded yet.
+const SNAPSHOT_GRACE: Duration = Duration::from_secs(60);
+
+/// What a path held before or after an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileState {
+    Absent,
+    File { hash: String, size: u64 },
+}
+
+/// Where an append started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppendStart {
+    /// The append created the file.
+    Created,
+    /// The file was this many bytes long.
+    At(u64),
+}
+
+#[derive(Debug, Clone)]
+enum Operation {
+    /// A file's content went from `before` to `after` (write, copy).
+    Replace {
+        path: PathBuf,
+        before: FileState,
+        after: FileState,
+    },
+    /// `added` was appended to a file. Only the appended bytes are kept, so
+    /// undo truncates the file back to `start` instead of restoring a copy.
+    Append {
+        path: PathBuf,
+        start: AppendStart,
+        added: FileState,
+    },
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Directories created, outermost first.
+    CreateDirs {
+        dirs: Vec<PathBuf>,
+    },
+    /// A directory tree copied to a destination that didn't exist.
+    CopyDir {
+        src: PathBuf,
+        dest: PathBuf,
+    },
+    Trash {
+        path: PathBuf,
+    },
+    Restore {
+        path: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    id: u64,
+    time: u64,
+    kind: &'static str,
+    op: Operation,
+}
+
+/// An undo or redo step, as listed by `fs_history`.
+#[derive(Debug, Serialize, Clone)]
+pub struct HistoryEntry {
+    pub id: u64,
+    /// `write`, `copy`, `rename`, `create_dir`, `copy_dir`, `trash` or `restore`.
+    pub kind: String,
+    pub paths: Vec<String>,
+    /// Unix seconds.
+    pub time: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct History {
+    /// Most recent first.
+    pub undo: Vec<HistoryEntry>,
+    /// Next redo first.
+    pub redo: Vec<HistoryEntry>,
+}
+
+#[derive(Default)]
+struct Journal {
+    next_id: u64,
+    undo: VecDeque<Entry>,
+    redo: Vec<Entry>,
+    /// The entry being undone or redone. It is off both stacks meanwhile, but
+    /// its snapshots must survive collection.
+    in_flight: Option<Entry>,
+}
+
+fn journal() -> &'static Mutex<Journal> {
+    static JOURNAL: OnceLock<Mutex<Journal>> = OnceLock::new();
+    JOURNAL.get_or_init(|| Mutex::new(Journal::default()))
+}
+
+/// Serializes undo and redo, which do their filesystem work without holding
+/// the journal lock.
+static STEP_LOCK: Mutex<()> = Mutex::new(());
+
+/// Delete snapshots left by a previous run; no entry points at them. Called
+/// once at startup, before anything is journaled.
+pub fn clear_stale_snapshots() {
+    if let Ok(dir) = snapshot_dir() {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+fn snapshot_dir() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Could not determine home directory")?;
+    Ok(home.join(".pocketpaw").join("cache").join("undo"))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Create `dir` (and its parents) with access for the owner only.
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(dir)
+}
+
+/// Write `data` to a new file readable by the owner only.
+fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(data)
+}
+
+/// Store `data` as a snapshot and return its state.
+fn store_snapshot(data: &[u8]) -> Option<FileState> {
+    let hash = sha256_hex(data);
+    let dir = snapshot_dir().ok()?;
+    let path = dir.join(&hash);
+    if path.exists() {
+        // Reused: mark it recent so collection doesn't race with recording
+        let file = fs::File::options().append(true).open(&path).ok()?;
+        file.set_modified(SystemTime::now()).ok()?;
+    } else {
+        create_private_dir(&dir).ok()?;
+        write_private(&path, data).ok()?;
+    }
+    Some(FileState::File {
+        hash,
+        size: data.len() as u64,
+    })
+}
+
+/// The current state of `path`, snapshotting its content. `None` if it can't
+/// be captured (a directory, a symlink, too large or unreadable), in which
+/// case the operation is not recorded.
+pub fn capture(path: &Path) -> Option<FileState> {
+    match fs::symlink_metadata(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Some(FileState::Absent),
+        Err(_) => None,
+        Ok(meta) if !meta.is_file() || meta.len() > MAX_SNAPSHOT_BYTES => None,
+        Ok(_) => store_snapshot(&fs::read(path).ok()?),
+    }
+}
+
+/// Like `capture`, for content the caller already has in memory.
+pub fn capture_data(data: &[u8]) -> Option<FileState> {
+    if data.len() as u64 > MAX_SNAPSHOT_BYTES {
+        return None;
+    }
+    store_snapshot(data)
+}
+
+fn matches(path: &Path, state: &FileState) -> bool {
+    match state {
+        FileState::Absent => matches!(
+            fs::symlink_metadata(path),
+            Err(e) if e.kind() == ErrorKind::NotFound
+        ),
+        FileState::File { hash, .. } => match fs::read(path) {
+            Ok(data) => sha256_hex(&data) == *hash,
+            Err(_) => false,
+        },
+    }
+}
+
+/// Whether `path` is `start` bytes long plus exactly the `added` bytes.
+fn ends_with_added(path: &Path, start: AppendStart, added: &FileState) -> bool {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let FileState::File { hash, size } = added else {
+        return false;
+    };
+    let offset = match start {
+        AppendStart::Created => 0,
+        AppendStart::At(len) => len,
+    };
+    let Ok(mut file) = fs::File::open(path) else {
+        return false;
+    };
+    if !file
+        .metadata()
+        .is_ok_and(|m| m.is_file() && m.len() == offset + size)
+    {
+        return false;
+    }
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.read_to_end(&mut tail))
+        .is_ok_and(|_| sha256_hex(&tail) == *hash)
+}
+
+fn read_snapshot(hash: &str) -> Result<Vec<u8>, String> {
+    fs::read(snapshot_dir()?.join(hash)).map_err(|e| format!("Failed to read snapshot: {}", e))
+}
+
+fn apply_state(path: &Path, state: &FileState) -> Result<(), String> {
+    match state {
+        FileState::Absent => {
+            fs::remove_file(path).map_err(|e| format!("Failed to delete file: {}", e))
+        }
+        FileState::File { hash, .. } => {
+            let data = read_snapshot(hash)?;
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
+            }
+            fs_write::write_atomic(path, &data)
+        }
+    }
+}
+
+fn exists(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+fn changed(path: &Path) -> String {
+    format!("{} has changed since", path.display())
+}
+
+impl Operation {
+    fn paths(&self) -> Vec<&Path> {
+        match self {
+            Operation::Replace { path, .. }
+            | Operation::Append { path, .. }
+            | Operation::Trash { path }
+            | Operation::Restore { path } => vec![path],
+            Operation::Rename { from, to } => vec![from, to],
+            Operation::CreateDirs { dirs } => dirs.iter().map(|d| d.as_path()).collect(),
+            Operation::CopyDir { src, dest } => vec![src, dest],
+        }
+    }
+
+    fn snapshot_bytes(&self) -> u64 {
+        match self {
+            Operation::Replace { before, after, .. } => [before, after]
+                .iter()
+                .map(|s| match s {
+                    FileState::File { size, .. } => *size,
+                    FileState::Absent => 0,
+                })
+                .sum(),
+            Operation::Append {
+                added: FileState::File { size, .. },
+                ..
+            } => *size,
+            _ => 0,
+        }
+    }
+
+    fn snapshot_hashes<'a>(&'a self, into: &mut HashSet<&'a str>) {
+        let states = match self {
+            Operation::Replace { before, after, .. } => vec![before, after],
+            Operation::Append { added, .. } => vec![added],
+            _ => return,
+        };
+        for state in states {
+            if let FileState::File { hash, .. } = state {
+                into.insert(hash);
+            }
+        }
+    }
+
+    fn undo(&self) -> Result<(), String> {
+        for path in self.paths() {
+            fs_scope::check(path)?;
+        }
+        match self {
+            Operation::Replace {
+                path,
+                before,
+                after,
+            } => {
+                if !matches(path, after) {
+                    return Err(changed(path));
+                }
+                apply_state(path, before)
+            }
+            Operation::Append { path, start, added } => {
+                if !ends_with_added(path, *start, added) {
+                    return Err(changed(path));
+                }
+                match start {
+                    AppendStart::Created => {
+                        fs::remove_file(path).map_err(|e| format!("Failed to delete file: {}", e))
+                    }
+                    AppendStart::At(len) => fs::OpenOptions::new()
+                        .write(true)
+                        .open(path)
+                        .and_then(|file| file.set_len(*len))
+                        .map_err(|e| format!("Failed to truncate file: {}", e)),
+                }
+            }
+            Operation::Rename { from, to } => {
+                if exists(from) || !exists(to) {
+                    return Err(changed(to));
+                }
+                fs_transfer::move_path(to, from)
+            }
+            Operation::CreateDirs { dirs } => {
+                for dir in dirs.iter().rev() {
+                    fs::remove_dir(dir).map_err(|e| format!("Failed to delete dir: {}", e))?;
+                }
+                Ok(())
+            }
+            Operation::CopyDir { dest, .. } => {
+                if !dest.is_dir() {
+                    return Err(changed(dest));
+                }
+                fs_trash::move_to_trash(dest)
+            }
+            Operation::Trash { path } => fs_trash::restore_latest(path),
+            Operation::Restore { path } => fs_trash::move_to_trash(path),
+        }
+    }
+
+    fn redo(&self) -> Result<(), String> {
+        for path in self.paths() {
+            fs_scope::check(path)?;
+        }
+        match self {
+            Operation::Replace {
+                path,
+                before,
+                after,
+            } => {
+                if !matches(path, before) {
+                    return Err(changed(path));
+                }
+                apply_state(path, after)
+            }
+            Operation::Append { path, start, added } => {
+                use std::io::Write;
+
+                let unchanged = match start {
+                    AppendStart::Created => !exists(path),
+                    AppendStart::At(len) => {
+                        fs::symlink_metadata(path).is_ok_and(|m| m.is_file() && m.len() == *len)
+                    }
+                };
+                let FileState::File { hash, .. } = added else {
+                    return Err(changed(path));
+                };
+                if !unchanged {
+                    return Err(changed(path));
+                }
+                let data = read_snapshot(hash)?;
+                fs::OpenOptions::new()
+                    .append(true)
+                    .create_new(*start == AppendStart::Created)
+                    .open(path)
+                    .and_then(|mut file| file.write_all(&data))
+                    .map_err(|e| format!("Failed to append to file: {}", e))
+            }
+            Operation::Rename { from, to } => {
+                if exists(to) || !exists(from) {
+                    return Err(changed(from));
+                }
+                fs_transfer::move_path(from, to)
+            }
+            Operation::CreateDirs { dirs } => match dirs.last() {
+                Some(dir) => {
+                    fs::create_dir_all(dir).map_err(|e| format!("Failed to create dir: {}", e))
+                }
+                None => Ok(()),
+            },
+            Operation::CopyDir { src, dest } => {
+                if exists(dest) {
+                    return Err(changed(dest));
+                }
+                fs_commands::copy_dir_recursive(src, dest)
+            }
+            Operation::Trash { path } => fs_trash::move_to_trash(path),
+            Operation::Restore { path } => fs_trash::restore_latest(path),
+        }
+    }
+}
+
+impl Entry {
+    fn summary(&self) -> HistoryEntry {
+        HistoryEntry {
+            id: self.id,
+            kind: self.kind.to_string(),
+            paths: self
+                .op
+                .paths()
+                .iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect(),
+            time: self.time,
+        }
+    }
+}
+
+impl Journal {
+    fn push(&mut self, kind: &'static str, op: Operation) {
+        self.next_id += 1;
+        self.undo.push_back(Entry {
+            id: self.next_id,
+            time: now_secs(),
+            kind,
+            op,
+        });
+        // A new operation invalidates everything that could be redone
+        self.redo.clear();
+
+        let mut total: u64 = self.undo.iter().map(|e| e.op.snapshot_bytes()).sum();
+        while self.undo.len() > MAX_ENTRIES || (total > MAX_TOTAL_BYTES && self.undo.len() > 1) {
+            if let Some(dropped) = self.undo.pop_front() {
+                total -= dropped.op.snapshot_bytes();
+            }
+        }
+        self.collect_snapshots();
+    }
+
+    /// Delete snapshot files no remaining entry refers to.
+    fn collect_snapshots(&self) {
+        let Ok(dir) = snapshot_dir() else { return };
+        let Ok(files) = fs::read_dir(&dir) else {
+            return;
+        };
+        let mut live = HashSet::new();
+        for entry in self.undo.iter().chain(&self.redo).chain(&self.in_flight) {
+            entry.op.snapshot_hashes(&mut live);
+        }
+        for file in files.flatten() {
+            let recent = file
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.elapsed().ok())
+                .is_some_and(|age| age < SNAPSHOT_GRACE);
+            if !recent && !live.contains(file.file_name().to_string_lossy().as_ref()) {
+                let _ = fs::remove_file(file.path());
+            }
+        }
+    }
+}
+
+fn record(kind: &'static str, op: Operation) {
+    journal().lock().unwrap().push(kind, op);
+}
+
+/// Record a content change. `before` comes from `capture` ahead of the
+/// operation; nothing is recorded if either side couldn't be captured.
+pub fn record_replace(
+    kind: &'static str,
+    path: &Path,
+    before: Option<FileState>,
+    after: Option<FileState>,
+) {
+    if let (Some(before), Some(after)) = (before, after) {
+        record(
+            kind,
+            Operation::Replace {
+                path: path.to_path_buf(),
+                before,
+                after,
+            },
+        );
+    }
+}
+
+/// Where an append to `path` would start, or `None` if it can't be journaled
+/// (not a regular file).
+pub fn append_start(path: &Path) -> Option<AppendStart> {
+    match fs::symlink_metadata(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Some(AppendStart::Created),
+        Ok(meta) if meta.is_file() => Some(AppendStart::At(meta.len())),
+        _ => None,
+    }
+}
+
+/// Record that `data` was appended to `path`, which started at `start`.
+pub fn record_append(path: &Path, start: Option<AppendStart>, data: &[u8]) {
+    if let (Some(start), Some(added)) = (start, capture_data(data)) {
+        record(
+            "append",
+            Operation::Append {
+                path: path.to_path_buf(),
+                start,
+                added,
+            },
+        );
+    }
+}
+
+pub fn record_rename(from: &Path, to: &Path) {
+    record(
+        "rename",
+        Operation::Rename {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+        },
+    );
+}
+
+/// `dirs` are the directories that didn't exist before, outermost first.
+pub fn record_create_dirs(dirs: Vec<PathBuf>) {
+    if !dirs.is_empty() {
+        record("create_dir", Operation::CreateDirs { dirs });
+    }
+}
+
+pub fn record_copy_dir(src: &Path, dest: &Path) {
+    record(
+        "copy_dir",
+        Operation::CopyDir {
+            src: src.to_path_buf(),
+            dest: dest.to_path_buf(),
+        },
+    );
+}
+
+pub fn record_trash(path: &Path) {
+    record(
+        "trash",
+        Operation::Trash {
+            path: path.to_path_buf(),
+        },
+    );
+}
+
+pub fn record_restore(path: &Path) {
+    record(
+        "restore",
+        Operation::Restore {
+            path: path.to_path_buf(),
+        },
+    );
+}
+
+/// Undo the most recent operation. Returns `None` if there is nothing to undo.
+/// If the files involved changed since, the entry is discarded and an error
+/// returned.
+#[tauri::command]
+pub fn fs_undo() -> Result<Option<HistoryEntry>, String> {
+    let _step = STEP_LOCK.lock().unwrap();
+    let Some(entry) = take_in_flight(|journal| journal.undo.pop_back()) else {
+        return Ok(None);
+    };
+    let result = entry.op.undo();
+    let mut journal = journal().lock().unwrap();
+    journal.in_flight = None;
+    match result {
+        Ok(()) => {
+            let summary = entry.summary();
+            journal.redo.push(entry);
+            Ok(Some(summary))
+        }
+        Err(e) => {
+            journal.collect_snapshots();
+            Err(format!("Cannot undo {}: {}", entry.kind, e))
+        }
+    }
+}
+
+/// Redo the most recently undone operation. Returns `None` if there is
+/// nothing to redo.
+#[tauri::command]
+pub fn fs_redo() -> Result<Option<HistoryEntry>, String> {
+    let _step = STEP_LOCK.lock().unwrap();
+    let Some(entry) = take_in_flight(|journal| journal.redo.pop()) else {
+        return Ok(None);
+    };
+    let result = entry.op.redo();
+    let mut journal = journal().lock().unwrap();
+    journal.in_flight = None;
+    match result {
+        Ok(()) => {
+            let summary = entry.summary();
+            journal.undo.push_back(entry);
+            Ok(Some(summary))
+        }
+        Err(e) => {
+            journal.collect_snapshots();
+            Err(format!("Cannot redo {}: {}", entry.kind, e))
+        }
+    }
+}
+
+/// Take the next entry off a stack, keeping a copy in `in_flight` while the
+/// journal lock is released for the filesystem work.
+fn take_in_flight(take: impl FnOnce(&mut Journal) -> Option<Entry>) -> Option<Entry> {
+    let mut journal = journal().lock().unwrap();
+    let entry = take(&mut journal)?;
+    journal.in_flight = Some(entry.clone());
+    Some(entry)
+}
+
+#[tauri::command]
+pub fn fs_history() -> History {
+    let journal = journal().lock().unwrap();
+    History {
+        undo: journal.undo.iter().rev().map(Entry::summary).collect(),
+        redo: journal.redo.iter().rev().map(Entry::summary).collect(),
+    }
+}
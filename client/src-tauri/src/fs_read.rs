@@ -0,0 +1,441 @@
+// Ranged reads and text decoding for large or non-UTF-8 files.
+//
+// Text is decoded with the encoding named by its BOM, as UTF-8 when it is
+// valid UTF-8, and otherwise with the encoding chardetng guesses from the
+// first 64 KiB (Latin-1/windows-1252, Shift_JIS, GBK, ...). The name of the
+// encoding used is reported back so the explorer can show it and let the user
+// reopen the file with another one.
+//
+// `fs_read_lines` serves windows of lines from files too big to load whole. A
+// per-file index of the byte offset of every `INDEX_STRIDE`th line is built
+// lazily as windows further down are requested and kept while the file is
+// unchanged or only grows, so paging through a multi-GB log only scans it once.
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use crate::fs_scope;
+
+/// Bytes looked at to detect the encoding.
+const SNIFF_BYTES: usize = 64 * 1024;
+/// Largest `fs_read_range` request.
+const MAX_RANGE_BYTES: usize = 16 * 1024 * 1024;
+/// Largest `fs_read_lines` window.
+const MAX_LINES: usize = 10_000;
+/// Longer lines are cut off in `fs_read_lines` results.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+/// Lines between two entries of a line index.
+const INDEX_STRIDE: u64 = 1024;
+/// Files whose line index is kept.
+const MAX_INDEXES: usize = 16;
+
+/// A whole file decoded to text.
+#[derive(Debug, Serialize)]
+pub struct DecodedText {
+    pub text: String,
+    /// WHATWG name of the encoding used, e.g. "UTF-8", "Shift_JIS".
+    pub encoding: String,
+    pub has_bom: bool,
+    /// Some bytes were not valid in the encoding and were replaced with U+FFFD.
+    pub malformed: bool,
+}
+
+/// A window of lines returned by `fs_read_lines`.
+#[derive(Debug, Serialize)]
+pub struct LineWindow {
+    /// Zero-based number of the first line in `lines`.
+    pub start_line: u64,
+    /// Without line terminators.
+    pub lines: Vec<String>,
+    pub encoding: String,
+    /// Number of lines in the file, once the index has reached its end.
+    pub total_lines: Option<u64>,
+    /// The window ends at the end of the file.
+    pub at_end: bool,
+}
+
+/// How lines are terminated in the file's encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Newline {
+    /// `\n` byte; any ASCII-compatible encoding.
+    Byte,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Newline {
+    fn for_encoding(encoding: &'static Encoding) -> Self {
+        if encoding == UTF_16LE {
+            Newline::Utf16Le
+        } else if encoding == UTF_16BE {
+            Newline::Utf16Be
+        } else {
+            Newline::Byte
+        }
+    }
+
+    /// Whether `prev, byte` end a line, `len` being the line's length so far
+    /// including `byte`.
+    fn ends_line(self, len: usize, prev: u8, byte: u8) -> bool {
+        match self {
+            Newline::Byte => byte == b'\n',
+            Newline::Utf16Le => len.is_multiple_of(2) && prev == b'\n' && byte == 0,
+            Newline::Utf16Be => len.is_multiple_of(2) && prev == 0 && byte == b'\n',
+        }
+    }
+}
+
+/// UTF-16 without a BOM: most code units of text have a zero high byte.
+fn sniff_utf16(sample: &[u8]) -> Option<&'static Encoding> {
+    let units = sample.len() / 2;
+    if units < 16 {
+        return None;
+    }
+    let zeros = |offset: usize| {
+        sample
+            .chunks_exact(2)
+            .filter(|unit| unit[offset] == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    if odd > units * 2 / 3 && even < units / 10 {
+        Some(UTF_16LE)
+    } else if even > units * 2 / 3 && odd < units / 10 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Detect the encoding of text starting with `sample`. `complete` is true when
+/// the sample is the whole file. Returns the encoding and the length of its
+/// BOM, if any.
+pub(crate) fn detect(sample: &[u8], complete: bool) -> (&'static Encoding, usize) {
+    if let Some(found) = Encoding::for_bom(sample) {
+        return found;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => return (UTF_8, 0),
+        // A sequence cut off by the end of the sample
+        Err(e) if !complete && e.error_len().is_none() => return (UTF_8, 0),
+        Err(_) => {}
+    }
+    if let Some(encoding) = sniff_utf16(sample) {
+        return (encoding, 0);
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(sample, complete);
+    (detector.guess(None, true), 0)
+}
+
+/// The encoding to use: `label` if given (e.g. "latin1", "shift_jis"),
+/// otherwise the detected one. The BOM length is returned either way so it is
+/// never decoded as text.
+fn choose(
+    label: Option<&str>,
+    sample: &[u8],
+    complete: bool,
+) -> Result<(&'static Encoding, usize), String> {
+    let (detected, bom) = detect(sample, complete);
+    match label {
+        Some(label) => {
+            let encoding = Encoding::for_label(label.as_bytes())
+                .ok_or_else(|| format!("Unknown encoding: {}", label))?;
+            Ok((encoding, if encoding == detected { bom } else { 0 }))
+        }
+        None => Ok((detected, bom)),
+    }
+}
+
+/// Decode a whole file's bytes.
+pub(crate) fn decode(bytes: &[u8], label: Option<&str>) -> Result<DecodedText, String> {
+    let sample = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    let (encoding, bom) = choose(label, sample, sample.len() == bytes.len())?;
+    let (text, malformed) = encoding.decode_without_bom_handling(&bytes[bom..]);
+    Ok(DecodedText {
+        text: text.into_owned(),
+        encoding: encoding.name().to_string(),
+        has_bom: bom > 0,
+        malformed,
+    })
+}
+
+fn read_sample(file: &mut fs::File) -> Result<(Vec<u8>, bool), String> {
+    let mut sample = Vec::with_capacity(SNIFF_BYTES);
+    file.by_ref()
+        .take(SNIFF_BYTES as u64 + 1)
+        .read_to_end(&mut sample)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let complete = sample.len() <= SNIFF_BYTES;
+    sample.truncate(SNIFF_BYTES);
+    Ok((sample, complete))
+}
+
+/// Read up to `length` bytes starting at `offset`. Fewer bytes are returned
+/// at the end of the file.
+fn read_range(path: &Path, offset: u64, length: usize) -> Result<Vec<u8>, String> {
+    if length > MAX_RANGE_BYTES {
+        return Err(format!(
+            "Range too large: {} bytes (at most {})",
+            length, MAX_RANGE_BYTES
+        ));
+    }
+    let mut file = fs::File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| format!("Failed to seek: {}", e))?;
+    let mut buf = Vec::with_capacity(length);
+    file.take(length as u64)
+        .read_to_end(&mut buf)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    Ok(buf)
+}
+
+/// Read the next line into `out` (when given, up to `MAX_LINE_BYTES`, with
+/// its terminator). Returns the bytes consumed, or `None` at the end of the
+/// file.
+fn next_line<R: BufRead>(
+    reader: &mut R,
+    newline: Newline,
+    mut out: Option<&mut Vec<u8>>,
+) -> std::io::Result<Option<u64>> {
+    let mut len = 0usize;
+    let mut prev = 0u8;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok((len > 0).then_some(len as u64));
+        }
+        let mut used = 0;
+        let mut done = false;
+        for &byte in buf {
+            used += 1;
+            len += 1;
+            if newline.ends_line(len, prev, byte) {
+                done = true;
+                break;
+            }
+            prev = byte;
+        }
+        if let Some(out) = out.as_deref_mut() {
+            let room = MAX_LINE_BYTES.saturating_sub(out.len());
+            out.extend_from_slice(&buf[..used.min(room)]);
+        }
+        reader.consume(used);
+        if done {
+            return Ok(Some(len as u64));
+        }
+    }
+}
+
+/// Offsets of every `INDEX_STRIDE`th line of a file, as far as it has been
+/// scanned.
+struct LineIndex {
+    len: u64,
+    modified: Option<SystemTime>,
+    newline: Newline,
+    /// `checkpoints[k]` is the byte offset of line `k * INDEX_STRIDE`.
+    checkpoints: Vec<u64>,
+    /// Lines in the file, once the scan has reached the end.
+    total_lines: Option<u64>,
+    /// The bytes just before `len`, to tell an append from a rewrite.
+    tail: Vec<u8>,
+    last_used: SystemTime,
+}
+
+impl LineIndex {
+    fn new(first_line: u64, newline: Newline) -> Self {
+        Self {
+            len: 0,
+            modified: None,
+            newline,
+            checkpoints: vec![first_line],
+            total_lines: None,
+            tail: Vec::new(),
+            last_used: SystemTime::now(),
+        }
+    }
+
+    /// Whether the index still describes `path` as it is now. A file that
+    /// only grew keeps its checkpoints but has to be scanned to the end again.
+    fn refresh(&mut self, path: &Path, meta: &fs::Metadata) -> bool {
+        let modified = meta.modified().ok();
+        if meta.len() == self.len && modified == self.modified {
+            return true;
+        }
+        // Same size but modified, or shorter: rewritten
+        if meta.len() <= self.len {
+            return false;
+        }
+        let start = self.len - self.tail.len() as u64;
+        match read_range(path, start, self.tail.len()) {
+            Ok(bytes) if bytes == self.tail => {
+                self.len = meta.len();
+                self.modified = modified;
+                self.total_lines = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Record that `line` starts at `offset`, if it is the next checkpoint.
+    fn reached(&mut self, line: u64, offset: u64) {
+        if line.is_multiple_of(INDEX_STRIDE) && line / INDEX_STRIDE == self.checkpoints.len() as u64
+        {
+            self.checkpoints.push(offset);
+        }
+    }
+
+    fn remember_state(&mut self, path: &Path, meta: &fs::Metadata) {
+        self.len = meta.len();
+        self.modified = meta.modified().ok();
+        let n = self.len.min(64);
+        self.tail = read_range(path, self.len - n, n as usize).unwrap_or_default();
+    }
+}
+
+fn indexes() -> &'static Mutex<HashMap<PathBuf, LineIndex>> {
+    static INDEXES: OnceLock<Mutex<HashMap<PathBuf, LineIndex>>> = OnceLock::new();
+    INDEXES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Read `count` lines starting at `start_line`, using and extending the
+/// cached line index of `path`.
+fn read_lines(
+    path: &Path,
+    start_line: u64,
+    count: usize,
+    label: Option<&str>,
+) -> Result<LineWindow, String> {
+    let mut file = fs::File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let meta = file
+        .metadata()
+        .map_err(|e| format!("Failed to stat {}: {}", path.display(), e))?;
+    let (sample, complete) = read_sample(&mut file)?;
+    let (encoding, bom) = choose(label, &sample, complete)?;
+    let newline = Newline::for_encoding(encoding);
+
+    // Taken out of the cache while scanning so other files aren't blocked
+    let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let cached = indexes().lock().unwrap().remove(&key);
+    let mut index = cached
+        .filter(|index| index.newline == newline && index.checkpoints[0] == bom as u64)
+        .and_then(|mut index| index.refresh(path, &meta).then_some(index))
+        .unwrap_or_else(|| LineIndex::new(bom as u64, newline));
+
+    // Start from the nearest known checkpoint and scan forward, recording new
+    // checkpoints on the way
+    let wanted = (start_line / INDEX_STRIDE) as usize;
+    let known = wanted.min(index.checkpoints.len() - 1);
+    let mut line = known as u64 * INDEX_STRIDE;
+    let mut offset = index.checkpoints[known];
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| format!("Failed to seek: {}", e))?;
+    let mut reader = BufReader::with_capacity(256 * 1024, file);
+    let io_err = |e: std::io::Error| format!("Failed to read file: {}", e);
+
+    let mut at_end = false;
+    while line < start_line {
+        match next_line(&mut reader, newline, None).map_err(io_err)? {
+            Some(consumed) => {
+                offset += consumed;
+                line += 1;
+                index.reached(line, offset);
+            }
+            None => {
+                at_end = true;
+                break;
+            }
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut raw = Vec::new();
+    while !at_end && lines.len() < count {
+        raw.clear();
+        match next_line(&mut reader, newline, Some(&mut raw)).map_err(io_err)? {
+            Some(consumed) => {
+                offset += consumed;
+                line += 1;
+                index.reached(line, offset);
+                let (text, _) = encoding.decode_without_bom_handling(&raw);
+                let text = text.strip_suffix('\n').unwrap_or(&text);
+                lines.push(text.strip_suffix('\r').unwrap_or(text).to_string());
+            }
+            None => at_end = true,
+        }
+    }
+    if !at_end && reader.fill_buf().map_err(io_err)?.is_empty() {
+        at_end = true;
+    }
+    if at_end {
+        index.total_lines = Some(line);
+    }
+
+    let window = LineWindow {
+        start_line: start_line.min(line),
+        lines,
+        encoding: encoding.name().to_string(),
+        total_lines: index.total_lines,
+        at_end,
+    };
+
+    index.remember_state(path, &meta);
+    index.last_used = SystemTime::now();
+    let mut indexes = indexes().lock().unwrap();
+    if indexes.len() >= MAX_INDEXES {
+        if let Some(oldest) = indexes
+            .iter()
+            .min_by_key(|(_, index)| index.last_used)
+            .map(|(key, _)| key.clone())
+        {
+            indexes.remove(&oldest);
+        }
+    }
+    indexes.insert(key, index);
+    Ok(window)
+}
+
+/// Read a whole file as text, decoded with the detected encoding or the one
+/// named by `encoding`.
+#[tauri::command]
+pub fn fs_read_file_decoded(path: String, encoding: Option<String>) -> Result<DecodedText, String> {
+    fs_scope::check(&path)?;
+    let bytes = fs::read(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+    decode(&bytes, encoding.as_deref())
+}
+
+/// Read up to `length` bytes at `offset`, returned as raw bytes
+/// (an `ArrayBuffer` in the webview).
+#[tauri::command]
+pub fn fs_read_range(
+    path: String,
+    offset: u64,
+    length: usize,
+) -> Result<tauri::ipc::Response, String> {
+    fs_scope::check(&path)?;
+    read_range(Path::new(&path), offset, length).map(tauri::ipc::Response::new)
+}
+
+/// Read `count` lines starting at zero-based `start_line`.
+#[tauri::command]
+pub fn fs_read_lines(
+    path: String,
+    start_line: u64,
+    count: usize,
+    encoding: Option<String>,
+) -> Result<LineWindow, String> {
+    fs_scope::check(&path)?;
+    read_lines(
+        Path::new(&path),
+        start_line,
+        count.min(MAX_LINES),
+        encoding.as_deref(),
+    )
+}
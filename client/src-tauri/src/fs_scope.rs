@@ -0,0 +1,388 @@
+// Filesystem scope policy for the fs_* commands.
+//
+// The webview can pass any path to the filesystem commands, so every path is
+// checked here before it is touched. A path is allowed when, after resolving
+// symlinks, it lies under an allowed root (the home directory and any
+// workspace roots configured in ~/.pocketpaw/client_fs_scope.json) and not
+// inside a denied location (SSH/GPG keys, the token store, undo snapshots).
+// Denied locations always win over allowed roots.
+//
+// Paths that don't exist yet (write and create targets) are resolved through
+// their nearest existing ancestor, so a symlinked parent can't be used to
+// escape the allowed roots either.
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+
+const CONFIG_FILE: &str = "client_fs_scope.json";
+
+/// Directories under home that are never accessible.
+const DENIED_HOME_DIRS: &[&str] = &[".ssh", ".gnupg"];
+
+/// Entries directly under ~/.pocketpaw that are never accessible. `*` matches
+/// any run of characters. The scope config itself is included so a window
+/// can't widen its own scope by writing the file.
+const DENIED_POCKETPAW_ENTRIES: &[&str] =
+    &["*token*", "client_oauth*", ".install_secret", CONFIG_FILE];
+
+/// Folders under ~/.pocketpaw that are never accessible: undo snapshots hold
+/// copies of files that may be private.
+const DENIED_POCKETPAW_DIRS: &[&str] = &["cache/undo"];
+
+/// System folders that can't be made workspace roots, nor anything inside
+/// them.
+#[cfg(unix)]
+const SYSTEM_DIRS: &[&str] = &[
+    "/bin", "/boot", "/dev", "/etc", "/proc", "/sbin", "/sys", "/usr", "/var", "/private",
+    "/System", "/Library",
+];
+
+/// Returned when a path is outside the allowed roots or inside a denied
+/// location. Serialized to the webview as `"PermissionDenied: <reason>: <path>"`
+/// like the other command errors.
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionDenied {
+    pub path: String,
+    pub reason: String,
+}
+
+impl fmt::Display for PermissionDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PermissionDenied: {}: {}", self.reason, self.path)
+    }
+}
+
+impl From<PermissionDenied> for String {
+    fn from(e: PermissionDenied) -> Self {
+        e.to_string()
+    }
+}
+
+/// User-editable part of the policy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScopeConfig {
+    /// Whether the home directory is an allowed root.
+    pub include_home: bool,
+    /// Additional allowed roots, e.g. project folders outside home.
+    pub workspaces: Vec<String>,
+}
+
+impl Default for ScopeConfig {
+    fn default() -> Self {
+        Self {
+            include_home: true,
+            workspaces: Vec::new(),
+        }
+    }
+}
+
+enum Denied {
+    /// A directory and everything below it.
+    Tree(PathBuf),
+    /// Entries directly under `dir` whose name matches `pattern`.
+    Entry { dir: PathBuf, pattern: &'static str },
+}
+
+struct Policy {
+    config: ScopeConfig,
+    roots: Vec<PathBuf>,
+    denied: Vec<Denied>,
+}
+
+impl Policy {
+    fn new(config: ScopeConfig) -> Self {
+        let home = dirs::home_dir();
+        let mut roots = Vec::new();
+        let mut denied = Vec::new();
+
+        if let Some(home) = &home {
+            if config.include_home {
+                roots.extend(resolve(home).ok());
+            }
+            for dir in DENIED_HOME_DIRS {
+                denied.extend(resolve(&home.join(dir)).ok().map(Denied::Tree));
+            }
+            if let Ok(dir) = resolve(&home.join(".pocketpaw")) {
+                for tree in DENIED_POCKETPAW_DIRS {
+                    denied.push(Denied::Tree(dir.join(tree)));
+                }
+                for pattern in DENIED_POCKETPAW_ENTRIES {
+                    denied.push(Denied::Entry {
+                        dir: dir.clone(),
+                        pattern,
+                    });
+                }
+            }
+        }
+        for workspace in &config.workspaces {
+            match resolve(Path::new(workspace))
+                .and_then(|root| check_workspace(&root).map(|()| root))
+            {
+                Ok(root) => roots.push(root),
+                Err(reason) => log::warn!("Ignoring workspace root {}: {}", workspace, reason),
+            }
+        }
+
+        Self {
+            config,
+            roots: roots.iter().map(|p| fold(p)).collect(),
+            denied: denied
+                .into_iter()
+                .map(|d| match d {
+                    Denied::Tree(dir) => Denied::Tree(fold(&dir)),
+                    Denied::Entry { dir, pattern } => Denied::Entry {
+                        dir: fold(&dir),
+                        pattern,
+                    },
+                })
+                .collect(),
+        }
+    }
+
+    /// Why `resolved` (already folded) may not be accessed, if it may not.
+    fn violation(&self, resolved: &Path) -> Option<&'static str> {
+        for denied in &self.denied {
+            let hit = match denied {
+                Denied::Tree(dir) => resolved.starts_with(dir),
+                Denied::Entry { dir, pattern } => match resolved
+                    .strip_prefix(dir)
+                    .ok()
+                    .and_then(|rest| rest.components().next())
+                {
+                    Some(Component::Normal(name)) => wildcard_match(pattern, name),
+                    _ => false,
+                },
+            };
+            if hit {
+                return Some("protected location");
+            }
+        }
+        if !self.roots.iter().any(|root| resolved.starts_with(root)) {
+            return Some("outside the allowed folders");
+        }
+        None
+    }
+
+    /// Whether a denied location lies inside the tree rooted at `resolved`.
+    fn contains_denied(&self, resolved: &Path) -> bool {
+        self.denied.iter().any(|denied| match denied {
+            Denied::Tree(dir) | Denied::Entry { dir, .. } => dir.starts_with(resolved),
+        })
+    }
+}
+
+fn config_path() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Could not determine home directory")?;
+    Ok(home.join(".pocketpaw").join(CONFIG_FILE))
+}
+
+fn load_config() -> ScopeConfig {
+    let path = match config_path() {
+        Ok(path) => path,
+        Err(_) => return ScopeConfig::default(),
+    };
+    match fs::read_to_string(&path) {
+        Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+            log::warn!("Invalid {}, using defaults: {}", CONFIG_FILE, e);
+            ScopeConfig::default()
+        }),
+        Err(_) => ScopeConfig::default(),
+    }
+}
+
+fn policy() -> &'static RwLock<Policy> {
+    static POLICY: OnceLock<RwLock<Policy>> = OnceLock::new();
+    POLICY.get_or_init(|| RwLock::new(Policy::new(load_config())))
+}
+
+/// Case-insensitive filesystems are compared in lowercase.
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn fold(path: &Path) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().to_lowercase())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn fold(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
+/// Match `name` against a pattern where `*` matches any run of characters.
+fn wildcard_match(pattern: &str, name: &OsStr) -> bool {
+    let name = fold(Path::new(name)).to_string_lossy().into_owned();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == name[n] {
+            p += 1;
+            n += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Resolve `path` the way the OS will when it is opened: canonicalize the
+/// longest existing prefix (following symlinks) and append the rest.
+fn resolve(path: &Path) -> Result<PathBuf, &'static str> {
+    if !path.is_absolute() {
+        return Err("path must be absolute");
+    }
+    let mut existing = path;
+    let mut missing: Vec<&OsStr> = Vec::new();
+    loop {
+        if let Ok(mut resolved) = fs::canonicalize(existing) {
+            resolved.extend(missing.iter().rev());
+            return Ok(resolved);
+        }
+        match existing.components().next_back() {
+            Some(Component::Normal(name)) => missing.push(name),
+            // `..` below a missing directory can't be resolved
+            _ => return Err("path cannot be resolved"),
+        }
+        existing = existing.parent().ok_or("path cannot be resolved")?;
+    }
+}
+
+/// Refuse workspace roots that would open up the whole system: filesystem
+/// roots, folders above the home directory (other users' homes) and system
+/// folders. `root` is already resolved.
+fn check_workspace(root: &Path) -> Result<(), &'static str> {
+    let root = fold(root);
+    if root.parent().is_none() {
+        return Err("a filesystem root can't be a workspace");
+    }
+    if let Some(home) = dirs::home_dir().and_then(|home| resolve(&home).ok()) {
+        let home = fold(&home);
+        if root.starts_with(&home) {
+            // Even where home lives below /var, as on Fedora Silverblue
+            return Ok(());
+        }
+        if home.starts_with(&root) {
+            return Err("a folder above the home folder can't be a workspace");
+        }
+    }
+    if system_dirs().iter().any(|dir| root.starts_with(dir)) {
+        return Err("a system folder can't be a workspace");
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn system_dirs() -> Vec<PathBuf> {
+    SYSTEM_DIRS
+        .iter()
+        .flat_map(|dir| {
+            // Also the resolved form, e.g. /etc -> /private/etc on macOS
+            let dir = Path::new(dir);
+            [Some(dir.to_path_buf()), fs::canonicalize(dir).ok()]
+        })
+        .flatten()
+        .map(|dir| fold(&dir))
+        .collect()
+}
+
+#[cfg(windows)]
+fn system_dirs() -> Vec<PathBuf> {
+    [
+        "SystemRoot",
+        "ProgramFiles",
+        "ProgramFiles(x86)",
+        "ProgramData",
+    ]
+    .iter()
+    .filter_map(|var| std::env::var_os(var))
+    .filter_map(|dir| resolve(Path::new(&dir)).ok())
+    .map(|dir| fold(&dir))
+    .collect()
+}
+
+fn denied(path: &Path, reason: &str) -> PermissionDenied {
+    log::warn!(
+        "Blocked filesystem access to {}: {}",
+        path.display(),
+        reason
+    );
+    PermissionDenied {
+        path: path.to_string_lossy().to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// Check that `path` may be accessed. Returns the resolved path; callers keep
+/// operating on the original so that e.g. deleting a symlink removes the link.
+pub fn check(path: impl AsRef<Path>) -> Result<PathBuf, PermissionDenied> {
+    let path = path.as_ref();
+    let resolved = resolve(path).map_err(|reason| denied(path, reason))?;
+    let policy = policy().read().unwrap();
+    match policy.violation(&fold(&resolved)) {
+        Some(reason) => Err(denied(path, reason)),
+        None => Ok(resolved),
+    }
+}
+
+/// Whether `path` may be accessed, without logging a refusal. For filtering
+/// listings.
+pub fn allows(path: impl AsRef<Path>) -> bool {
+    let Ok(resolved) = resolve(path.as_ref()) else {
+        return false;
+    };
+    let policy = policy().read().unwrap();
+    policy.violation(&fold(&resolved)).is_none()
+}
+
+/// Like `check`, and additionally refuse if a denied location lies anywhere
+/// below `path`. Used for operations that act on a whole tree (recursive
+/// delete, directory copy, moving a directory).
+pub fn check_tree(path: impl AsRef<Path>) -> Result<PathBuf, PermissionDenied> {
+    let path = path.as_ref();
+    let resolved = check(path)?;
+    if policy().read().unwrap().contains_denied(&fold(&resolved)) {
+        return Err(denied(path, "contains a protected location"));
+    }
+    Ok(resolved)
+}
+
+#[tauri::command]
+pub fn fs_get_scope() -> ScopeConfig {
+    policy().read().unwrap().config.clone()
+}
+
+/// Replace the scope config. Workspace roots must be existing directories,
+/// and not a filesystem root, a folder above home or a system folder.
+#[tauri::command]
+pub fn fs_set_scope(config: ScopeConfig) -> Result<(), String> {
+    for workspace in &config.workspaces {
+        let path = Path::new(workspace);
+        if !path.is_absolute() || !path.is_dir() {
+            return Err(format!("Workspace root is not a directory: {}", workspace));
+        }
+        resolve(path)
+            .and_then(|root| check_workspace(&root))
+            .map_err(|reason| format!("Workspace root not allowed, {}: {}", reason, workspace))?;
+    }
+
+    let path = config_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
+    }
+    let data = serde_json::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialize scope: {}", e))?;
+    fs::write(&path, data).map_err(|e| format!("Failed to save scope: {}", e))?;
+
+    *policy().write().unwrap() = Policy::new(config);
+    Ok(())
+}
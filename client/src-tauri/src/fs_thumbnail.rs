@@ -3,6 +3,8 @@ use base64::engine::general_purpose::STANDARD as BASE64;
 use image::ImageReader;
 use serde::Serialize;
 use sha2::{Digest, Sha256};
+
+use crate::fs_scope;
 use std::fs;
 use std::io::Cursor;
 use std::path::Path;
@@ -47,6 +49,7 @@ fn get_modified_time(path: &Path) -> u64 {
 
 #[tauri::command]
 pub async fn fs_thumbnail(path: String) -> Result<ThumbnailResult, String> {
+    fs_scope::check(&path)?;
     let file_path = Path::new(&path);
 
     if !file_path.exists() {
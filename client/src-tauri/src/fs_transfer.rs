@@ -0,0 +1,766 @@
+// Copy and move jobs with progress, cancellation and a conflict policy.
+//
+// `fs_transfer` checks the request, then works on a background thread and
+// returns straight away, like `fs_grep`. The calling window gets throttled
+// `fs-transfer-progress` events, an `fs-transfer-conflict` event whenever a
+// destination exists and the policy is `ask` (answered with
+// `fs_transfer_resolve`), and one `fs-transfer-done` event with the report.
+// Every event carries the caller-chosen job id, which `fs_transfer_cancel`
+// also takes.
+//
+// Symlinks are recreated as links, never followed. Files are copied through
+// a temporary file next to the destination, so a cancelled or failed copy
+// leaves no truncated file behind. A failure on one entry is added to the
+// report and the job carries on with the rest. Moves rename where possible
+// and copy then delete across filesystems.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+use crate::fs_journal;
+use crate::fs_scope;
+use crate::fs_trash;
+use crate::fs_write;
+
+const COPY_BUFFER_BYTES: usize = 1024 * 1024;
+/// Progress events are sent at most this often.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferMode {
+    Copy,
+    Move,
+}
+
+/// What to do when the destination already exists.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    Skip,
+    /// Replace files; folders are merged.
+    Overwrite,
+    /// Keep both, naming the new one "name (2).ext".
+    Rename,
+    /// Ask the calling window each time.
+    Ask,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct TransferRequest {
+    pub mode: TransferMode,
+    pub sources: Vec<String>,
+    /// Directory the sources are copied or moved into.
+    pub dest_dir: String,
+    pub conflict: ConflictPolicy,
+    /// Keep modification times and, where permitted, ownership. Permissions
+    /// are always kept.
+    pub preserve_metadata: bool,
+}
+
+impl Default for TransferRequest {
+    fn default() -> Self {
+        Self {
+            mode: TransferMode::Copy,
+            sources: Vec::new(),
+            dest_dir: String::new(),
+            conflict: ConflictPolicy::Ask,
+            preserve_metadata: true,
+        }
+    }
+}
+
+/// Reply to an `fs-transfer-conflict` event.
+#[derive(Debug, Deserialize)]
+pub struct ConflictAnswer {
+    /// `ask` is treated as `skip`.
+    pub action: ConflictPolicy,
+    /// Use the same action for the remaining conflicts of the job.
+    #[serde(default)]
+    pub apply_to_all: bool,
+}
+
+/// Payload of `fs-transfer-progress`.
+#[derive(Debug, Serialize, Clone)]
+pub struct TransferProgress {
+    pub job_id: String,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub files_done: u64,
+    pub files_total: u64,
+    pub current_path: String,
+}
+
+/// Payload of `fs-transfer-conflict`.
+#[derive(Debug, Serialize, Clone)]
+pub struct TransferConflict {
+    pub job_id: String,
+    pub source: String,
+    pub dest: String,
+    pub source_is_dir: bool,
+    pub dest_is_dir: bool,
+    pub source_size: u64,
+    pub dest_size: u64,
+    pub source_modified: u64,
+    pub dest_modified: u64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TransferFailure {
+    pub path: String,
+    pub error: String,
+}
+
+/// Payload of `fs-transfer-done`.
+#[derive(Debug, Serialize, Clone)]
+pub struct TransferReport {
+    pub job_id: String,
+    pub files_done: u64,
+    pub bytes_done: u64,
+    /// Entries left alone because of the conflict policy.
+    pub skipped: u64,
+    pub failures: Vec<TransferFailure>,
+    pub cancelled: bool,
+    /// Where the sources ended up, for those that made it.
+    pub destinations: Vec<String>,
+}
+
+struct Job {
+    cancelled: Arc<AtomicBool>,
+    answers: Sender<ConflictAnswer>,
+}
+
+/// The running jobs, by job id.
+fn jobs() -> &'static Mutex<HashMap<String, Job>> {
+    static JOBS: OnceLock<Mutex<HashMap<String, Job>>> = OnceLock::new();
+    JOBS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Where an entry goes once conflicts are settled.
+enum Target {
+    /// Nothing there (any more).
+    Fresh(PathBuf),
+    /// An existing folder to copy a folder's contents into.
+    Merge(PathBuf),
+    /// An existing entry to replace.
+    Replace(PathBuf),
+    Skip,
+}
+
+fn mtime_secs(meta: &fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Files (including symlinks) and bytes below `path`, without following
+/// links.
+fn tally(path: &Path) -> (u64, u64) {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| tally(&entry.path()))
+            .fold((0, 0), |(files, bytes), (f, b)| (files + f, bytes + b)),
+        Ok(meta) if meta.is_file() => (1, meta.len()),
+        Ok(_) => (1, 0),
+        Err(_) => (0, 0),
+    }
+}
+
+/// `dest` with " (2)", " (3)", ... added before the extension, whichever is
+/// free first.
+fn unique_name(dest: &Path, is_dir: bool) -> PathBuf {
+    let name = dest
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (stem, ext) = match name.rfind('.') {
+        Some(dot) if dot > 0 && !is_dir => (&name[..dot], &name[dot..]),
+        _ => (name.as_str(), ""),
+    };
+    (2..)
+        .map(|n| dest.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|candidate| fs::symlink_metadata(candidate).is_err())
+        .unwrap_or_else(|| dest.to_path_buf())
+}
+
+fn copy_symlink(src: &Path, dest: &Path) -> io::Result<()> {
+    let target = fs::read_link(src)?;
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(&target, dest)
+    }
+    #[cfg(windows)]
+    {
+        if fs::metadata(src).is_ok_and(|m| m.is_dir()) {
+            std::os::windows::fs::symlink_dir(&target, dest)
+        } else {
+            std::os::windows::fs::symlink_file(&target, dest)
+        }
+    }
+}
+
+/// Set `file`'s access and modification times to `meta`'s.
+fn copy_times(file: &fs::File, meta: &fs::Metadata) -> io::Result<()> {
+    let mut times = fs::FileTimes::new();
+    if let Ok(accessed) = meta.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    if let Ok(modified) = meta.modified() {
+        times = times.set_modified(modified);
+    }
+    file.set_times(times)
+}
+
+#[cfg(unix)]
+fn copy_owner(path: &Path, meta: &fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+    // Only possible as root or for one of our own groups
+    let _ = std::os::unix::fs::chown(path, Some(meta.uid()), Some(meta.gid()));
+}
+
+#[cfg(not(unix))]
+fn copy_owner(_path: &Path, _meta: &fs::Metadata) {}
+
+/// One copy or move job.
+pub(crate) struct Transfer {
+    mode: TransferMode,
+    policy: ConflictPolicy,
+    preserve_metadata: bool,
+    cancelled: Arc<AtomicBool>,
+    /// Answers to conflict questions; without it `ask` skips.
+    answers: Option<Receiver<ConflictAnswer>>,
+    /// Window the events go to; without it the job runs silently.
+    target: Option<(AppHandle, String)>,
+    report: TransferReport,
+    bytes_total: u64,
+    files_total: u64,
+    current: PathBuf,
+    last_progress: Option<Instant>,
+    buffer: Vec<u8>,
+}
+
+impl Transfer {
+    fn new(job_id: String, request: &TransferRequest, cancelled: Arc<AtomicBool>) -> Self {
+        Self {
+            mode: request.mode,
+            policy: request.conflict,
+            preserve_metadata: request.preserve_metadata,
+            cancelled,
+            answers: None,
+            target: None,
+            report: TransferReport {
+                job_id,
+                files_done: 0,
+                bytes_done: 0,
+                skipped: 0,
+                failures: Vec::new(),
+                cancelled: false,
+                destinations: Vec::new(),
+            },
+            bytes_total: 0,
+            files_total: 0,
+            current: PathBuf::new(),
+            last_progress: None,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        if let Some((app, label)) = &self.target {
+            let _ = app.emit_to(label.as_str(), event, payload);
+        }
+    }
+
+    fn progress(&mut self, force: bool) {
+        if !force
+            && self
+                .last_progress
+                .is_some_and(|at| at.elapsed() < PROGRESS_INTERVAL)
+        {
+            return;
+        }
+        self.last_progress = Some(Instant::now());
+        self.emit(
+            "fs-transfer-progress",
+            TransferProgress {
+                job_id: self.report.job_id.clone(),
+                bytes_done: self.report.bytes_done,
+                bytes_total: self.bytes_total,
+                files_done: self.report.files_done,
+                files_total: self.files_total,
+                current_path: self.current.to_string_lossy().to_string(),
+            },
+        );
+    }
+
+    fn fail(&mut self, path: &Path, error: String) {
+        // Entries interrupted by cancelling aren't failures
+        if !self.is_cancelled() {
+            self.report.failures.push(TransferFailure {
+                path: path.to_string_lossy().to_string(),
+                error,
+            });
+        }
+    }
+
+    /// Count `path` as done without transferring it.
+    fn pass_over(&mut self, path: &Path) {
+        let (files, bytes) = tally(path);
+        self.report.files_done += files;
+        self.report.bytes_done += bytes;
+    }
+
+    /// Ask the window what to do about an existing destination.
+    fn ask(
+        &mut self,
+        src: &Path,
+        meta: &fs::Metadata,
+        dest: &Path,
+        existing: &fs::Metadata,
+    ) -> ConflictPolicy {
+        if self.answers.is_none() {
+            return ConflictPolicy::Skip;
+        }
+        self.emit(
+            "fs-transfer-conflict",
+            TransferConflict {
+                job_id: self.report.job_id.clone(),
+                source: src.to_string_lossy().to_string(),
+                dest: dest.to_string_lossy().to_string(),
+                source_is_dir: meta.is_dir(),
+                dest_is_dir: existing.is_dir(),
+                source_size: meta.len(),
+                dest_size: existing.len(),
+                source_modified: mtime_secs(meta),
+                dest_modified: mtime_secs(existing),
+            },
+        );
+        let answers = self.answers.as_ref().unwrap();
+        let answer = loop {
+            if self.cancelled.load(Ordering::SeqCst) {
+                return ConflictPolicy::Skip;
+            }
+            match answers.recv_timeout(Duration::from_millis(200)) {
+                Ok(answer) => break answer,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => return ConflictPolicy::Skip,
+            }
+        };
+        let action = match answer.action {
+            ConflictPolicy::Ask => ConflictPolicy::Skip,
+            action => action,
+        };
+        if answer.apply_to_all {
+            self.policy = action;
+        }
+        action
+    }
+
+    fn target(&mut self, src: &Path, meta: &fs::Metadata, dest: &Path) -> Target {
+        let Ok(existing) = fs::symlink_metadata(dest) else {
+            return Target::Fresh(dest.to_path_buf());
+        };
+        // Copying next to the original makes a copy; moving onto itself is
+        // a no-op
+        if src == dest {
+            return match self.mode {
+                TransferMode::Copy => Target::Fresh(unique_name(dest, meta.is_dir())),
+                TransferMode::Move => Target::Skip,
+            };
+        }
+        let policy = match self.policy {
+            ConflictPolicy::Ask => self.ask(src, meta, dest, &existing),
+            policy => policy,
+        };
+        match policy {
+            ConflictPolicy::Rename => Target::Fresh(unique_name(dest, meta.is_dir())),
+            ConflictPolicy::Overwrite if meta.is_dir() && existing.is_dir() => {
+                Target::Merge(dest.to_path_buf())
+            }
+            ConflictPolicy::Overwrite => Target::Replace(dest.to_path_buf()),
+            ConflictPolicy::Skip | ConflictPolicy::Ask => Target::Skip,
+        }
+    }
+
+    /// Copy `src`'s content into a new file at `tmp`.
+    fn copy_contents(&mut self, src: &Path, tmp: &Path, meta: &fs::Metadata) -> io::Result<()> {
+        let mut input = fs::File::open(src)?;
+        let mut output = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(tmp)?;
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.resize(COPY_BUFFER_BYTES, 0);
+        let copied = loop {
+            if self.is_cancelled() {
+                break Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+            }
+            let n = match input.read(&mut buffer) {
+                Ok(0) => break Ok(()),
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => break Err(e),
+            };
+            if let Err(e) = output.write_all(&buffer[..n]) {
+                break Err(e);
+            }
+            self.report.bytes_done += n as u64;
+            self.progress(false);
+        };
+        self.buffer = buffer;
+        copied?;
+        fs::set_permissions(tmp, meta.permissions())?;
+        if self.preserve_metadata {
+            copy_times(&output, meta)?;
+            copy_owner(tmp, meta);
+        }
+        // The source is deleted next; make sure the copy is on disk and
+        // matches it first
+        if self.mode == TransferMode::Move {
+            output.sync_all()?;
+            let now = fs::metadata(src)?;
+            if output.metadata()?.len() != meta.len()
+                || now.len() != meta.len()
+                || now.modified().ok() != meta.modified().ok()
+            {
+                return Err(io::Error::other("the file changed while it was copied"));
+            }
+        }
+        Ok(())
+    }
+
+    fn copy_file(&mut self, src: &Path, dest: &Path, meta: &fs::Metadata) -> Result<(), String> {
+        let tmp = fs_write::temp_path(dest)?;
+        let result = self
+            .copy_contents(src, &tmp, meta)
+            .and_then(|()| fs::rename(&tmp, dest));
+        if let Err(e) = result {
+            let _ = fs::remove_file(&tmp);
+            return Err(format!("Failed to copy {}: {}", src.display(), e));
+        }
+        Ok(())
+    }
+
+    /// Copy a folder's entries into `dest`, which exists. Returns whether
+    /// every entry made it.
+    fn copy_children(&mut self, src: &Path, dest: &Path) -> bool {
+        let entries = match fs::read_dir(src) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.fail(src, format!("Failed to read dir: {}", e));
+                return false;
+            }
+        };
+        let mut complete = true;
+        for entry in entries {
+            if self.is_cancelled() {
+                return false;
+            }
+            match entry {
+                Ok(entry) => {
+                    complete &= self
+                        .item(&entry.path(), &dest.join(entry.file_name()), false)
+                        .is_some();
+                }
+                Err(e) => {
+                    self.fail(src, format!("Failed to read entry: {}", e));
+                    complete = false;
+                }
+            }
+        }
+        complete
+    }
+
+    /// Transfer `src` to `dest`, settling conflicts first. Returns where it
+    /// ended up, or `None` if it was skipped or didn't fully make it.
+    fn item(&mut self, src: &Path, dest: &Path, top: bool) -> Option<PathBuf> {
+        if self.is_cancelled() {
+            return None;
+        }
+        self.current = src.to_path_buf();
+        self.progress(false);
+        let meta = match fs::symlink_metadata(src) {
+            Ok(meta) => meta,
+            Err(e) => {
+                self.fail(src, format!("Failed to read {}: {}", src.display(), e));
+                return None;
+            }
+        };
+
+        let (dest, merge) = match self.target(src, &meta, dest) {
+            Target::Skip => {
+                self.report.skipped += 1;
+                self.pass_over(src);
+                return None;
+            }
+            Target::Fresh(dest) => (dest, false),
+            Target::Merge(dest) => (dest, true),
+            Target::Replace(dest) => {
+                // A file replaces a file in one rename; anything else has to
+                // make room first
+                let both_files =
+                    meta.is_file() && fs::symlink_metadata(&dest).is_ok_and(|m| m.is_file());
+                if !both_files {
+                    if let Err(e) = fs_trash::move_to_trash(&dest) {
+                        self.fail(src, e);
+                        return None;
+                    }
+                }
+                (dest, false)
+            }
+        };
+        let before = (top && meta.is_file() && self.mode == TransferMode::Copy)
+            .then(|| fs_journal::capture(&dest));
+
+        if self.mode == TransferMode::Move && !merge {
+            match fs::rename(src, &dest) {
+                Ok(()) => {
+                    self.pass_over(&dest);
+                    if top {
+                        fs_journal::record_rename(src, &dest);
+                    }
+                    return Some(dest);
+                }
+                // Different filesystems: copy, then delete below
+                Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+                Err(e) => {
+                    self.fail(src, format!("Failed to move: {}", e));
+                    return None;
+                }
+            }
+        }
+
+        let copied = if meta.is_symlink() {
+            copy_symlink(src, &dest)
+                .map(|()| self.report.files_done += 1)
+                .map_err(|e| format!("Failed to copy link {}: {}", src.display(), e))
+        } else if meta.is_dir() {
+            let created = if merge {
+                Ok(())
+            } else {
+                fs::create_dir(&dest)
+                    .map_err(|e| format!("Failed to create dir {}: {}", dest.display(), e))
+            };
+            match created {
+                Ok(()) if self.copy_children(src, &dest) => {
+                    let _ = fs::set_permissions(&dest, meta.permissions());
+                    if self.preserve_metadata {
+                        // Directories can't be opened for this on Windows
+                        if let Ok(dir) = fs::File::open(&dest) {
+                            let _ = copy_times(&dir, &meta);
+                        }
+                        copy_owner(&dest, &meta);
+                    }
+                    Ok(())
+                }
+                // The failing entries are already in the report
+                Ok(()) => return None,
+                Err(e) => Err(e),
+            }
+        } else {
+            self.copy_file(src, &dest, &meta)
+                .map(|()| self.report.files_done += 1)
+        };
+        if let Err(e) = copied {
+            self.fail(src, e);
+            return None;
+        }
+
+        if self.mode == TransferMode::Move {
+            let removed = if meta.is_dir() {
+                // Its entries were moved one by one
+                fs::remove_dir(src)
+            } else {
+                fs::remove_file(src)
+            };
+            if let Err(e) = removed {
+                self.fail(
+                    src,
+                    format!("Copied, but failed to delete the original: {}", e),
+                );
+                return None;
+            }
+            if top && !merge {
+                // Journaled like the rename it stands in for
+                fs_journal::record_rename(src, &dest);
+            }
+        } else if top && !merge {
+            match before {
+                Some(before) => {
+                    fs_journal::record_replace("copy", &dest, before, fs_journal::capture(&dest))
+                }
+                None if meta.is_dir() => fs_journal::record_copy_dir(src, &dest),
+                None => {}
+            }
+        }
+        Some(dest)
+    }
+
+    /// Transfer each of `sources` into `dest_dir`.
+    fn run(mut self, sources: &[String], dest_dir: &Path) -> TransferReport {
+        for source in sources {
+            let (files, bytes) = tally(Path::new(source));
+            self.files_total += files;
+            self.bytes_total += bytes;
+        }
+        self.progress(true);
+
+        let dest_resolved = fs::canonicalize(dest_dir).unwrap_or_else(|_| dest_dir.to_path_buf());
+        for source in sources {
+            if self.is_cancelled() {
+                break;
+            }
+            let src = Path::new(source);
+            let Some(name) = src.file_name() else {
+                self.fail(src, "Cannot copy a filesystem root".to_string());
+                continue;
+            };
+            let src_resolved = fs::canonicalize(src).unwrap_or_else(|_| src.to_path_buf());
+            if src.is_dir() && !src.is_symlink() && dest_resolved.starts_with(&src_resolved) {
+                self.fail(src, "Cannot copy a folder into itself".to_string());
+                self.pass_over(src);
+                continue;
+            }
+            if let Some(dest) = self.item(src, &dest_dir.join(name), true) {
+                self.report
+                    .destinations
+                    .push(dest.to_string_lossy().to_string());
+            }
+        }
+
+        self.report.cancelled = self.is_cancelled();
+        self.progress(true);
+        self.report
+    }
+}
+
+/// Move `src` to `dest` like `fs::rename`, but copy then delete when they
+/// are on different filesystems. An existing file at `dest` is replaced; an
+/// existing folder is an error, as it would be for a rename across folders.
+pub(crate) fn move_path(src: &Path, dest: &Path) -> Result<(), String> {
+    match fs::rename(src, dest) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        Err(e) => return Err(format!("Failed to move: {}", e)),
+    }
+    let src_is_dir = fs::symlink_metadata(src).is_ok_and(|m| m.is_dir());
+    if let Ok(existing) = fs::symlink_metadata(dest) {
+        if src_is_dir || existing.is_dir() {
+            return Err(format!("Failed to move: {} already exists", dest.display()));
+        }
+    }
+
+    let request = TransferRequest {
+        mode: TransferMode::Move,
+        conflict: ConflictPolicy::Overwrite,
+        ..Default::default()
+    };
+    let mut transfer = Transfer::new(String::new(), &request, Arc::new(AtomicBool::new(false)));
+    transfer.item(src, dest, false);
+    match transfer.report.failures.as_slice() {
+        [] => Ok(()),
+        [only] => Err(format!("Failed to move {}: {}", only.path, only.error)),
+        [first, rest @ ..] => Err(format!(
+            "Failed to move {} and {} more: {}",
+            first.path,
+            rest.len(),
+            first.error
+        )),
+    }
+}
+
+/// Start copying or moving `request.sources` into `request.dest_dir`.
+/// Progress, conflicts and the final report arrive as `fs-transfer-*`
+/// events tagged with `job_id`. Invalid requests are reported here rather
+/// than as events.
+#[tauri::command]
+pub fn fs_transfer(
+    app: AppHandle,
+    webview: tauri::Webview,
+    job_id: String,
+    request: TransferRequest,
+) -> Result<(), String> {
+    if request.sources.is_empty() {
+        return Err("Nothing to transfer".to_string());
+    }
+    for source in &request.sources {
+        fs_scope::check_tree(source)?;
+    }
+    fs_scope::check(&request.dest_dir)?;
+    if !Path::new(&request.dest_dir).is_dir() {
+        return Err(format!("Not a directory: {}", request.dest_dir));
+    }
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let (answers_tx, answers_rx) = mpsc::channel();
+    if let Some(previous) = jobs().lock().unwrap().insert(
+        job_id.clone(),
+        Job {
+            cancelled: cancelled.clone(),
+            answers: answers_tx,
+        },
+    ) {
+        previous.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    let label = webview.label().to_string();
+    let mut transfer = Transfer::new(job_id.clone(), &request, cancelled.clone());
+    transfer.answers = Some(answers_rx);
+    transfer.target = Some((app.clone(), label.clone()));
+    std::thread::spawn(move || {
+        let report = transfer.run(&request.sources, Path::new(&request.dest_dir));
+        {
+            let mut jobs = jobs().lock().unwrap();
+            if jobs
+                .get(&job_id)
+                .is_some_and(|job| Arc::ptr_eq(&job.cancelled, &cancelled))
+            {
+                jobs.remove(&job_id);
+            }
+        }
+        let _ = app.emit_to(label.as_str(), "fs-transfer-done", report);
+    });
+    Ok(())
+}
+
+/// Answer a conflict question of a running job.
+#[tauri::command]
+pub fn fs_transfer_resolve(job_id: String, answer: ConflictAnswer) -> Result<(), String> {
+    let jobs = jobs().lock().unwrap();
+    let job = jobs
+        .get(&job_id)
+        .ok_or_else(|| format!("No transfer running with id {}", job_id))?;
+    job.answers
+        .send(answer)
+        .map_err(|_| format!("Transfer {} has finished", job_id))
+}
+
+/// Cancel a running job. Entries already transferred stay where they are.
+/// Returns false if no job with that id is running.
+#[tauri::command]
+pub fn fs_transfer_cancel(job_id: String) -> bool {
+    match jobs().lock().unwrap().remove(&job_id) {
+        Some(job) => {
+            job.cancelled.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
@@ -0,0 +1,218 @@
+// Move-to-trash deletion for the explorer.
+//
+// `fs_delete` moves items to the system trash (the freedesktop.org Trash with
+// `.trashinfo` files on Linux, the Recycle Bin on Windows, the Finder Trash on
+// macOS) so they can be restored. Removing data for good is the separate
+// `fs_delete_permanently`.
+//
+// Listing and restoring use the trash crate's `os_limited` API, which isn't
+// available on macOS; there `fs_list_trash` and `fs_restore_from_trash` return
+// an error and the Finder's "Put Back" has to be used instead.
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::fs_journal;
+use crate::fs_scope;
+
+/// An item in the trash, as returned by `fs_list_trash`.
+#[derive(Debug, Serialize, Clone)]
+pub struct TrashEntry {
+    /// Platform-specific id, passed back to `fs_restore_from_trash`.
+    pub id: String,
+    pub name: String,
+    pub original_path: String,
+    /// Unix seconds.
+    pub deleted_at: i64,
+}
+
+/// A non-recursive delete of a directory only makes sense for an empty one,
+/// same as `remove_dir`.
+fn ensure_deletable(p: &Path, recursive: bool) -> Result<(), String> {
+    if p.is_dir() && !recursive {
+        let mut entries = fs::read_dir(p).map_err(|e| format!("Failed to read dir: {}", e))?;
+        if entries.next().is_some() {
+            return Err(format!("Directory not empty: {}", p.display()));
+        }
+    }
+    Ok(())
+}
+
+/// Move a file or directory to the system trash.
+#[tauri::command]
+pub fn fs_delete(path: String, recursive: bool) -> Result<(), String> {
+    if recursive {
+        fs_scope::check_tree(&path)?;
+    } else {
+        fs_scope::check(&path)?;
+    }
+    let p = Path::new(&path);
+    ensure_deletable(p, recursive)?;
+    move_to_trash(p)?;
+    fs_journal::record_trash(&trashed_path(p));
+    Ok(())
+}
+
+/// Delete a file or directory without going through the trash.
+#[tauri::command]
+pub fn fs_delete_permanently(path: String, recursive: bool) -> Result<(), String> {
+    if recursive {
+        fs_scope::check_tree(&path)?;
+    } else {
+        fs_scope::check(&path)?;
+    }
+    let p = Path::new(&path);
+    if p.is_dir() {
+        if recursive {
+            fs::remove_dir_all(p).map_err(|e| format!("Failed to delete dir: {}", e))
+        } else {
+            fs::remove_dir(p).map_err(|e| format!("Failed to delete dir: {}", e))
+        }
+    } else {
+        fs::remove_file(p).map_err(|e| format!("Failed to delete file: {}", e))
+    }
+}
+
+#[cfg(desktop)]
+pub(crate) fn move_to_trash(p: &Path) -> Result<(), String> {
+    trash::delete(p).map_err(|e| format!("Failed to move to trash: {}", e))
+}
+
+#[cfg(not(desktop))]
+pub(crate) fn move_to_trash(_p: &Path) -> Result<(), String> {
+    Err("Trash is not available on this platform; use fs_delete_permanently".to_string())
+}
+
+/// The original path the trash records for `p`: its canonical parent plus its
+/// own name, so a trashed symlink is matched as the link.
+fn trashed_path(p: &Path) -> PathBuf {
+    match (
+        p.parent().and_then(|d| fs::canonicalize(d).ok()),
+        p.file_name(),
+    ) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => p.to_path_buf(),
+    }
+}
+
+/// Restore the most recently trashed item that came from `path`.
+pub(crate) fn restore_latest(path: &Path) -> Result<(), String> {
+    if fs::symlink_metadata(path).is_ok() {
+        return Err(format!("Cannot restore, {} already exists", path.display()));
+    }
+    let original = trashed_path(path);
+    let item = platform::list()?
+        .into_iter()
+        .filter(|item| item.original_path() == original)
+        .max_by_key(|item| item.time_deleted)
+        .ok_or_else(|| format!("{} is no longer in the trash", path.display()))?;
+    platform::restore(vec![item])
+}
+
+/// Items in the trash whose original location is inside the fs scope, most
+/// recently deleted first.
+#[tauri::command]
+pub fn fs_list_trash() -> Result<Vec<TrashEntry>, String> {
+    let mut entries: Vec<TrashEntry> = platform::list()?
+        .iter()
+        .filter(|item| fs_scope::allows(item.original_path()))
+        .map(|item| TrashEntry {
+            id: item.id.to_string_lossy().to_string(),
+            name: item.name.to_string_lossy().to_string(),
+            original_path: item.original_path().to_string_lossy().to_string(),
+            deleted_at: item.time_deleted,
+        })
+        .collect();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.deleted_at));
+    Ok(entries)
+}
+
+/// Restore trashed items to their original location. Fails without restoring
+/// anything if an id is unknown or something already exists at an original
+/// path.
+#[tauri::command]
+pub fn fs_restore_from_trash(ids: Vec<String>) -> Result<(), String> {
+    let items: Vec<_> = platform::list()?
+        .into_iter()
+        .filter(|item| {
+            ids.iter()
+                .any(|id| item.id.to_string_lossy() == id.as_str())
+        })
+        .collect();
+    if items.len() != ids.len() {
+        return Err("Some items are no longer in the trash".to_string());
+    }
+    for item in &items {
+        let original = item.original_path();
+        fs_scope::check(&original)?;
+        if original.exists() {
+            return Err(format!(
+                "Cannot restore, {} already exists",
+                original.display()
+            ));
+        }
+    }
+    let restored: Vec<PathBuf> = items.iter().map(|item| item.original_path()).collect();
+    platform::restore(items)?;
+    for path in &restored {
+        fs_journal::record_restore(path);
+    }
+    Ok(())
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+mod platform {
+    pub use trash::TrashItem;
+
+    pub fn list() -> Result<Vec<TrashItem>, String> {
+        trash::os_limited::list().map_err(|e| format!("Failed to list trash: {}", e))
+    }
+
+    pub fn restore(items: Vec<TrashItem>) -> Result<(), String> {
+        trash::os_limited::restore_all(items).map_err(|e| format!("Failed to restore: {}", e))
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+mod platform {
+    use std::ffi::OsString;
+    use std::path::PathBuf;
+
+    /// Same shape as `trash::TrashItem`; never constructed on this platform.
+    pub struct TrashItem {
+        pub id: OsString,
+        pub name: OsString,
+        pub original_parent: PathBuf,
+        pub time_deleted: i64,
+    }
+
+    impl TrashItem {
+        pub fn original_path(&self) -> PathBuf {
+            self.original_parent.join(&self.name)
+        }
+    }
+
+    pub fn list() -> Result<Vec<TrashItem>, String> {
+        Err("Listing the trash is not supported on this platform".to_string())
+    }
+
+    pub fn restore(_items: Vec<TrashItem>) -> Result<(), String> {
+        Err("Restoring from the trash is not supported on this platform".to_string())
+    }
+}
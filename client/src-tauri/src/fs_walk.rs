@@ -0,0 +1,52 @@
+// Directory walking shared by the recursive search commands.
+//
+// By default the walk honors the ignore rules a developer would expect:
+// `.gitignore` files (also outside of git repositories), `.ignore` files,
+// `.git/info/exclude` and the global git excludes file, and skips hidden
+// entries. Both can be switched off per call. The `.git` directory itself is
+// never descended into.
+use ignore::{DirEntry, WalkBuilder};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy)]
+pub struct WalkOptions {
+    /// Skip what .gitignore / .ignore / git excludes ignore.
+    pub respect_ignore: bool,
+    /// Include dot files and dot directories.
+    pub include_hidden: bool,
+    /// Levels below the root to descend; `None` for no limit.
+    pub max_depth: Option<usize>,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            respect_ignore: true,
+            include_hidden: false,
+            max_depth: None,
+        }
+    }
+}
+
+/// A walker over `root` configured by `options`. Symlinks are not followed.
+/// Directories (with everything below them) and files for which `filter`
+/// returns false are skipped.
+pub(crate) fn builder<F>(root: &Path, options: &WalkOptions, filter: F) -> WalkBuilder
+where
+    F: Fn(&DirEntry) -> bool + Send + Sync + 'static,
+{
+    let respect = options.respect_ignore;
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .follow_links(false)
+        .max_depth(options.max_depth)
+        .hidden(!options.include_hidden)
+        .parents(respect)
+        .ignore(respect)
+        .git_ignore(respect)
+        .git_global(respect)
+        .git_exclude(respect)
+        .require_git(false)
+        .filter_entry(move |entry| entry.file_name() != ".git" && filter(entry));
+    builder
+}
@@ -1,90 +1,521 @@
-use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+// Directory watching for the webviews.
+//
+// Every `fs_watch` call creates its own subscription (watcher plus debounce
+// thread) and returns its id; any number can be active at once, from any
+// window. Raw notify events are collected until the subscription has been
+// quiet for its debounce interval (at most `MAX_BATCH_DELAY` after the first
+// event), coalesced per path, and sent as one `fs-change` event to the window
+// that subscribed. A rename is reported as a single `rename` change with
+// `from` and `to` instead of a delete and a create.
+//
+// Network and FUSE filesystems (NFS, SMB, sshfs, 9p container mounts, ...)
+// accept native watches but never deliver events for them, and native
+// watching fails once the OS watch limit is reached. In both cases the
+// subscription polls instead, and `fs_watch` reports which backend it got.
+use notify::event::{CreateKind, ModifyKind, RemoveKind, RenameMode};
+use notify::{Event, EventKind, PollWatcher, RecursiveMode, Watcher};
 use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter, Manager};
 
+use crate::fs_scope;
+
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(150);
+const MAX_DEBOUNCE: Duration = Duration::from_secs(5);
+/// A steady stream of events is still flushed this often.
+const MAX_BATCH_DELAY: Duration = Duration::from_secs(1);
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 #[derive(Debug, Serialize, Clone)]
 pub struct FileChangeEvent {
+    /// For renames, the new path.
     pub path: String,
+    /// "create", "modify", "delete" or "rename".
     pub kind: String,
     pub is_dir: bool,
+    /// Old and new path of a rename.
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// Payload of `fs-change`: the coalesced changes of one debounce interval.
+#[derive(Debug, Serialize, Clone)]
+pub struct FileChangeBatch {
+    pub subscription: u64,
+    pub changes: Vec<FileChangeEvent>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchBackend {
+    /// inotify, FSEvents or ReadDirectoryChangesW.
+    Native,
+    /// Rescans at `poll_interval_ms`, so changes show up with a delay.
+    Poll,
+}
+
+/// Result of `fs_watch`.
+#[derive(Debug, Serialize)]
+pub struct WatchInfo {
+    pub subscription: u64,
+    pub backend: WatchBackend,
+    pub poll_interval_ms: Option<u64>,
+    /// Why polling was chosen.
+    pub reason: Option<String>,
+}
+
+struct Subscription {
+    /// Label of the webview the events go to.
+    label: String,
+    /// Dropping the watcher ends the subscription's debounce thread.
+    _watcher: Box<dyn Watcher + Send>,
 }
 
+#[derive(Default)]
 pub struct WatcherState {
-    watcher: Mutex<Option<RecommendedWatcher>>,
-    watched_path: Mutex<Option<String>>,
+    subscriptions: Mutex<HashMap<u64, Subscription>>,
+    next_id: AtomicU64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Op {
+    Create,
+    Modify,
+    Delete,
+    Rename(PathBuf),
+}
+
+struct Change {
+    op: Op,
+    /// The event said it's a directory; used for paths that are gone.
+    dir_hint: bool,
+}
+
+/// Folds a burst of notify events into one change per path.
+#[derive(Default)]
+struct Coalescer {
+    /// Paths in the order they first changed.
+    order: Vec<PathBuf>,
+    changes: HashMap<PathBuf, Change>,
+    /// Rename sources waiting for their destination, by tracker.
+    moved_from: HashMap<usize, PathBuf>,
+    /// Trackers whose From/To pair was already joined, so the backend's
+    /// combined rename event is a duplicate.
+    joined: HashSet<usize>,
+    /// A rename source from a backend without trackers (FSEvents reports the
+    /// two halves of a rename as consecutive events).
+    untracked_from: Option<PathBuf>,
+}
+
+impl Coalescer {
+    fn record(&mut self, path: PathBuf, op: Op, dir_hint: bool) {
+        let Some(change) = self.changes.get_mut(&path) else {
+            self.order.push(path.clone());
+            self.changes.insert(path, Change { op, dir_hint });
+            return;
+        };
+        change.dir_hint |= dir_hint;
+        change.op = match (&change.op, op) {
+            // Created and gone again within the burst
+            (Op::Create, Op::Delete) => {
+                self.changes.remove(&path);
+                return;
+            }
+            // A renamed file removed again: the original is gone
+            (Op::Rename(from), Op::Delete) => {
+                let from = from.clone();
+                self.changes.remove(&path);
+                self.record(from, Op::Delete, dir_hint);
+                return;
+            }
+            (Op::Create, Op::Modify) => Op::Create,
+            (Op::Rename(from), Op::Modify) => Op::Rename(from.clone()),
+            (Op::Delete, Op::Create) => Op::Modify,
+            (Op::Delete, Op::Modify) => Op::Modify,
+            (Op::Modify, Op::Create) => Op::Modify,
+            (_, op) => op,
+        };
+    }
+
+    fn rename(&mut self, from: PathBuf, to: PathBuf) {
+        let op = match self.changes.remove(&from).map(|c| c.op) {
+            // Renaming something new in this burst just creates it elsewhere
+            Some(Op::Create) => Op::Create,
+            // a -> b -> c is a -> c, and a -> b -> a is at most a modification
+            Some(Op::Rename(original)) if original == to => Op::Modify,
+            Some(Op::Rename(original)) => Op::Rename(original),
+            _ => Op::Rename(from),
+        };
+        if let Some(change) = self.changes.get_mut(&to) {
+            change.op = op;
+        } else {
+            self.order.push(to.clone());
+            self.changes.insert(
+                to,
+                Change {
+                    op,
+                    dir_hint: false,
+                },
+            );
+        }
+    }
+
+    fn push(&mut self, event: Event) {
+        let tracker = event.attrs.tracker();
+        let mut paths = event.paths.into_iter();
+        match event.kind {
+            EventKind::Create(kind) => {
+                for path in paths {
+                    self.record(path, Op::Create, kind == CreateKind::Folder);
+                }
+            }
+            EventKind::Remove(kind) => {
+                for path in paths {
+                    self.record(path, Op::Delete, kind == RemoveKind::Folder);
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(mode)) => match mode {
+                RenameMode::From => {
+                    if let Some(path) = paths.next() {
+                        match tracker {
+                            Some(tracker) => {
+                                self.moved_from.insert(tracker, path);
+                            }
+                            None => self.record(path, Op::Delete, false),
+                        }
+                    }
+                }
+                RenameMode::To => {
+                    if let Some(path) = paths.next() {
+                        match tracker.and_then(|t| self.moved_from.remove(&t)) {
+                            Some(from) => {
+                                self.joined.extend(tracker);
+                                self.rename(from, path);
+                            }
+                            None => self.record(path, Op::Create, false),
+                        }
+                    }
+                }
+                RenameMode::Both => {
+                    if tracker.is_some_and(|t| self.joined.contains(&t)) {
+                        return;
+                    }
+                    if let (Some(from), Some(to)) = (paths.next(), paths.next()) {
+                        self.rename(from, to);
+                    }
+                }
+                _ => {
+                    for path in paths {
+                        if path.exists() {
+                            match self.untracked_from.take() {
+                                Some(from) => self.rename(from, path),
+                                None => self.record(path, Op::Create, false),
+                            }
+                        } else if let Some(previous) = self.untracked_from.replace(path) {
+                            self.record(previous, Op::Delete, false);
+                        }
+                    }
+                }
+            },
+            EventKind::Modify(_) => {
+                for path in paths {
+                    self.record(path, Op::Modify, false);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(mut self) -> Vec<FileChangeEvent> {
+        // Halves of renames whose other half was outside the watched tree
+        let unmatched: Vec<PathBuf> = self
+            .moved_from
+            .drain()
+            .map(|(_, path)| path)
+            .chain(self.untracked_from.take())
+            .collect();
+        for path in unmatched {
+            self.record(path, Op::Delete, false);
+        }
+
+        let mut events = Vec::new();
+        for path in self.order {
+            let Some(change) = self.changes.remove(&path) else {
+                continue;
+            };
+            if !fs_scope::allows(&path) {
+                continue;
+            }
+            let is_dir = std::fs::symlink_metadata(&path)
+                .map(|m| m.is_dir())
+                .unwrap_or(change.dir_hint);
+            let display = path.to_string_lossy().to_string();
+            let (kind, from, to) = match change.op {
+                Op::Create => ("create", None, None),
+                Op::Modify => ("modify", None, None),
+                Op::Delete => ("delete", None, None),
+                Op::Rename(from) => (
+                    "rename",
+                    Some(from.to_string_lossy().to_string()),
+                    Some(display.clone()),
+                ),
+            };
+            events.push(FileChangeEvent {
+                path: display,
+                kind: kind.to_string(),
+                is_dir,
+                from,
+                to,
+            });
+        }
+        events
+    }
 }
 
-impl Default for WatcherState {
-    fn default() -> Self {
-        Self {
-            watcher: Mutex::new(None),
-            watched_path: Mutex::new(None),
+/// Debounce thread of one subscription; ends when its watcher is dropped.
+fn run(app: AppHandle, id: u64, label: String, rx: Receiver<Event>, debounce: Duration) {
+    let mut open = true;
+    while open {
+        let Ok(first) = rx.recv() else {
+            break;
+        };
+        let started = Instant::now();
+        let mut batch = Coalescer::default();
+        batch.push(first);
+        loop {
+            let wait = debounce.min(MAX_BATCH_DELAY.saturating_sub(started.elapsed()));
+            match rx.recv_timeout(wait) {
+                Ok(event) => batch.push(event),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    open = false;
+                    break;
+                }
+            }
+        }
+        let changes = batch.finish();
+        if !changes.is_empty() {
+            let payload = FileChangeBatch {
+                subscription: id,
+                changes,
+            };
+            let _ = app.emit_to(label.as_str(), "fs-change", payload);
         }
     }
 }
 
-fn event_kind_to_string(kind: &EventKind) -> Option<&'static str> {
-    match kind {
-        EventKind::Create(_) => Some("create"),
-        EventKind::Modify(_) => Some("modify"),
-        EventKind::Remove(_) => Some("delete"),
+/// The kind of filesystem `path` is on, if it's one where native change
+/// events don't arrive.
+#[cfg(target_os = "linux")]
+pub(crate) fn remote_filesystem(path: &Path) -> Option<&'static str> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    // Magic numbers from linux/magic.h; f_type is signed on some targets
+    match stat.f_type as u64 & 0xFFFF_FFFF {
+        0x6969 => Some("NFS"),
+        0x517B => Some("SMB"),
+        0xFF53_4D42 => Some("CIFS"),
+        0xFE53_4D42 => Some("SMB2"),
+        0x6573_5546 => Some("FUSE"),
+        0x0102_1997 => Some("9P"),
+        0x786F_4256 => Some("VirtualBox shared folder"),
+        0x00C3_6400 => Some("Ceph"),
+        0x5346_414F => Some("AFS"),
+        0x7375_7245 => Some("Coda"),
         _ => None,
     }
 }
 
-#[tauri::command]
-pub fn fs_watch(path: String, app: AppHandle) -> Result<(), String> {
-    let state = app.state::<WatcherState>();
+#[cfg(target_os = "macos")]
+pub(crate) fn remote_filesystem(path: &Path) -> Option<&'static str> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let name = unsafe { std::ffi::CStr::from_ptr(stat.f_fstypename.as_ptr()) };
+    match name.to_bytes() {
+        b"nfs" => Some("NFS"),
+        b"smbfs" => Some("SMB"),
+        b"afpfs" => Some("AFP"),
+        b"webdav" => Some("WebDAV"),
+        name if name.windows(4).any(|w| w == b"fuse") => Some("FUSE"),
+        _ => None,
+    }
+}
 
-    // Stop existing watcher
-    {
-        let mut w = state.watcher.lock().map_err(|e| e.to_string())?;
-        *w = None;
-        let mut wp = state.watched_path.lock().map_err(|e| e.to_string())?;
-        *wp = None;
+#[cfg(windows)]
+pub(crate) fn remote_filesystem(path: &Path) -> Option<&'static str> {
+    use windows::core::HSTRING;
+    use windows::Win32::Storage::FileSystem::GetDriveTypeW;
+    use windows::Win32::System::WindowsProgramming::DRIVE_REMOTE;
+    let text = path.to_string_lossy();
+    if (text.starts_with(r"\\") && !text.starts_with(r"\\?\")) || text.starts_with(r"\\?\UNC\") {
+        return Some("network share");
     }
+    // The drive root, e.g. C:\
+    let root = path.ancestors().last()?;
+    let drive_type = unsafe { GetDriveTypeW(&HSTRING::from(root.as_os_str())) };
+    (drive_type == DRIVE_REMOTE).then_some("network drive")
+}
 
-    let app_handle = app.clone();
-    let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub(crate) fn remote_filesystem(_path: &Path) -> Option<&'static str> {
+    None
+}
+
+fn start_watcher(
+    backend: WatchBackend,
+    path: &Path,
+    mode: RecursiveMode,
+    poll_interval: Duration,
+    tx: Sender<Event>,
+) -> notify::Result<Box<dyn Watcher + Send>> {
+    let handler = move |res: Result<Event, notify::Error>| {
         if let Ok(event) = res {
-            if let Some(kind_str) = event_kind_to_string(&event.kind) {
-                for path in &event.paths {
-                    let is_dir = std::fs::metadata(path)
-                        .map(|m| m.is_dir())
-                        .unwrap_or(false);
-                    let payload = FileChangeEvent {
-                        path: path.to_string_lossy().to_string(),
-                        kind: kind_str.to_string(),
-                        is_dir,
-                    };
-                    let _ = app_handle.emit("fs-change", &payload);
-                }
-            }
+            let _ = tx.send(event);
         }
-    })
-    .map_err(|e| format!("Failed to create watcher: {}", e))?;
-
-    watcher
-        .watch(path.as_ref(), RecursiveMode::NonRecursive)
-        .map_err(|e| format!("Failed to watch path: {}", e))?;
+    };
+    let mut watcher: Box<dyn Watcher + Send> = match backend {
+        WatchBackend::Native => Box::new(notify::recommended_watcher(handler)?),
+        WatchBackend::Poll => Box::new(PollWatcher::new(
+            handler,
+            notify::Config::default().with_poll_interval(poll_interval),
+        )?),
+    };
+    watcher.watch(path, mode)?;
+    Ok(watcher)
+}
 
-    {
-        let mut w = state.watcher.lock().map_err(|e| e.to_string())?;
-        *w = Some(watcher);
-        let mut wp = state.watched_path.lock().map_err(|e| e.to_string())?;
-        *wp = Some(path);
+/// Native watching failed for a reason polling doesn't share, e.g. the
+/// inotify watch or instance limit.
+fn polling_helps(error: &notify::Error) -> bool {
+    match &error.kind {
+        notify::ErrorKind::MaxFilesWatch => true,
+        notify::ErrorKind::Io(e) => !matches!(
+            e.kind(),
+            std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied
+        ),
+        _ => false,
     }
+}
 
-    Ok(())
+/// Watch `path` for changes, including everything below it when `recursive`
+/// is set. Falls back to polling every `poll_interval_ms` where native
+/// events are unavailable; the result says which backend is used.
+#[tauri::command]
+pub fn fs_watch(
+    app: AppHandle,
+    webview: tauri::Webview,
+    path: String,
+    recursive: Option<bool>,
+    debounce_ms: Option<u64>,
+    poll_interval_ms: Option<u64>,
+) -> Result<WatchInfo, String> {
+    fs_scope::check(&path)?;
+    let state = app.state::<WatcherState>();
+    let debounce = debounce_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_DEBOUNCE)
+        .min(MAX_DEBOUNCE);
+    let poll_interval = poll_interval_ms
+        .map(Duration::from_millis)
+        .unwrap_or(DEFAULT_POLL_INTERVAL)
+        .max(MIN_POLL_INTERVAL);
+    let mode = if recursive.unwrap_or(false) {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    let target = Path::new(&path);
+
+    let (tx, rx) = mpsc::channel();
+    let (watcher, backend, reason) = match remote_filesystem(target) {
+        Some(filesystem) => {
+            let watcher = start_watcher(WatchBackend::Poll, target, mode, poll_interval, tx)
+                .map_err(|e| format!("Failed to watch path: {}", e))?;
+            let reason = format!("{} filesystems don't report changes", filesystem);
+            (watcher, WatchBackend::Poll, Some(reason))
+        }
+        None => match start_watcher(
+            WatchBackend::Native,
+            target,
+            mode,
+            poll_interval,
+            tx.clone(),
+        ) {
+            Ok(watcher) => (watcher, WatchBackend::Native, None),
+            Err(e) if polling_helps(&e) => {
+                log::warn!("Native watch of {} failed, polling: {}", path, e);
+                let watcher = start_watcher(WatchBackend::Poll, target, mode, poll_interval, tx)
+                    .map_err(|e| format!("Failed to watch path: {}", e))?;
+                let reason = format!("Native file watching failed: {}", e);
+                (watcher, WatchBackend::Poll, Some(reason))
+            }
+            Err(e) => return Err(format!("Failed to watch path: {}", e)),
+        },
+    };
+
+    let id = state.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+    let label = webview.label().to_string();
+    state
+        .subscriptions
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(
+            id,
+            Subscription {
+                label: label.clone(),
+                _watcher: watcher,
+            },
+        );
+    std::thread::spawn(move || run(app, id, label, rx, debounce));
+    Ok(WatchInfo {
+        subscription: id,
+        backend,
+        poll_interval_ms: (backend == WatchBackend::Poll)
+            .then_some(poll_interval.as_millis() as u64),
+        reason,
+    })
 }
 
+/// End a subscription of the calling window. Unknown ids, and ids of other
+/// windows' subscriptions, are ignored.
 #[tauri::command]
-pub fn fs_unwatch(app: AppHandle) -> Result<(), String> {
+pub fn fs_unwatch(
+    app: AppHandle,
+    webview: tauri::Webview,
+    subscription: u64,
+) -> Result<(), String> {
     let state = app.state::<WatcherState>();
-    let mut w = state.watcher.lock().map_err(|e| e.to_string())?;
-    *w = None;
-    let mut wp = state.watched_path.lock().map_err(|e| e.to_string())?;
-    *wp = None;
+    let mut subscriptions = state.subscriptions.lock().map_err(|e| e.to_string())?;
+    // Windows may only end their own subscriptions
+    if subscriptions
+        .get(&subscription)
+        .is_some_and(|s| s.label == webview.label())
+    {
+        subscriptions.remove(&subscription);
+    }
     Ok(())
 }
+
+/// End all subscriptions of a webview, e.g. when its page is reloaded.
+pub fn unwatch_webview(app: &AppHandle, label: &str) {
+    let state = app.state::<WatcherState>();
+    let mut subscriptions = match state.subscriptions.lock() {
+        Ok(subscriptions) => subscriptions,
+        Err(_) => return,
+    };
+    subscriptions.retain(|_, s| s.label != label);
+}
@@ -0,0 +1,534 @@
+// File writes.
+//
+// Content is written to a temporary file next to the target, synced, and then
+// renamed over it, so a crash leaves either the old or the new file and never
+// a truncated one. The target's permissions (and owner, on Unix) carry over to
+// the new file, and writing through a symlink replaces the file it points to.
+//
+// Editor-style saves pass the mtime or hash they last saw. If the file changed
+// on disk since, nothing is written and `WriteResult::Conflict` is returned so
+// the caller can show the newer version instead of silently overwriting it.
+//
+// Large or binary content can be uploaded in pieces: `fs_write_begin` opens a
+// session backed by a temp file, `fs_write_chunk` appends raw IPC bytes to it,
+// and `fs_write_commit` renames it into place (`fs_write_abort` discards it).
+// Until the commit the target is untouched.
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use crate::fs_journal;
+use crate::fs_scope;
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum WriteResult {
+    /// The new version, to pass as the expectation on the next save.
+    Written { mtime: u64, sha256: String },
+    /// The file changed since the caller last saw it. Both fields are `None`
+    /// if it no longer exists.
+    Conflict {
+        current_mtime: Option<u64>,
+        current_hash: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileVersion {
+    /// Seconds, like `FileEntry::modified`.
+    pub mtime: u64,
+    pub sha256: String,
+}
+
+/// Upload sessions untouched for this long are dropped with their temp file.
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Header carrying the session id of a raw `fs_write_chunk` request.
+const SESSION_HEADER: &str = "x-write-session";
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn mtime_secs(meta: &fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn version(path: &Path) -> Result<FileVersion, String> {
+    let meta =
+        fs::metadata(path).map_err(|e| format!("Failed to stat {}: {}", path.display(), e))?;
+    let data = fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    Ok(FileVersion {
+        mtime: mtime_secs(&meta),
+        sha256: sha256_hex(&data),
+    })
+}
+
+/// A unique temporary path next to `target`.
+pub(crate) fn temp_path(target: &Path) -> Result<PathBuf, String> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let parent = target.parent().ok_or("Invalid file path")?;
+    let name = target.file_name().ok_or("Invalid file path")?;
+    Ok(parent.join(format!(
+        ".{}.{}-{}.tmp",
+        name.to_string_lossy(),
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )))
+}
+
+/// The file a write to `path` should replace: the target of a symlink rather
+/// than the link itself.
+fn write_target(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Give `tmp` the permissions and owner of the file it is about to replace.
+fn copy_attributes(tmp: &Path, existing: &fs::Metadata) -> std::io::Result<()> {
+    fs::set_permissions(tmp, existing.permissions())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        // Only possible when running as root or when changing to one of our own
+        // groups; otherwise the new file is owned by us like any other write.
+        let _ = std::os::unix::fs::chown(tmp, Some(existing.uid()), Some(existing.gid()));
+    }
+    Ok(())
+}
+
+/// Move a fully written temporary file over `target`, keeping the target's
+/// attributes. Removes `tmp` on failure.
+pub(crate) fn replace_with(tmp: &Path, target: &Path) -> Result<(), String> {
+    let result = (|| {
+        if let Ok(existing) = fs::metadata(target) {
+            copy_attributes(tmp, &existing)?;
+        }
+        fs::rename(tmp, target)
+    })();
+    if let Err(e) = result {
+        let _ = fs::remove_file(tmp);
+        return Err(format!("Failed to write file: {}", e));
+    }
+    // Persist the rename itself (best effort; not supported on Windows)
+    #[cfg(unix)]
+    if let Some(parent) = target.parent() {
+        if let Ok(dir) = fs::File::open(parent) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
+}
+
+/// Replace `path` with `data` atomically. Refuses read-only files, which a
+/// rename would otherwise happily replace.
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    let target = write_target(path);
+    if fs::metadata(&target).is_ok_and(|m| m.permissions().readonly()) {
+        return Err(format!("File is read-only: {}", path.display()));
+    }
+    let tmp = temp_path(&target)?;
+    let written = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&tmp)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(format!("Failed to write file: {}", e));
+    }
+    replace_with(&tmp, &target)
+}
+
+/// `Some(Conflict)` if `path` no longer matches what the caller expects. The
+/// hash is authoritative when given, so a touched but unchanged file is not a
+/// conflict; otherwise the mtime is compared.
+pub(crate) fn check_expected(
+    path: &Path,
+    expected_mtime: Option<u64>,
+    expected_sha256: Option<&str>,
+) -> Result<Option<WriteResult>, String> {
+    if expected_mtime.is_none() && expected_sha256.is_none() {
+        return Ok(None);
+    }
+    let current = match version(path) {
+        Ok(current) => current,
+        Err(_) if matches!(fs::metadata(path), Err(e) if e.kind() == ErrorKind::NotFound) => {
+            return Ok(Some(WriteResult::Conflict {
+                current_mtime: None,
+                current_hash: None,
+            }));
+        }
+        Err(e) => return Err(e),
+    };
+    let unchanged = match expected_sha256 {
+        Some(hash) => hash.eq_ignore_ascii_case(&current.sha256),
+        None => expected_mtime == Some(current.mtime),
+    };
+    if unchanged {
+        return Ok(None);
+    }
+    Ok(Some(WriteResult::Conflict {
+        current_mtime: Some(current.mtime),
+        current_hash: Some(current.sha256),
+    }))
+}
+
+/// Copy the current file to `<name>.bak` before it is replaced.
+pub(crate) fn write_backup(path: &Path) -> Result<(), String> {
+    if !path.is_file() {
+        return Ok(());
+    }
+    let name = path.file_name().ok_or("Invalid file path")?;
+    let backup = path.with_file_name(format!("{}.bak", name.to_string_lossy()));
+    fs_scope::check(&backup)?;
+    fs::copy(path, &backup)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to write backup: {}", e))
+}
+
+fn written(path: &Path, sha256: String) -> Result<WriteResult, String> {
+    let meta =
+        fs::metadata(path).map_err(|e| format!("Failed to stat {}: {}", path.display(), e))?;
+    Ok(WriteResult::Written {
+        mtime: mtime_secs(&meta),
+        sha256,
+    })
+}
+
+/// Decode base64 content, also accepting the data URLs `fs_read_file_base64`
+/// returns.
+fn decode_base64(data: &str) -> Result<Vec<u8>, String> {
+    let encoded = match data.strip_prefix("data:") {
+        Some(url) => url.split_once("base64,").map(|(_, d)| d).unwrap_or(url),
+        None => data,
+    };
+    BASE64
+        .decode(encoded.trim())
+        .map_err(|e| format!("Invalid base64 data: {}", e))
+}
+
+fn write_checked(
+    path: &str,
+    data: &[u8],
+    expected_mtime: Option<u64>,
+    expected_sha256: Option<String>,
+    backup: Option<bool>,
+) -> Result<WriteResult, String> {
+    fs_scope::check(path)?;
+    let p = Path::new(path);
+    if let Some(conflict) = check_expected(p, expected_mtime, expected_sha256.as_deref())? {
+        return Ok(conflict);
+    }
+    let before = fs_journal::capture(p);
+    if backup.unwrap_or(false) {
+        write_backup(p)?;
+    }
+    write_atomic(p, data)?;
+    fs_journal::record_replace("write", p, before, fs_journal::capture_data(data));
+    written(p, sha256_hex(data))
+}
+
+fn append(path: &str, data: &[u8]) -> Result<(), String> {
+    fs_scope::check(path)?;
+    let p = Path::new(path);
+    let start = fs_journal::append_start(p);
+    fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(p)
+        .and_then(|mut file| file.write_all(data))
+        .map_err(|e| format!("Failed to append to file: {}", e))?;
+    fs_journal::record_append(p, start, data);
+    Ok(())
+}
+
+/// Write text to a file atomically. With `expected_mtime` / `expected_sha256`
+/// (from `fs_file_version` or the previous save) nothing is written if the
+/// file changed since; `backup` keeps the previous content as `<name>.bak`.
+#[tauri::command]
+pub fn fs_write_file(
+    path: String,
+    content: String,
+    expected_mtime: Option<u64>,
+    expected_sha256: Option<String>,
+    backup: Option<bool>,
+) -> Result<WriteResult, String> {
+    write_checked(
+        &path,
+        content.as_bytes(),
+        expected_mtime,
+        expected_sha256,
+        backup,
+    )
+}
+
+/// Like `fs_write_file`, for binary content sent as base64 or a data URL.
+#[tauri::command]
+pub fn fs_write_file_base64(
+    path: String,
+    data: String,
+    expected_mtime: Option<u64>,
+    expected_sha256: Option<String>,
+    backup: Option<bool>,
+) -> Result<WriteResult, String> {
+    let data = decode_base64(&data)?;
+    write_checked(&path, &data, expected_mtime, expected_sha256, backup)
+}
+
+/// Append text to a file, creating it if needed. Appends in place rather than
+/// through a temp file, so readers tailing the file see the new data.
+#[tauri::command]
+pub fn fs_append_file(path: String, content: String) -> Result<(), String> {
+    append(&path, content.as_bytes())
+}
+
+/// Like `fs_append_file`, for binary content sent as base64 or a data URL.
+#[tauri::command]
+pub fn fs_append_file_base64(path: String, data: String) -> Result<(), String> {
+    append(&path, &decode_base64(&data)?)
+}
+
+/// The mtime and SHA-256 of a file, to pass as expectations to
+/// `fs_write_file`.
+#[tauri::command]
+pub fn fs_file_version(path: String) -> Result<FileVersion, String> {
+    fs_scope::check(&path)?;
+    version(Path::new(&path))
+}
+
+struct Session {
+    /// As given to `fs_write_begin`, for the scope check and the journal.
+    path: PathBuf,
+    /// The file the commit replaces.
+    target: PathBuf,
+    tmp: PathBuf,
+    file: fs::File,
+    hasher: Sha256,
+    size: u64,
+    expected_mtime: Option<u64>,
+    expected_sha256: Option<String>,
+    backup: bool,
+    last_used: Instant,
+}
+
+impl Session {
+    fn discard(self) {
+        drop(self.file);
+        let _ = fs::remove_file(&self.tmp);
+    }
+}
+
+fn sessions() -> &'static Mutex<HashMap<u64, Session>> {
+    static SESSIONS: OnceLock<Mutex<HashMap<u64, Session>>> = OnceLock::new();
+    SESSIONS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn take_session(id: u64) -> Result<Session, String> {
+    sessions()
+        .lock()
+        .unwrap()
+        .remove(&id)
+        .ok_or_else(|| format!("Unknown or expired write session {}", id))
+}
+
+/// Copy the current content of `target` into the session's temp file, so the
+/// chunks are appended to it.
+fn copy_existing(target: &Path, file: &mut fs::File, hasher: &mut Sha256) -> std::io::Result<u64> {
+    let mut src = match fs::File::open(target) {
+        Ok(src) => src,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let n = src.read(&mut buf)?;
+        if n == 0 {
+            return Ok(size);
+        }
+        file.write_all(&buf[..n])?;
+        hasher.update(&buf[..n]);
+        size += n as u64;
+    }
+}
+
+/// Start a chunked write to `path` and return the session id. With `append`
+/// the chunks are added to the current content; the commit then fails with a
+/// conflict if the file changes in the meantime, even without expectations.
+#[tauri::command]
+pub fn fs_write_begin(
+    path: String,
+    append: Option<bool>,
+    expected_mtime: Option<u64>,
+    expected_sha256: Option<String>,
+    backup: Option<bool>,
+) -> Result<u64, String> {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+    fs_scope::check(&path)?;
+    let p = Path::new(&path);
+    let target = write_target(p);
+    if fs::metadata(&target).is_ok_and(|m| m.permissions().readonly()) {
+        return Err(format!("File is read-only: {}", path));
+    }
+
+    {
+        let mut sessions = sessions().lock().unwrap();
+        let expired: Vec<u64> = sessions
+            .iter()
+            .filter(|(_, s)| s.last_used.elapsed() > SESSION_IDLE_TIMEOUT)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in expired {
+            if let Some(session) = sessions.remove(&id) {
+                log::warn!("Dropping idle write session for {}", session.path.display());
+                session.discard();
+            }
+        }
+    }
+
+    let tmp = temp_path(&target)?;
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&tmp)
+        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    let mut hasher = Sha256::new();
+    let mut expected_sha256 = expected_sha256;
+    let mut size = 0;
+    if append.unwrap_or(false) {
+        size = match copy_existing(&target, &mut file, &mut hasher) {
+            Ok(size) => size,
+            Err(e) => {
+                drop(file);
+                let _ = fs::remove_file(&tmp);
+                return Err(format!("Failed to read file: {}", e));
+            }
+        };
+        if expected_sha256.is_none() && size > 0 {
+            expected_sha256 = Some(hex(&hasher.clone().finalize()));
+        }
+    }
+
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    sessions().lock().unwrap().insert(
+        id,
+        Session {
+            path: p.to_path_buf(),
+            target,
+            tmp,
+            file,
+            hasher,
+            size,
+            expected_mtime,
+            expected_sha256,
+            backup: backup.unwrap_or(false),
+            last_used: Instant::now(),
+        },
+    );
+    Ok(id)
+}
+
+/// Append raw bytes to a write session. The session id is passed in the
+/// `x-write-session` header; chunks must be sent one after another. Returns
+/// the number of bytes written so far.
+#[tauri::command]
+pub fn fs_write_chunk(request: tauri::ipc::Request<'_>) -> Result<u64, String> {
+    let id: u64 = request
+        .headers()
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| format!("Missing {} header", SESSION_HEADER))?;
+    let tauri::ipc::InvokeBody::Raw(data) = request.body() else {
+        return Err("Expected raw bytes".to_string());
+    };
+
+    let mut sessions = sessions().lock().unwrap();
+    let session = sessions
+        .get_mut(&id)
+        .ok_or_else(|| format!("Unknown or expired write session {}", id))?;
+    if let Err(e) = session.file.write_all(data) {
+        let session = sessions.remove(&id).unwrap();
+        session.discard();
+        return Err(format!("Failed to write file: {}", e));
+    }
+    session.hasher.update(data);
+    session.size += data.len() as u64;
+    session.last_used = Instant::now();
+    Ok(session.size)
+}
+
+/// Move the session's temp file into place. Returns a conflict, and discards
+/// the upload, if the target no longer matches the session's expectations.
+#[tauri::command]
+pub fn fs_write_commit(session: u64) -> Result<WriteResult, String> {
+    let session = take_session(session)?;
+    if let Err(e) = session.file.sync_all() {
+        session.discard();
+        return Err(format!("Failed to write file: {}", e));
+    }
+    let conflict = check_expected(
+        &session.path,
+        session.expected_mtime,
+        session.expected_sha256.as_deref(),
+    );
+    match conflict {
+        Ok(None) => {}
+        Ok(Some(conflict)) => {
+            session.discard();
+            return Ok(conflict);
+        }
+        Err(e) => {
+            session.discard();
+            return Err(e);
+        }
+    }
+    let before = fs_journal::capture(&session.path);
+    if session.backup {
+        if let Err(e) = write_backup(&session.path) {
+            session.discard();
+            return Err(e);
+        }
+    }
+    let Session {
+        path,
+        target,
+        tmp,
+        file,
+        hasher,
+        ..
+    } = session;
+    drop(file);
+    replace_with(&tmp, &target)?;
+    fs_journal::record_replace("write", &path, before, fs_journal::capture(&path));
+    written(&target, hex(&hasher.finalize()))
+}
+
+/// Discard a write session. Unknown ids are ignored, so aborting an expired
+/// session is not an error.
+#[tauri::command]
+pub fn fs_write_abort(session: u64) {
+    if let Ok(session) = take_session(session) {
+        session.discard();
+    }
+}
@@ -0,0 +1,158 @@
+// Per-window access to the app's IPC commands.
+//
+// Every window used to be able to call every command, so the quick ask
+// overlay could delete files or overwrite OAuth tokens. Commands are now
+// grouped into permission sets and each window label is granted only the sets
+// it needs. The check runs in the invoke handler, before the command itself,
+// using the label of the webview that sent the request.
+//
+// Plugin and core permissions (window, dialog, updater, ...) are split the
+// same way in capabilities/*.json.
+use tauri::ipc::Invoke;
+use tauri::Runtime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermissionSet {
+    /// Harmless queries and the shared backend connection.
+    Common,
+    /// Reading files and directories, thumbnails, watching.
+    FsRead,
+    /// Creating, changing and deleting files; changing the fs scope.
+    FsWrite,
+    /// Installing, starting and stopping the PocketPaw backend.
+    Backend,
+    /// Reading the current credentials (needed to talk to the backend).
+    AuthSession,
+    /// Signing in and out, storing and switching accounts.
+    AuthManage,
+    /// Showing, hiding and arranging the app's windows.
+    WindowControl,
+    /// Persistent app settings (notifications, tray, appearance).
+    Settings,
+}
+
+use PermissionSet::*;
+
+/// The commands in each permission set. Commands not listed here are refused
+/// in every window.
+#[rustfmt::skip]
+const COMMANDS: &[(PermissionSet, &[&str])] = &[
+    (
+        Common,
+        &[
+            "get_active_context", "get_native_effect", "get_notification_settings",
+            "get_tray_state", "get_attach_mode", "get_attach_info", "is_side_panel_collapsed",
+            "get_pending_quickask", "ws_bridge_connect", "ws_bridge_disconnect",
+            "ws_bridge_state", "ws_send",
+        ],
+    ),
+    (
+        FsRead,
+        &[
+            "fs_read_dir", "fs_read_file_text", "fs_stat", "fs_exists", "fs_read_file_base64",
+            "fs_read_file_head", "fs_resolve_path", "fs_parent_dir", "fs_get_default_dirs",
+            "fs_stat_extended", "fs_search_recursive", "fs_thumbnail", "fs_watch", "fs_unwatch",
+            "fs_get_scope", "fs_list_trash", "fs_history", "fs_file_version",
+            "fs_read_file_decoded", "fs_read_range", "fs_read_lines", "fs_grep", "fs_grep_cancel",
+            "fs_index_query", "fs_index_record_open", "fs_index_roots", "fs_archive_list",
+        ],
+    ),
+    (
+        FsWrite,
+        &[
+            "fs_write_file", "fs_delete", "fs_rename", "fs_move", "fs_create_dir", "fs_copy_file",
+            "fs_copy_dir", "fs_open_in_terminal", "fs_set_scope", "fs_delete_permanently",
+            "fs_restore_from_trash", "fs_undo", "fs_redo", "fs_write_file_base64",
+            "fs_append_file", "fs_append_file_base64", "fs_write_begin", "fs_write_chunk",
+            "fs_write_commit", "fs_write_abort", "fs_index_add_root", "fs_index_remove_root",
+            "fs_transfer", "fs_transfer_resolve", "fs_transfer_cancel", "fs_archive_extract",
+            "fs_archive_create", "fs_archive_cancel",
+        ],
+    ),
+    (
+        Backend,
+        &[
+            "get_pocketpaw_config_dir", "check_backend_running", "check_pocketpaw_version",
+            "check_pocketpaw_installed", "install_pocketpaw", "start_pocketpaw_backend",
+            "stop_pocketpaw_backend", "restart_pocketpaw_backend",
+        ],
+    ),
+    (
+        AuthSession,
+        &["read_access_token", "read_oauth_tokens", "refresh_oauth_tokens", "list_oauth_accounts"],
+    ),
+    (
+        AuthManage,
+        &[
+            "save_oauth_tokens", "clear_oauth_tokens", "switch_oauth_account",
+            "remove_oauth_account", "start_device_login", "cancel_device_login",
+            "start_oauth_server", "cancel_oauth_server", "proxy_post", "proxy_get",
+        ],
+    ),
+    (
+        WindowControl,
+        &[
+            "toggle_side_panel", "show_side_panel", "hide_side_panel", "collapse_side_panel",
+            "expand_side_panel", "dock_side_panel", "toggle_quick_ask", "show_quick_ask",
+            "hide_quick_ask", "quickask_to_sidepanel", "set_attach_mode", "detach_side_panel",
+        ],
+    ),
+    (Settings, &["set_notification_settings", "set_tray_state", "set_vibrancy_theme"]),
+];
+
+fn command_set(command: &str) -> Option<PermissionSet> {
+    COMMANDS
+        .iter()
+        .find(|(_, commands)| commands.contains(&command))
+        .map(|(set, _)| *set)
+}
+
+/// The permission sets granted to a window label.
+fn window_sets(label: &str) -> &'static [PermissionSet] {
+    match label {
+        "main" => &[
+            Common,
+            FsRead,
+            FsWrite,
+            Backend,
+            AuthSession,
+            AuthManage,
+            WindowControl,
+            Settings,
+        ],
+        // Chat with file attachments; no file changes or sign-in
+        "sidepanel" => &[Common, FsRead, AuthSession, WindowControl],
+        "quickask" => &[Common, AuthSession, WindowControl],
+        _ => &[],
+    }
+}
+
+fn is_allowed(label: &str, command: &str) -> bool {
+    command_set(command).is_some_and(|set| window_sets(label).contains(&set))
+}
+
+/// Wrap the generated command handler so every call is checked against the
+/// calling window's permission sets. Refused calls are logged and rejected
+/// with an error instead of reaching the command.
+pub fn guard<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let label = invoke.message.webview_ref().label().to_string();
+        let command = invoke.message.command().to_string();
+        if !is_allowed(&label, &command) {
+            log::warn!(
+                "Blocked IPC call: window '{}' may not call '{}' ({:?})",
+                label,
+                command,
+                command_set(&command)
+            );
+            invoke.resolver.reject(format!(
+                "Command {} is not allowed in window {}",
+                command, label
+            ));
+            return true;
+        }
+        handler(invoke)
+    }
+}
@@ -1,10 +1,29 @@
+mod backend_transport;
 mod commands;
 mod context;
+mod fs_archive;
 mod fs_commands;
+mod fs_grep;
+mod fs_index;
+mod fs_journal;
+mod fs_read;
+mod fs_scope;
 mod fs_thumbnail;
+mod fs_transfer;
+mod fs_trash;
+mod fs_walk;
 mod fs_watcher;
+mod fs_write;
+mod ipc_permissions;
 mod oauth;
+mod oauth_accounts;
+mod oauth_device;
+mod oauth_refresh;
+mod token_store;
+mod ws_bridge;
 
+#[cfg(desktop)]
+mod notifications;
 #[cfg(desktop)]
 mod quick_ask;
 #[cfg(desktop)]
@@ -63,13 +82,21 @@ pub fn run() {
             ));
     }
 
-    builder = builder.manage(fs_watcher::WatcherState::default());
+    builder = builder
+        .manage(fs_watcher::WatcherState::default())
+        .manage(ws_bridge::WsBridgeState::default())
+        .manage(commands::BackendProcess::default())
+        .manage(oauth_refresh::RefreshState::default())
+        .manage(oauth_device::DeviceLoginState::default());
 
     #[cfg(desktop)]
     {
         builder = builder
+            .manage(notifications::NotificationState::default())
+            .manage(oauth::OAuthServerState::default())
             .manage(quick_ask::PendingQuickAsk(std::sync::Mutex::new(None)))
             .manage(side_panel::SidePanelState::default())
+            .manage(tray::TrayState::default())
             .manage(window_attach::WindowAttachState::new())
             .manage(vibrancy::ActiveEffect(std::sync::Mutex::new(
                 vibrancy::NativeEffect::None,
@@ -77,7 +104,14 @@ pub fn run() {
     }
 
     builder
-        .invoke_handler(tauri::generate_handler![
+        // A reloaded page starts without watchers; drop the old page's ones
+        .on_page_load(|webview, payload| {
+            if payload.event() == tauri::webview::PageLoadEvent::Started {
+                fs_watcher::unwatch_webview(webview.app_handle(), webview.label());
+            }
+        })
+        // Each window may only call the commands in its permission sets
+        .invoke_handler(ipc_permissions::guard(tauri::generate_handler![
             commands::read_access_token,
             commands::get_pocketpaw_config_dir,
             commands::check_backend_running,
@@ -85,17 +119,50 @@ pub fn run() {
             commands::check_pocketpaw_installed,
             commands::install_pocketpaw,
             commands::start_pocketpaw_backend,
+            commands::stop_pocketpaw_backend,
+            commands::restart_pocketpaw_backend,
             context::get_active_context,
             oauth::read_oauth_tokens,
             oauth::save_oauth_tokens,
             oauth::clear_oauth_tokens,
+            oauth_accounts::list_oauth_accounts,
+            oauth_accounts::switch_oauth_account,
+            oauth_accounts::remove_oauth_account,
+            oauth_refresh::refresh_oauth_tokens,
+            oauth_device::start_device_login,
+            oauth_device::cancel_device_login,
             oauth::proxy_post,
             oauth::proxy_get,
             fs_commands::fs_read_dir,
             fs_commands::fs_read_file_text,
-            fs_commands::fs_write_file,
-            fs_commands::fs_delete,
+            fs_write::fs_write_file,
+            fs_write::fs_file_version,
+            fs_read::fs_read_file_decoded,
+            fs_read::fs_read_range,
+            fs_read::fs_read_lines,
+            fs_grep::fs_grep,
+            fs_grep::fs_grep_cancel,
+            fs_index::fs_index_query,
+            fs_index::fs_index_record_open,
+            fs_index::fs_index_roots,
+            fs_index::fs_index_add_root,
+            fs_index::fs_index_remove_root,
+            fs_write::fs_write_file_base64,
+            fs_write::fs_append_file,
+            fs_write::fs_append_file_base64,
+            fs_write::fs_write_begin,
+            fs_write::fs_write_chunk,
+            fs_write::fs_write_commit,
+            fs_write::fs_write_abort,
+            fs_trash::fs_delete,
+            fs_trash::fs_delete_permanently,
+            fs_trash::fs_list_trash,
+            fs_trash::fs_restore_from_trash,
+            fs_journal::fs_undo,
+            fs_journal::fs_redo,
+            fs_journal::fs_history,
             fs_commands::fs_rename,
+            fs_commands::fs_move,
             fs_commands::fs_stat,
             fs_commands::fs_create_dir,
             fs_commands::fs_exists,
@@ -105,16 +172,35 @@ pub fn run() {
             fs_commands::fs_get_default_dirs,
             fs_commands::fs_copy_file,
             fs_commands::fs_copy_dir,
+            fs_transfer::fs_transfer,
+            fs_transfer::fs_transfer_resolve,
+            fs_transfer::fs_transfer_cancel,
+            fs_archive::fs_archive_list,
+            fs_archive::fs_archive_extract,
+            fs_archive::fs_archive_create,
+            fs_archive::fs_archive_cancel,
             fs_commands::fs_stat_extended,
             fs_commands::fs_open_in_terminal,
             fs_commands::fs_search_recursive,
             fs_commands::fs_read_file_head,
+            fs_scope::fs_get_scope,
+            fs_scope::fs_set_scope,
             fs_thumbnail::fs_thumbnail,
             fs_watcher::fs_watch,
             fs_watcher::fs_unwatch,
+            ws_bridge::ws_bridge_connect,
+            ws_bridge::ws_bridge_disconnect,
+            ws_bridge::ws_bridge_state,
+            ws_bridge::ws_send,
             #[cfg(desktop)]
             oauth::start_oauth_server,
             #[cfg(desktop)]
+            oauth::cancel_oauth_server,
+            #[cfg(desktop)]
+            notifications::get_notification_settings,
+            #[cfg(desktop)]
+            notifications::set_notification_settings,
+            #[cfg(desktop)]
             side_panel::toggle_side_panel,
             #[cfg(desktop)]
             side_panel::show_side_panel,
@@ -139,6 +225,10 @@ pub fn run() {
             #[cfg(desktop)]
             quick_ask::get_pending_quickask,
             #[cfg(desktop)]
+            tray::set_tray_state,
+            #[cfg(desktop)]
+            tray::get_tray_state,
+            #[cfg(desktop)]
             vibrancy::get_native_effect,
             #[cfg(desktop)]
             vibrancy::set_vibrancy_theme,
@@ -150,13 +240,25 @@ pub fn run() {
             window_attach::get_attach_info,
             #[cfg(desktop)]
             window_attach::detach_side_panel,
-        ])
+        ]))
         .setup(|_app| {
+            // One shared backend WebSocket for all windows
+            ws_bridge::start(_app.handle());
+            // Keep OAuth tokens fresh even while the webviews are suspended
+            oauth_refresh::start(_app.handle());
+            // Load and refresh the file index for fuzzy file search
+            fs_index::start();
+            // Undo snapshots don't outlive the journal that pointed at them
+            fs_journal::clear_stale_snapshots();
+
             // Desktop-only: system tray + close-to-tray
             #[cfg(desktop)]
             {
                 tray::setup_tray(_app.handle())?;
 
+                // Native notifications for backend events, even while hidden
+                ws_bridge::subscribe(_app.handle(), notifications::handle_backend_event);
+
                 let window = _app.get_webview_window("main").unwrap();
 
                 // Open devtools in debug builds
@@ -182,11 +284,16 @@ pub fn run() {
                 window_attach::start_poll_loop(_app.handle().clone());
 
                 let window_clone = window.clone();
-                window.on_window_event(move |event| {
-                    if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                let app_handle = _app.handle().clone();
+                window.on_window_event(move |event| match event {
+                    tauri::WindowEvent::CloseRequested { api, .. } => {
                         api.prevent_close();
                         let _ = window_clone.hide();
                     }
+                    tauri::WindowEvent::Focused(true) => {
+                        notifications::on_main_focused(&app_handle);
+                    }
+                    _ => {}
                 });
             }
 
@@ -0,0 +1,459 @@
+// Native desktop notifications driven by backend WebSocket events.
+//
+// The WebSocket bridge hands every backend event to `handle_backend_event`,
+// which maps reminders, task completions, plan approvals and errors to native
+// notifications. Delivery honours per-category toggles and a do-not-disturb
+// schedule, and bursts within a short window are grouped into one summary.
+// On Linux, clicking a notification focuses `main` and emits `tray-navigate`
+// with the relevant route. The notification plugin used elsewhere reports no
+// clicks, so there a click only brings the app forward.
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+use crate::tray;
+
+const SETTINGS_FILE: &str = "client_notifications.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationCategory {
+    Reminder,
+    TaskComplete,
+    PlanApproval,
+    Error,
+}
+
+impl NotificationCategory {
+    /// Plural noun used in grouped summaries ("3 more reminders").
+    fn plural(&self) -> &'static str {
+        match self {
+            NotificationCategory::Reminder => "reminders",
+            NotificationCategory::TaskComplete => "completed tasks",
+            NotificationCategory::PlanApproval => "plans awaiting approval",
+            NotificationCategory::Error => "errors",
+        }
+    }
+}
+
+/// Quiet hours in local time, e.g. `22:00`–`07:00`. The window may wrap
+/// past midnight.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoNotDisturb {
+    pub enabled: bool,
+    pub start: String,
+    pub end: String,
+}
+
+impl Default for DoNotDisturb {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: "22:00".to_string(),
+            end: "07:00".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    pub enabled: bool,
+    pub reminder: bool,
+    pub task_complete: bool,
+    pub plan_approval: bool,
+    pub error: bool,
+    pub do_not_disturb: DoNotDisturb,
+    /// Notifications of the same category arriving within this many seconds
+    /// of the last one shown are grouped into a single summary.
+    pub group_window_secs: u64,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            reminder: true,
+            task_complete: true,
+            plan_approval: true,
+            error: true,
+            do_not_disturb: DoNotDisturb::default(),
+            group_window_secs: 10,
+        }
+    }
+}
+
+impl NotificationSettings {
+    fn category_enabled(&self, category: NotificationCategory) -> bool {
+        self.enabled
+            && match category {
+                NotificationCategory::Reminder => self.reminder,
+                NotificationCategory::TaskComplete => self.task_complete,
+                NotificationCategory::PlanApproval => self.plan_approval,
+                NotificationCategory::Error => self.error,
+            }
+    }
+}
+
+/// A native notification derived from a backend event.
+#[derive(Debug, Clone)]
+struct PendingNotification {
+    category: NotificationCategory,
+    title: String,
+    body: String,
+    route: String,
+}
+
+/// Tracks a burst of same-category notifications.
+struct BurstGroup {
+    /// Bumped each time the group is replaced, so a delayed flush scheduled
+    /// for an earlier burst can tell it is stale.
+    generation: u64,
+    started: Instant,
+    suppressed: u32,
+    route: String,
+    flush_scheduled: bool,
+}
+
+/// Managed state for backend-driven notifications.
+pub struct NotificationState {
+    settings: Mutex<NotificationSettings>,
+    groups: Mutex<HashMap<NotificationCategory, BurstGroup>>,
+}
+
+impl Default for NotificationState {
+    fn default() -> Self {
+        Self {
+            settings: Mutex::new(load_settings().unwrap_or_default()),
+            groups: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+fn settings_file_path() -> Result<std::path::PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Could not determine home directory")?;
+    Ok(home.join(".pocketpaw").join(SETTINGS_FILE))
+}
+
+fn load_settings() -> Result<NotificationSettings, String> {
+    let path = settings_file_path()?;
+    let data = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read notification settings: {}", e))?;
+    serde_json::from_str(&data).map_err(|e| format!("Failed to parse notification settings: {}", e))
+}
+
+// ---------------------------------------------------------------------------
+// Tauri commands
+// ---------------------------------------------------------------------------
+
+#[tauri::command]
+pub fn get_notification_settings(app: AppHandle) -> NotificationSettings {
+    let state = app.state::<NotificationState>();
+    let settings = state.settings.lock().unwrap().clone();
+    settings
+}
+
+#[tauri::command]
+pub fn set_notification_settings(
+    app: AppHandle,
+    settings: NotificationSettings,
+) -> Result<(), String> {
+    for time in [&settings.do_not_disturb.start, &settings.do_not_disturb.end] {
+        parse_hh_mm(time).ok_or_else(|| format!("Invalid time (expected HH:MM): {}", time))?;
+    }
+
+    let path = settings_file_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
+    }
+    let data = serde_json::to_string_pretty(&settings)
+        .map_err(|e| format!("Failed to serialize: {}", e))?;
+    fs::write(&path, data).map_err(|e| format!("Failed to write notification settings: {}", e))?;
+
+    let state = app.state::<NotificationState>();
+    *state.settings.lock().unwrap() = settings;
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Event handling
+// ---------------------------------------------------------------------------
+
+/// Entry point registered with the WebSocket bridge.
+pub fn handle_backend_event(app: &AppHandle, event: &serde_json::Value) {
+    if let Some(notification) = map_event(event) {
+        notify(app, notification);
+    }
+}
+
+/// Map a backend event to a notification, if it warrants one.
+fn map_event(event: &serde_json::Value) -> Option<PendingNotification> {
+    let str_field = |v: &serde_json::Value, key: &str| -> String {
+        v.get(key)
+            .and_then(|f| f.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    match event.get("type")?.as_str()? {
+        "reminder" => {
+            let reminder = event.get("reminder")?;
+            Some(PendingNotification {
+                category: NotificationCategory::Reminder,
+                title: "Reminder".to_string(),
+                body: str_field(reminder, "text"),
+                route: "/chat".to_string(),
+            })
+        }
+        "mc_task_completed" => match event.get("status")?.as_str()? {
+            "completed" => Some(PendingNotification {
+                category: NotificationCategory::TaskComplete,
+                title: "Task completed".to_string(),
+                body: str_field(event, "task_id"),
+                route: "/command-center".to_string(),
+            }),
+            "error" | "timeout" => Some(PendingNotification {
+                category: NotificationCategory::Error,
+                title: "Task failed".to_string(),
+                body: str_field(event, "error"),
+                route: "/command-center".to_string(),
+            }),
+            _ => None,
+        },
+        "dw_planning_complete" => {
+            let error = str_field(event, "error");
+            if !error.is_empty() {
+                return Some(PendingNotification {
+                    category: NotificationCategory::Error,
+                    title: "Planning failed".to_string(),
+                    body: error,
+                    route: "/projects".to_string(),
+                });
+            }
+            if event.get("status")?.as_str()? != "awaiting_approval" {
+                return None;
+            }
+            Some(PendingNotification {
+                category: NotificationCategory::PlanApproval,
+                title: "Plan ready for approval".to_string(),
+                body: str_field(event, "title"),
+                route: "/projects".to_string(),
+            })
+        }
+        "error" => Some(PendingNotification {
+            category: NotificationCategory::Error,
+            title: "PocketPaw error".to_string(),
+            body: str_field(event, "content"),
+            route: "/health".to_string(),
+        }),
+        _ => None,
+    }
+}
+
+/// Apply settings, do-not-disturb and burst grouping, then show.
+fn notify(app: &AppHandle, notification: PendingNotification) {
+    let state = app.state::<NotificationState>();
+    let settings = state.settings.lock().unwrap().clone();
+    if !settings.category_enabled(notification.category)
+        || in_do_not_disturb(&settings.do_not_disturb)
+    {
+        return;
+    }
+
+    let window = Duration::from_secs(settings.group_window_secs);
+    let mut groups = state.groups.lock().unwrap();
+    let mut generation = 0;
+    let mut expired = None;
+    if let Some(group) = groups.get_mut(&notification.category) {
+        generation = group.generation + 1;
+        if group.started.elapsed() >= window {
+            // The burst's flush may not have run yet; report its count here
+            // rather than lose it when the group is replaced below.
+            if group.suppressed > 0 {
+                expired = Some((group.suppressed, group.route.clone()));
+            }
+        } else {
+            group.suppressed += 1;
+            group.route = notification.route;
+            if !group.flush_scheduled {
+                group.flush_scheduled = true;
+                let remaining = window.saturating_sub(group.started.elapsed());
+                let app_handle = app.clone();
+                let category = notification.category;
+                let generation = group.generation;
+                std::thread::spawn(move || {
+                    std::thread::sleep(remaining);
+                    flush_group(&app_handle, category, generation);
+                });
+            }
+            return;
+        }
+    }
+    groups.insert(
+        notification.category,
+        BurstGroup {
+            generation,
+            started: Instant::now(),
+            suppressed: 0,
+            route: notification.route.clone(),
+            flush_scheduled: false,
+        },
+    );
+    drop(groups);
+
+    if let Some((count, route)) = expired {
+        show_summary(app, notification.category, count, &route);
+    }
+    show(
+        app,
+        &notification.title,
+        &notification.body,
+        &notification.route,
+    );
+}
+
+/// Show one summary for notifications suppressed during a burst. Does
+/// nothing if the burst's group has since been replaced.
+fn flush_group(app: &AppHandle, category: NotificationCategory, generation: u64) {
+    let state = app.state::<NotificationState>();
+    let summary = {
+        let mut groups = state.groups.lock().unwrap();
+        match groups.get_mut(&category) {
+            Some(group) if group.generation != generation => None,
+            Some(group) if group.suppressed > 0 => {
+                let summary = (group.suppressed, group.route.clone());
+                // Start a fresh window so the summary itself opens a new burst
+                group.started = Instant::now();
+                group.suppressed = 0;
+                group.flush_scheduled = false;
+                Some(summary)
+            }
+            Some(group) => {
+                group.flush_scheduled = false;
+                None
+            }
+            None => None,
+        }
+    };
+
+    if let Some((count, route)) = summary {
+        show_summary(app, category, count, &route);
+    }
+}
+
+fn show_summary(app: &AppHandle, category: NotificationCategory, count: u32, route: &str) {
+    let body = format!("{} more {}", count, category.plural());
+    show(app, "PocketPaw", &body, route);
+}
+
+// ---------------------------------------------------------------------------
+// Do-not-disturb
+// ---------------------------------------------------------------------------
+
+fn parse_hh_mm(value: &str) -> Option<u32> {
+    let (h, m) = value.trim().split_once(':')?;
+    let h: u32 = h.parse().ok()?;
+    let m: u32 = m.parse().ok()?;
+    if h > 23 || m > 59 {
+        return None;
+    }
+    Some(h * 60 + m)
+}
+
+fn in_do_not_disturb(dnd: &DoNotDisturb) -> bool {
+    use chrono::Timelike;
+
+    if !dnd.enabled {
+        return false;
+    }
+    let (start, end) = match (parse_hh_mm(&dnd.start), parse_hh_mm(&dnd.end)) {
+        (Some(s), Some(e)) => (s, e),
+        _ => return false,
+    };
+    let now = chrono::Local::now();
+    let minutes = now.hour() * 60 + now.minute();
+
+    if start <= end {
+        minutes >= start && minutes < end
+    } else {
+        // Window wraps past midnight, e.g. 22:00–07:00
+        minutes >= start || minutes < end
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Delivery and click-through
+// ---------------------------------------------------------------------------
+
+/// Count the notification as unread on the tray unless `main` is focused.
+fn mark_unread(app: &AppHandle) {
+    let focused = app
+        .get_webview_window("main")
+        .and_then(|w| w.is_focused().ok())
+        .unwrap_or(false);
+    if !focused {
+        tray::mark_unread(app);
+    }
+}
+
+/// Focus the main window and navigate it to `route`.
+#[cfg(target_os = "linux")]
+fn open_route(app: &AppHandle, route: &str) {
+    use tauri::Emitter;
+
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+        let _ = app.emit("tray-navigate", route);
+    }
+}
+
+/// Linux: talk to the notification daemon directly so the default action
+/// (clicking the notification) can be observed.
+#[cfg(target_os = "linux")]
+fn show(app: &AppHandle, title: &str, body: &str, route: &str) {
+    let handle = notify_rust::Notification::new()
+        .appname("PocketPaw")
+        .summary(title)
+        .body(body)
+        .action("default", "Open")
+        .show();
+
+    match handle {
+        Ok(handle) => {
+            mark_unread(app);
+            let app_handle = app.clone();
+            let route = route.to_string();
+            std::thread::spawn(move || {
+                handle.wait_for_action(|action| {
+                    if action == "default" {
+                        open_route(&app_handle, &route);
+                    }
+                });
+            });
+        }
+        Err(e) => log::warn!("Failed to show notification: {}", e),
+    }
+}
+
+/// macOS/Windows: the notification plugin reports no clicks, so there is no
+/// route to open. Focusing `main` for any other reason must not navigate it.
+#[cfg(not(target_os = "linux"))]
+fn show(app: &AppHandle, title: &str, body: &str, _route: &str) {
+    use tauri_plugin_notification::NotificationExt;
+
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        log::warn!("Failed to show notification: {}", e);
+        return;
+    }
+    mark_unread(app);
+}
+
+/// Called when the main window gains focus: clears the tray's unread count.
+pub fn on_main_focused(app: &AppHandle) {
+    tray::clear_unread(app);
+}
@@ -1,14 +1,22 @@
 use serde::{Deserialize, Serialize};
-use std::fs;
+#[cfg(desktop)]
+use std::collections::HashMap;
 #[cfg(desktop)]
 use std::io::{BufRead, BufReader, Write};
 #[cfg(desktop)]
-use std::net::TcpListener;
+use std::net::{TcpListener, TcpStream};
+#[cfg(desktop)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(desktop)]
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use tauri::AppHandle;
 #[cfg(desktop)]
-use tauri::{AppHandle, Emitter};
+use tauri::{Emitter, Manager};
 
-const TOKEN_FILE: &str = "client_oauth.json";
+use crate::backend_transport;
+use crate::oauth_accounts;
+use crate::ws_bridge;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OAuthTokens {
@@ -18,130 +26,360 @@ pub struct OAuthTokens {
     pub scopes: Vec<String>,
 }
 
-fn token_file_path() -> Result<std::path::PathBuf, String> {
-    let home = dirs::home_dir().ok_or("Could not determine home directory")?;
-    Ok(home.join(".pocketpaw").join(TOKEN_FILE))
-}
-
+/// Read the active account's tokens from the token store, migrating a
+/// plaintext `client_oauth.json` on first use.
 #[tauri::command]
 pub fn read_oauth_tokens() -> Result<OAuthTokens, String> {
-    let path = token_file_path()?;
-    let data = fs::read_to_string(&path).map_err(|e| format!("Failed to read tokens: {}", e))?;
-    serde_json::from_str(&data).map_err(|e| format!("Failed to parse tokens: {}", e))
+    oauth_accounts::active_tokens()?
+        .map(|(_, tokens)| tokens)
+        .ok_or_else(|| "Failed to read tokens: no tokens stored".to_string())
 }
 
+/// Save tokens (e.g. after sign-in) and reschedule the background refresher.
+/// Without `account_id` the active account is updated; a new `account_id`
+/// adds that account and makes it active.
 #[tauri::command]
-pub fn save_oauth_tokens(tokens: OAuthTokens) -> Result<(), String> {
-    let path = token_file_path()?;
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
-    }
-    let data =
-        serde_json::to_string_pretty(&tokens).map_err(|e| format!("Failed to serialize: {}", e))?;
-    fs::write(&path, data).map_err(|e| format!("Failed to write tokens: {}", e))?;
-
-    // Restrict file permissions to owner-only (0600) on Unix so other users
-    // on the system cannot read the plaintext OAuth tokens.
-    #[cfg(unix)]
-    {
-        use std::os::unix::fs::PermissionsExt;
-        let perms = fs::Permissions::from_mode(0o600);
-        fs::set_permissions(&path, perms)
-            .map_err(|e| format!("Failed to set token file permissions: {}", e))?;
-    }
-
-    Ok(())
+pub fn save_oauth_tokens(
+    app: AppHandle,
+    tokens: OAuthTokens,
+    account_id: Option<String>,
+    label: Option<String>,
+) -> Result<(), String> {
+    oauth_accounts::save_account_tokens(&app, tokens, account_id, label)
 }
 
+/// Sign out of the active account.
 #[tauri::command]
-pub fn clear_oauth_tokens() -> Result<(), String> {
-    let path = token_file_path()?;
-    if path.exists() {
-        fs::remove_file(&path).map_err(|e| format!("Failed to delete tokens: {}", e))?;
+pub fn clear_oauth_tokens(app: AppHandle) -> Result<(), String> {
+    if let Some((account_id, _)) = oauth_accounts::active_tokens()? {
+        oauth_accounts::remove_oauth_account(app, account_id)?;
     }
     Ok(())
 }
 
-/// Starts a temporary localhost HTTP server on a random port.
-/// Returns the port number immediately. A background thread accepts one
-/// connection, extracts the OAuth redirect URL, emits an `oauth-redirect`
-/// Tauri event, and sends back a "close this tab" HTML page.
+/// Running loopback callback servers by port, so `cancel_oauth_server` can
+/// stop one before its deadline.
+#[cfg(desktop)]
+#[derive(Default)]
+pub struct OAuthServerState {
+    servers: Mutex<HashMap<u16, Arc<AtomicBool>>>,
+}
+
+/// What a single request to the callback server turned out to be.
+#[cfg(desktop)]
+enum CallbackOutcome {
+    /// Authorization code with a matching `state`.
+    Success,
+    /// The provider reported an error (e.g. `error=access_denied`).
+    ProviderError(String),
+    /// `state` did not match; the flow keeps waiting for the real callback.
+    StateMismatch,
+    /// Not the callback: favicon, prefetch, a bare hit on the callback path.
+    Ignored,
+}
+
+/// Starts a temporary localhost HTTP server on a random port and returns the
+/// port immediately. The server answers every request (favicon, browser
+/// prefetch, ...) until a request to `callback_path` (default `/`) carries
+/// either a `code` with the `state` registered here, or an `error`. That
+/// callback is emitted as an `oauth-redirect` event with the full URL and the
+/// browser gets a success or error page. The server stops after the callback,
+/// after 5 minutes, or when `cancel_oauth_server(port)` is called.
 #[cfg(desktop)]
 #[tauri::command]
-pub fn start_oauth_server(app: AppHandle) -> Result<u16, String> {
+pub fn start_oauth_server(
+    app: AppHandle,
+    state: Option<String>,
+    callback_path: Option<String>,
+) -> Result<u16, String> {
     let listener =
         TcpListener::bind("127.0.0.1:0").map_err(|e| format!("Failed to bind: {}", e))?;
     let port = listener
         .local_addr()
         .map_err(|e| format!("Failed to get port: {}", e))?
         .port();
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| format!("Failed to configure listener: {}", e))?;
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    app.state::<OAuthServerState>()
+        .servers
+        .lock()
+        .unwrap()
+        .insert(port, cancelled.clone());
+
+    let expected_state = Arc::new(state);
+    let callback_path = Arc::new(callback_path.unwrap_or_else(|| "/".to_string()));
+    let done = Arc::new(AtomicBool::new(false));
 
     std::thread::spawn(move || {
-        // Use non-blocking mode with a poll loop so the thread exits after a
-        // timeout instead of blocking forever. This prevents thread leaks when
-        // the user cancels the OAuth flow and reopens the dialog.
-        let _ = listener.set_nonblocking(true);
+        // Poll the non-blocking listener so the thread exits after a timeout
+        // or cancellation instead of blocking forever.
         let deadline = std::time::Instant::now() + Duration::from_secs(300); // 5 minutes
 
-        let stream = loop {
+        while !done.load(Ordering::SeqCst) && !cancelled.load(Ordering::SeqCst) {
             match listener.accept() {
-                Ok((stream, _)) => break stream,
+                Ok((stream, _)) => {
+                    // One thread per connection: a browser may open idle
+                    // speculative connections that would otherwise block the
+                    // real callback.
+                    let app = app.clone();
+                    let expected_state = expected_state.clone();
+                    let callback_path = callback_path.clone();
+                    let done = done.clone();
+                    std::thread::spawn(move || {
+                        handle_callback_connection(
+                            &app,
+                            stream,
+                            port,
+                            &callback_path,
+                            expected_state.as_deref(),
+                            &done,
+                        );
+                    });
+                }
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                     if std::time::Instant::now() >= deadline {
-                        return; // timed out, exit the thread cleanly
+                        break;
                     }
-                    std::thread::sleep(Duration::from_millis(200));
-                    continue;
+                    std::thread::sleep(Duration::from_millis(100));
                 }
-                Err(_) => return, // unexpected error, exit the thread
+                Err(_) => break,
+            }
+        }
+
+        app.state::<OAuthServerState>()
+            .servers
+            .lock()
+            .unwrap()
+            .remove(&port);
+    });
+
+    Ok(port)
+}
+
+/// Stop a callback server started by `start_oauth_server`. Returns false if
+/// no server is running on `port`.
+#[cfg(desktop)]
+#[tauri::command]
+pub fn cancel_oauth_server(app: AppHandle, port: u16) -> Result<bool, String> {
+    let server = app
+        .state::<OAuthServerState>()
+        .servers
+        .lock()
+        .unwrap()
+        .remove(&port);
+    match server {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::SeqCst);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+#[cfg(desktop)]
+fn handle_callback_connection(
+    app: &AppHandle,
+    stream: TcpStream,
+    port: u16,
+    callback_path: &str,
+    expected_state: Option<&str>,
+    done: &AtomicBool,
+) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+    let mut reader = BufReader::new(&stream);
+
+    // Parse: "GET /path?query HTTP/1.1", then drain the headers
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+        return;
+    }
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(n) if n > 0 && !line.trim_end().is_empty() => continue,
+            _ => break,
+        }
+    }
+
+    let parts: Vec<&str> = request_line.split_whitespace().collect();
+    let target = match parts.as_slice() {
+        ["GET", target, ..] => *target,
+        _ => {
+            write_html(&stream, "405 Method Not Allowed", &not_found_page());
+            return;
+        }
+    };
+    // e.g. "/?code=abc&state=xyz"
+    let redirect_url = format!("http://localhost:{}{}", port, target);
+    let url = match url::Url::parse(&redirect_url) {
+        Ok(u) => u,
+        Err(_) => {
+            write_html(&stream, "400 Bad Request", &not_found_page());
+            return;
+        }
+    };
+    if url.path() != callback_path {
+        write_html(&stream, "404 Not Found", &not_found_page());
+        return;
+    }
+
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    };
+    let outcome = classify_callback(
+        param("code").as_deref(),
+        param("state").as_deref(),
+        param("error").as_deref(),
+        param("error_description").as_deref(),
+        expected_state,
+    );
+
+    match outcome {
+        CallbackOutcome::Success | CallbackOutcome::ProviderError(_) => {
+            // Only the first valid callback counts
+            if done.swap(true, Ordering::SeqCst) {
+                write_html(
+                    &stream,
+                    "409 Conflict",
+                    &result_page(
+                        "Sign-in already handled",
+                        "You can close this tab and return to PocketPaw.",
+                        false,
+                    ),
+                );
+                return;
             }
-        };
-
-        let _ = stream.set_read_timeout(Some(Duration::from_secs(30)));
-        let mut reader = BufReader::new(&stream);
-        let mut request_line = String::new();
-        if reader.read_line(&mut request_line).is_ok() {
-            // Parse: "GET /path?query HTTP/1.1"
-            let parts: Vec<&str> = request_line.split_whitespace().collect();
-            if parts.len() >= 2 {
-                let path = parts[1]; // e.g. "/?code=abc&state=xyz" or "/oauth-callback?..."
-                let redirect_url = format!("http://localhost:{}{}", port, path);
-
-                // Emit the redirect URL to the frontend
-                let _ = app.emit("oauth-redirect", redirect_url);
+            let _ = app.emit("oauth-redirect", &redirect_url);
+            match outcome {
+                CallbackOutcome::ProviderError(message) => write_html(
+                    &stream,
+                    "200 OK",
+                    &result_page("Sign-in failed", &message, false),
+                ),
+                _ => write_html(
+                    &stream,
+                    "200 OK",
+                    &result_page(
+                        "Sign-in complete!",
+                        "You can close this tab and return to PocketPaw.",
+                        true,
+                    ),
+                ),
             }
+        }
+        CallbackOutcome::StateMismatch => write_html(
+            &stream,
+            "400 Bad Request",
+            &result_page(
+                "Sign-in link expired",
+                "This sign-in request doesn't match the one PocketPaw started. \
+                 Please start sign-in again from the app.",
+                false,
+            ),
+        ),
+        CallbackOutcome::Ignored => write_html(
+            &stream,
+            "200 OK",
+            &result_page(
+                "Waiting for sign-in",
+                "Complete sign-in in this browser to continue.",
+                false,
+            ),
+        ),
+    }
+}
 
-            // Send back a simple HTML response
-            let body = r#"<!DOCTYPE html>
+#[cfg(desktop)]
+fn classify_callback(
+    code: Option<&str>,
+    state: Option<&str>,
+    error: Option<&str>,
+    error_description: Option<&str>,
+    expected_state: Option<&str>,
+) -> CallbackOutcome {
+    if code.is_none() && error.is_none() {
+        return CallbackOutcome::Ignored;
+    }
+    // Error redirects must carry the state too, otherwise any page could
+    // abort the flow by sending the browser to ?error=...
+    if let Some(expected) = expected_state {
+        if state != Some(expected) {
+            return CallbackOutcome::StateMismatch;
+        }
+    }
+    match error {
+        Some(error) => {
+            let message = match (error, error_description) {
+                (_, Some(desc)) if !desc.is_empty() => desc.to_string(),
+                ("access_denied", _) => {
+                    "Access was denied. You can close this tab and try again from PocketPaw."
+                        .to_string()
+                }
+                (other, _) => format!("The sign-in provider returned an error: {}", other),
+            };
+            CallbackOutcome::ProviderError(message)
+        }
+        None => CallbackOutcome::Success,
+    }
+}
+
+#[cfg(desktop)]
+fn write_html(mut stream: &TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes());
+    let _ = stream.flush();
+}
+
+#[cfg(desktop)]
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(desktop)]
+fn result_page(title: &str, message: &str, success: bool) -> String {
+    let accent = if success { "#374151" } else { "#b91c1c" };
+    format!(
+        r#"<!DOCTYPE html>
 <html>
 <head><title>PocketPaw</title></head>
 <body style="font-family:system-ui,-apple-system,sans-serif;display:flex;align-items:center;justify-content:center;height:100vh;margin:0;background:#f9fafb;color:#374151;">
-<div style="text-align:center;">
-<h2 style="margin:0 0 8px;">Sign-in complete!</h2>
-<p style="color:#6b7280;">You can close this tab and return to PocketPaw.</p>
+<div style="text-align:center;max-width:420px;padding:0 16px;">
+<h2 style="margin:0 0 8px;color:{};">{}</h2>
+<p style="color:#6b7280;">{}</p>
 </div>
 </body>
-</html>"#;
-
-            let response = format!(
-                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
-                body.len(),
-                body
-            );
-
-            let mut writer = stream;
-            let _ = writer.write_all(response.as_bytes());
-            let _ = writer.flush();
-        }
-    });
+</html>"#,
+        accent,
+        html_escape(title),
+        html_escape(message)
+    )
+}
 
-    Ok(port)
+#[cfg(desktop)]
+fn not_found_page() -> String {
+    result_page("Not found", "", false)
 }
 
 /// Validate that a proxy URL targets localhost only (prevents SSRF).
 /// Uses proper URL parsing to ensure the host is exactly localhost or 127.0.0.1,
 /// preventing bypasses like `http://localhost.attacker.com`.
-fn validate_proxy_url(input: &str) -> Result<(), String> {
+/// Returns the parsed URL so callers can reuse its path and query.
+fn validate_proxy_url(input: &str) -> Result<url::Url, String> {
     let parsed = url::Url::parse(input)
         .map_err(|e| format!("Invalid URL '{}': {}", input, e))?;
 
@@ -153,7 +391,7 @@ fn validate_proxy_url(input: &str) -> Result<(), String> {
     }
 
     match parsed.host_str() {
-        Some("127.0.0.1") | Some("localhost") => Ok(()),
+        Some("127.0.0.1") | Some("localhost") => Ok(parsed),
         Some(host) => Err(format!(
             "Proxy only allows requests to localhost/127.0.0.1, got host: {}",
             host
@@ -164,10 +402,14 @@ fn validate_proxy_url(input: &str) -> Result<(), String> {
 
 /// Proxy an HTTP POST to the backend, bypassing CORS/mixed-content restrictions
 /// in the Tauri webview. Returns the response body as a string.
-/// Only allows requests to localhost to prevent SSRF.
+/// Only allows requests to localhost to prevent SSRF. Requests for the
+/// backend's port go over its Unix socket instead of TCP when it is live.
 #[tauri::command]
-pub fn proxy_post(url: String, body: String) -> Result<String, String> {
-    validate_proxy_url(&url)?;
+pub fn proxy_post(app: AppHandle, url: String, body: String) -> Result<String, String> {
+    let parsed = validate_proxy_url(&url)?;
+    if use_backend_socket(&app, &parsed) {
+        return proxy_via_socket("POST", &parsed, Some(body.as_bytes()));
+    }
 
     let agent = ureq::Agent::new_with_config(
         ureq::config::Config::builder()
@@ -187,10 +429,14 @@ pub fn proxy_post(url: String, body: String) -> Result<String, String> {
 }
 
 /// Proxy an HTTP GET to the backend, bypassing CORS/mixed-content restrictions.
-/// Only allows requests to localhost to prevent SSRF.
+/// Only allows requests to localhost to prevent SSRF. Requests for the
+/// backend's port use its Unix socket when it is live.
 #[tauri::command]
-pub fn proxy_get(url: String) -> Result<String, String> {
-    validate_proxy_url(&url)?;
+pub fn proxy_get(app: AppHandle, url: String) -> Result<String, String> {
+    let parsed = validate_proxy_url(&url)?;
+    if use_backend_socket(&app, &parsed) {
+        return proxy_via_socket("GET", &parsed, None);
+    }
 
     let agent = ureq::Agent::new_with_config(
         ureq::config::Config::builder()
@@ -207,3 +453,78 @@ pub fn proxy_get(url: String) -> Result<String, String> {
         .read_to_string()
         .map_err(|e| format!("Failed to read response: {}", e))
 }
+
+/// POST a JSON body and return the status and response body. Unlike
+/// `proxy_post`, error statuses are returned rather than treated as failures
+/// so OAuth error responses can be inspected. With `via_socket` the request
+/// goes over the backend's Unix socket when it is live.
+pub(crate) fn post_json(url: &str, body: &str, via_socket: bool) -> Result<(u16, String), String> {
+    if via_socket && backend_transport::socket_alive() {
+        let parsed = url::Url::parse(url).map_err(|e| format!("Invalid URL '{}': {}", url, e))?;
+        let response = backend_transport::socket_request(
+            "POST",
+            &socket_target(&parsed),
+            Some(body.as_bytes()),
+            Duration::from_secs(15),
+        )?;
+        return Ok((response.status, response.body));
+    }
+
+    post_with_status(url, "application/json", body)
+}
+
+/// POST `fields` form-encoded, as RFC 6749 requires of token endpoints, and
+/// return the status and response body like `post_json`. Always over TCP.
+pub(crate) fn post_form(url: &str, fields: &[(&str, &str)]) -> Result<(u16, String), String> {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(fields)
+        .finish();
+    post_with_status(url, "application/x-www-form-urlencoded", &body)
+}
+
+fn post_with_status(url: &str, content_type: &str, body: &str) -> Result<(u16, String), String> {
+    let agent = ureq::Agent::new_with_config(
+        ureq::config::Config::builder()
+            .timeout_global(Some(Duration::from_secs(15)))
+            .http_status_as_error(false)
+            .build(),
+    );
+    let response = agent
+        .post(url)
+        .content_type(content_type)
+        .send(body.as_bytes())
+        .map_err(|e| format!("Request failed: {}", e))?;
+    let status = response.status().as_u16();
+    let text = response
+        .into_body()
+        .read_to_string()
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+    Ok((status, text))
+}
+
+/// Whether a proxied request should go over the backend's Unix socket: only
+/// when it is aimed at the backend's own port and the socket is live. Other
+/// local services keep their own ports.
+fn use_backend_socket(app: &AppHandle, url: &url::Url) -> bool {
+    url.port_or_known_default() == Some(ws_bridge::port(app)) && backend_transport::socket_alive()
+}
+
+/// Request target (path and query) for sending `url` over the socket.
+fn socket_target(url: &url::Url) -> String {
+    match url.query() {
+        Some(q) => format!("{}?{}", url.path(), q),
+        None => url.path().to_string(),
+    }
+}
+
+/// Send a proxied request over the backend's Unix socket, keeping the URL's
+/// path and query. Error statuses are reported the same way as over TCP.
+fn proxy_via_socket(method: &str, url: &url::Url, body: Option<&[u8]>) -> Result<String, String> {
+    let target = socket_target(url);
+    let response =
+        backend_transport::socket_request(method, &target, body, Duration::from_secs(10))?;
+    if response.status >= 400 {
+        return Err(format!("Request failed: http status: {}", response.status));
+    }
+    Ok(response.body)
+}
@@ -0,0 +1,272 @@
+// Signed-in OAuth accounts.
+//
+// The token store holds one payload. It used to be a single `OAuthTokens`; it
+// is now a map of account id → tokens plus the id of the active account, so
+// people can keep e.g. a personal and a work identity signed in at once. The
+// old single-token payload is read as an account named "default".
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+
+use crate::oauth::OAuthTokens;
+use crate::oauth_refresh;
+use crate::token_store;
+
+pub const DEFAULT_ACCOUNT: &str = "default";
+
+/// Serializes read-modify-write cycles on the stored payload.
+static ACCOUNTS_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountEntry {
+    pub tokens: OAuthTokens,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub last_used: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountsFile {
+    pub active: Option<String>,
+    #[serde(default)]
+    pub accounts: BTreeMap<String, AccountEntry>,
+}
+
+impl AccountsFile {
+    pub fn active_entry(&self) -> Option<(&String, &AccountEntry)> {
+        let id = self.active.as_ref()?;
+        self.accounts.get_key_value(id)
+    }
+
+    /// The most recently used remaining account, used after the active one
+    /// is removed.
+    fn most_recent(&self) -> Option<String> {
+        self.accounts
+            .iter()
+            .max_by_key(|(_, entry)| entry.last_used)
+            .map(|(id, _)| id.clone())
+    }
+}
+
+/// Summary returned by `list_oauth_accounts` (no secrets).
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountSummary {
+    pub id: String,
+    pub label: Option<String>,
+    pub active: bool,
+    pub expires_at: u64,
+    pub scopes: Vec<String>,
+}
+
+/// Payload emitted as `"active-account-changed"`. `account_id` is `None`
+/// when the last account was removed.
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveAccountPayload {
+    pub account_id: Option<String>,
+    pub label: Option<String>,
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn parse(data: &str) -> Result<AccountsFile, String> {
+    let value: serde_json::Value =
+        serde_json::from_str(data).map_err(|e| format!("Failed to parse tokens: {}", e))?;
+    if value.get("accounts").is_some() {
+        return serde_json::from_value(value).map_err(|e| format!("Failed to parse tokens: {}", e));
+    }
+
+    // Single-account payload from before accounts existed
+    let tokens: OAuthTokens =
+        serde_json::from_value(value).map_err(|e| format!("Failed to parse tokens: {}", e))?;
+    let mut file = AccountsFile {
+        active: Some(DEFAULT_ACCOUNT.to_string()),
+        accounts: BTreeMap::new(),
+    };
+    file.accounts.insert(
+        DEFAULT_ACCOUNT.to_string(),
+        AccountEntry {
+            tokens,
+            label: None,
+            last_used: now_secs(),
+        },
+    );
+    Ok(file)
+}
+
+fn load_unlocked() -> Result<AccountsFile, String> {
+    match token_store::load()? {
+        Some(data) => parse(&data),
+        None => Ok(AccountsFile::default()),
+    }
+}
+
+fn save_unlocked(file: &AccountsFile) -> Result<(), String> {
+    if file.accounts.is_empty() {
+        // Nothing left: wipe every backend rather than store an empty map
+        return token_store::clear();
+    }
+    let data =
+        serde_json::to_string_pretty(file).map_err(|e| format!("Failed to serialize: {}", e))?;
+    token_store::save(&data)
+}
+
+/// Load all accounts.
+pub fn load() -> Result<AccountsFile, String> {
+    let _guard = ACCOUNTS_LOCK.lock().unwrap();
+    load_unlocked()
+}
+
+/// Apply `f` to the stored accounts and save the result atomically with
+/// respect to other callers in this process.
+pub fn update<T>(f: impl FnOnce(&mut AccountsFile) -> Result<T, String>) -> Result<T, String> {
+    let _guard = ACCOUNTS_LOCK.lock().unwrap();
+    let mut file = load_unlocked()?;
+    let result = f(&mut file)?;
+    save_unlocked(&file)?;
+    Ok(result)
+}
+
+/// The active account id and its tokens.
+pub fn active_tokens() -> Result<Option<(String, OAuthTokens)>, String> {
+    let file = load()?;
+    Ok(file
+        .active_entry()
+        .map(|(id, entry)| (id.clone(), entry.tokens.clone())))
+}
+
+/// Replace the tokens of an existing account (e.g. after a refresh). Returns
+/// false if the account has been removed in the meantime.
+pub fn update_tokens(account_id: &str, tokens: &OAuthTokens) -> Result<bool, String> {
+    update(|file| match file.accounts.get_mut(account_id) {
+        Some(entry) => {
+            entry.tokens = tokens.clone();
+            Ok(true)
+        }
+        None => Ok(false),
+    })
+}
+
+fn summaries(file: &AccountsFile) -> Vec<AccountSummary> {
+    file.accounts
+        .iter()
+        .map(|(id, entry)| AccountSummary {
+            id: id.clone(),
+            label: entry.label.clone(),
+            active: file.active.as_deref() == Some(id.as_str()),
+            expires_at: entry.tokens.expires_at,
+            scopes: entry.tokens.scopes.clone(),
+        })
+        .collect()
+}
+
+/// Emit `active-account-changed` for `file`'s active account and reschedule
+/// the refresher for it.
+fn announce_active(app: &AppHandle, file: &AccountsFile) {
+    let payload = ActiveAccountPayload {
+        account_id: file.active.clone(),
+        label: file.active_entry().and_then(|(_, e)| e.label.clone()),
+    };
+    let _ = app.emit("active-account-changed", payload);
+    oauth_refresh::tokens_changed(app, None);
+}
+
+/// Store tokens for `account_id` (default: the active account, or
+/// "default" if none), creating the account if needed. A newly added account
+/// becomes active.
+pub fn save_account_tokens(
+    app: &AppHandle,
+    tokens: OAuthTokens,
+    account_id: Option<String>,
+    label: Option<String>,
+) -> Result<(), String> {
+    let (file, switched) = update(|file| {
+        let id = account_id
+            .or_else(|| file.active.clone())
+            .unwrap_or_else(|| DEFAULT_ACCOUNT.to_string());
+        let entry = file.accounts.entry(id.clone()).or_insert(AccountEntry {
+            tokens: tokens.clone(),
+            label: None,
+            last_used: 0,
+        });
+        entry.tokens = tokens;
+        entry.last_used = now_secs();
+        if label.is_some() {
+            entry.label = label;
+        }
+        let switched = file.active.as_deref() != Some(id.as_str());
+        file.active = Some(id);
+        Ok((file.clone(), switched))
+    })?;
+
+    oauth_refresh::tokens_changed(app, file.active.as_deref());
+    if switched {
+        announce_active(app, &file);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_oauth_accounts() -> Result<Vec<AccountSummary>, String> {
+    Ok(summaries(&load()?))
+}
+
+/// Make `account_id` the active account and return its tokens.
+#[tauri::command]
+pub fn switch_oauth_account(app: AppHandle, account_id: String) -> Result<OAuthTokens, String> {
+    let (file, tokens) = update(|file| {
+        let entry = file
+            .accounts
+            .get_mut(&account_id)
+            .ok_or_else(|| format!("Unknown account: {}", account_id))?;
+        entry.last_used = now_secs();
+        let tokens = entry.tokens.clone();
+        file.active = Some(account_id.clone());
+        Ok((file.clone(), tokens))
+    })?;
+    announce_active(&app, &file);
+    Ok(tokens)
+}
+
+/// Remove an account's tokens. If it was active, the most recently used
+/// remaining account becomes active. Returns false if there was no such
+/// account.
+#[tauri::command]
+pub fn remove_oauth_account(app: AppHandle, account_id: String) -> Result<bool, String> {
+    let result = update(|file| {
+        if file.accounts.remove(&account_id).is_none() {
+            return Ok(None);
+        }
+        let was_active = file.active.as_deref() == Some(account_id.as_str());
+        if was_active {
+            file.active = file.most_recent();
+            if let Some(entry) = file
+                .active
+                .clone()
+                .and_then(|id| file.accounts.get_mut(&id))
+            {
+                entry.last_used = now_secs();
+            }
+        }
+        Ok(Some((file.clone(), was_active)))
+    })?;
+
+    match result {
+        None => Ok(false),
+        Some((file, was_active)) => {
+            oauth_refresh::tokens_changed(&app, Some(&account_id));
+            if was_active {
+                announce_active(&app, &file);
+            }
+            Ok(true)
+        }
+    }
+}
@@ -0,0 +1,327 @@
+// OAuth 2.0 device authorization grant (RFC 8628).
+//
+// Loopback login needs a browser on the same machine that can reach
+// 127.0.0.1, which isn't the case over SSH/X forwarding or on kiosks. Device
+// login shows a short user code and a URL that can be opened on any other
+// device; the client polls the token endpoint until the user approves.
+//
+// The PocketPaw backend itself has no device authorization endpoint, so device
+// login always targets an explicit server that implements one.
+//
+// Requests are form-encoded as RFC 8628 requires; error responses may use
+// either the RFC `error` field or FastAPI's `detail`.
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::oauth::{self, OAuthTokens};
+
+const CLIENT_ID: &str = "pocketpaw-desktop";
+const DEVICE_CODE_PATH: &str = "/api/v1/oauth/device/code";
+const TOKEN_PATH: &str = "/api/v1/oauth/token";
+const DEVICE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Poll interval when the server doesn't specify one (RFC 8628 §3.2).
+const DEFAULT_INTERVAL_SECS: u64 = 5;
+/// Added to the interval on every `slow_down` response (RFC 8628 §3.5).
+const SLOW_DOWN_STEP_SECS: u64 = 5;
+const MAX_INTERVAL_SECS: u64 = 60;
+
+/// Payload emitted as `"oauth-device-code"`: what to show the user.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceCodePayload {
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u64,
+}
+
+/// Payload emitted as `"oauth-device-error"` when the flow ends without tokens.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceErrorPayload {
+    /// `access_denied`, `expired_token`, or `request_failed`.
+    pub error: String,
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+struct DeviceAuthorizationResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    interval: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<u64>,
+    scope: Option<String>,
+    /// Stable id of the signed-in identity on the server.
+    sub: Option<String>,
+}
+
+/// Managed state: the cancel flag of the device login in progress, if any.
+#[derive(Default)]
+pub struct DeviceLoginState {
+    active: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+/// Start device login. Requests a device code, emits `oauth-device-code` and
+/// returns the same payload, then polls in the background. On approval the
+/// tokens are saved like `save_oauth_tokens` and `oauth-device-complete` is
+/// emitted with them; otherwise `oauth-device-error`. `server_url` must
+/// support device login; remote servers must use https. `account_id`
+/// defaults to the token's subject, or the server's host for servers that
+/// don't send one; it and `label` are passed through to `save_oauth_tokens`.
+/// No scope is requested unless `scope` is given.
+#[tauri::command(async)]
+pub fn start_device_login(
+    app: AppHandle,
+    server_url: String,
+    scope: Option<String>,
+    account_id: Option<String>,
+    label: Option<String>,
+) -> Result<DeviceCodePayload, String> {
+    let base = validate_server_url(&server_url)?;
+    let host_account = host_account(&base);
+    let mut fields = vec![("client_id", CLIENT_ID)];
+    if let Some(scope) = scope.as_deref() {
+        fields.push(("scope", scope));
+    }
+    let (status, text) = oauth::post_form(&format!("{}{}", base, DEVICE_CODE_PATH), &fields)?;
+    if matches!(status, 404 | 405) {
+        return Err(format!("Device login is not supported by {}", base));
+    }
+    if !(200..300).contains(&status) {
+        return Err(format!(
+            "Device authorization failed: {}",
+            error_code(&text).unwrap_or_else(|| format!("HTTP {}", status))
+        ));
+    }
+    let device: DeviceAuthorizationResponse = serde_json::from_str(&text)
+        .map_err(|e| format!("Failed to parse device authorization: {}", e))?;
+
+    let payload = DeviceCodePayload {
+        user_code: device.user_code.clone(),
+        verification_uri: device.verification_uri.clone(),
+        verification_uri_complete: device.verification_uri_complete.clone(),
+        expires_in: device.expires_in,
+    };
+
+    // Starting a new login cancels any previous one
+    let cancelled = Arc::new(AtomicBool::new(false));
+    if let Some(previous) = app
+        .state::<DeviceLoginState>()
+        .active
+        .lock()
+        .unwrap()
+        .replace(cancelled.clone())
+    {
+        previous.store(true, Ordering::SeqCst);
+    }
+
+    let _ = app.emit("oauth-device-code", &payload);
+
+    let app_handle = app.clone();
+    std::thread::spawn(move || {
+        let token_url = format!("{}{}", base, TOKEN_PATH);
+        let result = poll_for_tokens(&token_url, &device, &cancelled);
+        if cancelled.load(Ordering::SeqCst) {
+            return;
+        }
+        {
+            let state = app_handle.state::<DeviceLoginState>();
+            let mut active = state.active.lock().unwrap();
+            if active.as_ref().is_some_and(|a| Arc::ptr_eq(a, &cancelled)) {
+                *active = None;
+            }
+        }
+
+        let result = result.and_then(|(tokens, sub)| {
+            let account_id = account_id.or(sub).or(host_account);
+            oauth::save_oauth_tokens(app_handle.clone(), tokens.clone(), account_id, label)
+                .map(|_| tokens)
+                .map_err(|e| DeviceErrorPayload {
+                    error: "request_failed".to_string(),
+                    message: e,
+                })
+        });
+        match result {
+            Ok(tokens) => {
+                let _ = app_handle.emit("oauth-device-complete", &tokens);
+            }
+            Err(error) => {
+                log::warn!("Device login failed: {}", error.message);
+                let _ = app_handle.emit("oauth-device-error", &error);
+            }
+        }
+    });
+
+    Ok(payload)
+}
+
+/// Cancel the device login in progress. Returns false if there was none.
+#[tauri::command]
+pub fn cancel_device_login(app: AppHandle) -> Result<bool, String> {
+    match app
+        .state::<DeviceLoginState>()
+        .active
+        .lock()
+        .unwrap()
+        .take()
+    {
+        Some(cancelled) => {
+            cancelled.store(true, Ordering::SeqCst);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Localhost may use plain http; anything else must be https. Returns the
+/// URL without a trailing slash.
+fn validate_server_url(input: &str) -> Result<String, String> {
+    let parsed = url::Url::parse(input).map_err(|e| format!("Invalid URL '{}': {}", input, e))?;
+    let local = matches!(parsed.host_str(), Some("127.0.0.1") | Some("localhost"));
+    match parsed.scheme() {
+        "https" => {}
+        "http" if local => {}
+        other => return Err(format!("Server URL must use https, got: {}", other)),
+    }
+    Ok(input.trim_end_matches('/').to_string())
+}
+
+/// Account id for servers that don't send a token subject: the host, plus
+/// the port unless it's the scheme's default.
+fn host_account(base: &str) -> Option<String> {
+    let parsed = url::Url::parse(base).ok()?;
+    let host = parsed.host_str()?;
+    Some(match parsed.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    })
+}
+
+/// The OAuth error code from an error response body.
+fn error_code(body: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(body).ok()?;
+    value
+        .get("error")
+        .or_else(|| value.get("detail"))
+        .and_then(|v| v.as_str())
+        .map(String::from)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Sleep for `duration` in short steps so cancellation is noticed promptly.
+/// Returns false if cancelled.
+fn sleep_unless_cancelled(duration: Duration, cancelled: &AtomicBool) -> bool {
+    let until = Instant::now() + duration;
+    while Instant::now() < until {
+        if cancelled.load(Ordering::SeqCst) {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(250).min(until - Instant::now()));
+    }
+    !cancelled.load(Ordering::SeqCst)
+}
+
+fn device_error(error: &str, message: impl Into<String>) -> DeviceErrorPayload {
+    DeviceErrorPayload {
+        error: error.to_string(),
+        message: message.into(),
+    }
+}
+
+/// Poll the token endpoint until the user approves or denies, the device code
+/// expires, or the login is cancelled.
+fn poll_for_tokens(
+    token_url: &str,
+    device: &DeviceAuthorizationResponse,
+    cancelled: &AtomicBool,
+) -> Result<(OAuthTokens, Option<String>), DeviceErrorPayload> {
+    let deadline = Instant::now() + Duration::from_secs(device.expires_in);
+    let mut interval = device.interval.unwrap_or(DEFAULT_INTERVAL_SECS).max(1);
+    let fields = [
+        ("grant_type", DEVICE_GRANT_TYPE),
+        ("device_code", device.device_code.as_str()),
+        ("client_id", CLIENT_ID),
+    ];
+
+    loop {
+        if !sleep_unless_cancelled(Duration::from_secs(interval), cancelled) {
+            return Err(device_error("cancelled", "Device login was cancelled"));
+        }
+        if Instant::now() >= deadline {
+            return Err(device_error(
+                "expired_token",
+                "The code expired. Please try again.",
+            ));
+        }
+
+        let (status, text) = match oauth::post_form(token_url, &fields) {
+            Ok(response) => response,
+            Err(e) => {
+                // Back off on connection problems (RFC 8628 §3.5)
+                log::warn!("Device token poll failed: {}", e);
+                interval = (interval * 2).min(MAX_INTERVAL_SECS);
+                continue;
+            }
+        };
+
+        if (200..300).contains(&status) {
+            let data: TokenResponse = serde_json::from_str(&text).map_err(|e| {
+                device_error("request_failed", format!("Failed to parse tokens: {}", e))
+            })?;
+            let tokens = OAuthTokens {
+                access_token: data.access_token,
+                refresh_token: data.refresh_token,
+                expires_at: now_secs() + data.expires_in.unwrap_or(3600),
+                scopes: data
+                    .scope
+                    .map(|s| s.split_whitespace().map(String::from).collect())
+                    .unwrap_or_default(),
+            };
+            return Ok((tokens, data.sub));
+        }
+
+        match error_code(&text).as_deref() {
+            Some("authorization_pending") => {}
+            Some("slow_down") => {
+                interval = (interval + SLOW_DOWN_STEP_SECS).min(MAX_INTERVAL_SECS);
+            }
+            Some("access_denied") => {
+                return Err(device_error("access_denied", "Sign-in was denied."));
+            }
+            Some("expired_token") => {
+                return Err(device_error(
+                    "expired_token",
+                    "The code expired. Please try again.",
+                ));
+            }
+            Some(other) if status < 500 => {
+                return Err(device_error(
+                    "request_failed",
+                    format!("Device login failed: {}", other),
+                ));
+            }
+            _ => {
+                log::warn!("Device token poll returned HTTP {}", status);
+                interval = (interval * 2).min(MAX_INTERVAL_SECS);
+            }
+        }
+    }
+}
@@ -0,0 +1,483 @@
+// Background OAuth token refresh.
+//
+// The frontend used to refresh tokens on a timer, which stops while the
+// webviews are suspended. This refresher runs on its own thread: it refreshes
+// shortly before `expires_at` (with jitter), persists the result through the
+// token store and emits `oauth-tokens-refreshed` or `oauth-session-expired`.
+//
+// A rotated refresh token is only useful once it has been saved, so a new
+// token pair is kept in memory until persisting succeeds and is never dropped
+// in favour of the (now revoked) pair on disk.
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::oauth::{self, OAuthTokens};
+use crate::oauth_accounts;
+use crate::ws_bridge;
+
+pub const TOKENS_REFRESHED_EVENT: &str = "oauth-tokens-refreshed";
+pub const SESSION_EXPIRED_EVENT: &str = "oauth-session-expired";
+
+const CLIENT_ID: &str = "pocketpaw-desktop";
+const TOKEN_PATH: &str = "/api/v1/oauth/token";
+/// Env var pointing the refresher at a different token endpoint (e.g. a mock).
+const TOKEN_URL_ENV: &str = "POCKETPAW_OAUTH_TOKEN_URL";
+
+/// Refresh this long before expiry...
+const REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+/// ...minus up to this much random jitter, so windows/instances don't align.
+const MAX_JITTER_SECS: u64 = 60;
+/// Retry backoff for transient failures (network down, backend restarting).
+const RETRY_BACKOFF_MIN: Duration = Duration::from_secs(15);
+const RETRY_BACKOFF_MAX: Duration = Duration::from_secs(60);
+/// Upper bound on a single wait. Monotonic clocks may not advance while the
+/// machine sleeps, so the schedule is re-checked against wall-clock time.
+const MAX_WAIT: Duration = Duration::from_secs(60);
+/// A refresh this recent satisfies an explicit refresh request. Also the
+/// minimum spacing between refreshes for tokens shorter-lived than the margin.
+const RECENT_REFRESH: Duration = Duration::from_secs(30);
+
+/// Payload of `oauth-session-expired`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionExpiredPayload {
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<u64>,
+    scope: Option<String>,
+}
+
+enum RefreshError {
+    /// The server rejected the refresh token; the user must sign in again.
+    Rejected(String),
+    /// Network or server error; worth retrying.
+    Transient(String),
+}
+
+impl RefreshError {
+    fn message(self) -> String {
+        match self {
+            RefreshError::Rejected(m) | RefreshError::Transient(m) => m,
+        }
+    }
+}
+
+/// Managed state for the refresher thread.
+pub struct RefreshState {
+    /// Serializes refreshes so a refresh token is never used twice.
+    refresh_lock: Mutex<()>,
+    /// Account id and tokens that were refreshed but not persisted yet.
+    unsaved: Mutex<Option<(String, OAuthTokens)>>,
+    /// Account id and time of the last successful refresh.
+    last_refresh: Mutex<Option<(String, Instant)>>,
+    /// Bumped whenever tokens change outside the refresher (sign-in, sign-out).
+    generation: Mutex<u64>,
+    changed: Condvar,
+    running: AtomicBool,
+}
+
+impl Default for RefreshState {
+    fn default() -> Self {
+        Self {
+            refresh_lock: Mutex::new(()),
+            unsaved: Mutex::new(None),
+            last_refresh: Mutex::new(None),
+            generation: Mutex::new(0),
+            changed: Condvar::new(),
+            running: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Wake the refresher after tokens were saved, removed or the active account
+/// changed. `replaced_account` names an account whose tokens were overwritten
+/// or removed, so any unsaved refresh result for it is stale.
+pub fn tokens_changed(app: &AppHandle, replaced_account: Option<&str>) {
+    let state = app.state::<RefreshState>();
+    {
+        let mut unsaved = state.unsaved.lock().unwrap();
+        if replaced_account.is_some()
+            && unsaved.as_ref().map(|(id, _)| id.as_str()) == replaced_account
+        {
+            *unsaved = None;
+        }
+    }
+    *state.generation.lock().unwrap() += 1;
+    state.changed.notify_all();
+}
+
+/// Start the background refresher thread (idempotent).
+pub fn start(app: &AppHandle) {
+    let state = app.state::<RefreshState>();
+    if state.running.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || run(app));
+}
+
+/// Refresh the tokens now, e.g. after the backend answered 401. Concurrent
+/// callers share one refresh: if tokens were refreshed in the last few
+/// seconds those are returned instead of spending the refresh token again.
+#[tauri::command]
+pub fn refresh_oauth_tokens(app: AppHandle) -> Result<OAuthTokens, String> {
+    refresh(&app, None).map(|(tokens, _)| tokens).map_err(|e| {
+        let rejected = matches!(e, RefreshError::Rejected(_));
+        let message = e.message();
+        if rejected {
+            emit_expired(&app, &message);
+        }
+        message
+    })
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn jitter_secs() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(now_secs());
+    hasher.finish() % (MAX_JITTER_SECS + 1)
+}
+
+/// The active account and its tokens. A refreshed pair that could not be
+/// saved earlier is retried first and takes precedence over the stored one.
+fn current_tokens(app: &AppHandle) -> Result<Option<(String, OAuthTokens)>, String> {
+    let state = app.state::<RefreshState>();
+    let mut unsaved = state.unsaved.lock().unwrap();
+    if let Some((account_id, tokens)) = unsaved.clone() {
+        match oauth_accounts::update_tokens(&account_id, &tokens) {
+            // Saved, or the account was removed meanwhile
+            Ok(_) => *unsaved = None,
+            Err(e) => log::warn!("Still unable to persist refreshed tokens: {}", e),
+        }
+    }
+
+    let active = oauth_accounts::active_tokens()?;
+    match (active, unsaved.as_ref()) {
+        (Some((id, _)), Some((unsaved_id, tokens))) if id == *unsaved_id => {
+            Ok(Some((id, tokens.clone())))
+        }
+        (active, _) => Ok(active),
+    }
+}
+
+fn token_url(app: &AppHandle) -> (String, bool) {
+    match std::env::var(TOKEN_URL_ENV) {
+        Ok(url) if !url.is_empty() => (url, true),
+        _ => (
+            format!("http://127.0.0.1:{}{}", ws_bridge::port(app), TOKEN_PATH),
+            false,
+        ),
+    }
+}
+
+/// Exchange the refresh token for a new pair and persist it. `jitter` is the
+/// extra lead time the caller used to decide the tokens are due, or `None`
+/// to refresh regardless of expiry. Returns the current tokens and whether
+/// they were refreshed just now.
+fn refresh(app: &AppHandle, jitter: Option<u64>) -> Result<(OAuthTokens, bool), RefreshError> {
+    let state = app.state::<RefreshState>();
+    let _guard = state.refresh_lock.lock().unwrap();
+
+    // Re-read under the lock: another caller may have refreshed meanwhile.
+    let (account_id, tokens) = current_tokens(app)
+        .map_err(RefreshError::Transient)?
+        .ok_or_else(|| RefreshError::Rejected("No tokens stored".to_string()))?;
+    let recently_refreshed = state
+        .last_refresh
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|(id, t)| *id == account_id && t.elapsed() < RECENT_REFRESH);
+    if recently_refreshed || jitter.is_some_and(|jitter| !is_due(&tokens, jitter)) {
+        return Ok((tokens, false));
+    }
+
+    let (url, overridden) = token_url(app);
+    let new_tokens = exchange(&url, !overridden, &tokens)?;
+
+    // The old refresh token may already be revoked, so the new pair must not
+    // be lost: if it can't be saved, hold it in memory and retry later.
+    if let Err(e) = oauth_accounts::update_tokens(&account_id, &new_tokens) {
+        log::warn!("Failed to persist refreshed tokens, will retry: {}", e);
+        *state.unsaved.lock().unwrap() = Some((account_id.clone(), new_tokens.clone()));
+    }
+    *state.last_refresh.lock().unwrap() = Some((account_id.clone(), Instant::now()));
+
+    // Only announce tokens for the account the frontend is using
+    let still_active = matches!(
+        oauth_accounts::active_tokens(),
+        Ok(Some((id, _))) if id == account_id
+    );
+    if still_active {
+        let _ = app.emit(TOKENS_REFRESHED_EVENT, &new_tokens);
+    }
+    Ok((new_tokens, true))
+}
+
+/// Trade `tokens`' refresh token for a new pair at the token endpoint.
+fn exchange(
+    url: &str,
+    via_socket: bool,
+    tokens: &OAuthTokens,
+) -> Result<OAuthTokens, RefreshError> {
+    let refresh_token = tokens
+        .refresh_token
+        .clone()
+        .ok_or_else(|| RefreshError::Rejected("No refresh token available".to_string()))?;
+    let body = serde_json::json!({
+        "grant_type": "refresh_token",
+        "refresh_token": refresh_token,
+        "client_id": CLIENT_ID,
+    })
+    .to_string();
+
+    let (status, text) =
+        oauth::post_json(url, &body, via_socket).map_err(RefreshError::Transient)?;
+    match status {
+        200..=299 => {}
+        408 | 429 | 500..=599 => {
+            return Err(RefreshError::Transient(format!(
+                "Refresh failed: HTTP {}: {}",
+                status, text
+            )))
+        }
+        _ => {
+            return Err(RefreshError::Rejected(format!(
+                "Refresh failed: HTTP {}: {}",
+                status, text
+            )))
+        }
+    }
+    let data: TokenResponse = serde_json::from_str(&text)
+        .map_err(|e| RefreshError::Transient(format!("Failed to parse token response: {}", e)))?;
+
+    Ok(OAuthTokens {
+        access_token: data.access_token,
+        // Servers that don't rotate refresh tokens omit it; keep the old one
+        refresh_token: data.refresh_token.or(Some(refresh_token)),
+        expires_at: now_secs() + data.expires_in.unwrap_or(3600),
+        scopes: data
+            .scope
+            .map(|s| s.split_whitespace().map(String::from).collect())
+            .unwrap_or_else(|| tokens.scopes.clone()),
+    })
+}
+
+/// Whether the tokens should be refreshed, given `jitter` extra seconds of
+/// lead time.
+fn is_due(tokens: &OAuthTokens, jitter: u64) -> bool {
+    now_secs() + REFRESH_MARGIN.as_secs() + jitter >= tokens.expires_at
+}
+
+fn emit_expired(app: &AppHandle, reason: &str) {
+    log::warn!("OAuth session expired: {}", reason);
+    let _ = app.emit(
+        SESSION_EXPIRED_EVENT,
+        SessionExpiredPayload {
+            reason: reason.to_string(),
+        },
+    );
+}
+
+/// Wait up to `timeout` (capped at `MAX_WAIT`) or until tokens change.
+/// Returns true if they changed.
+fn wait(app: &AppHandle, generation: u64, timeout: Duration) -> bool {
+    let state = app.state::<RefreshState>();
+    let guard = state.generation.lock().unwrap();
+    let (guard, _) = state
+        .changed
+        .wait_timeout_while(guard, timeout.min(MAX_WAIT), |g| *g == generation)
+        .unwrap();
+    *guard != generation
+}
+
+fn run(app: AppHandle) {
+    let mut backoff = RETRY_BACKOFF_MIN;
+    let mut jitter = jitter_secs();
+    // Set once the session has expired; cleared when new tokens are saved.
+    let mut expired_generation: Option<u64> = None;
+
+    loop {
+        let generation = *app.state::<RefreshState>().generation.lock().unwrap();
+        if expired_generation == Some(generation) {
+            wait(&app, generation, MAX_WAIT);
+            continue;
+        }
+
+        let (account_id, tokens) = match current_tokens(&app) {
+            Ok(Some(current)) => current,
+            Ok(None) => {
+                wait(&app, generation, MAX_WAIT);
+                continue;
+            }
+            Err(e) => {
+                log::warn!("Token refresher could not read tokens: {}", e);
+                wait(&app, generation, backoff);
+                continue;
+            }
+        };
+
+        let since_refresh = app
+            .state::<RefreshState>()
+            .last_refresh
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|(id, _)| *id == account_id)
+            .map(|(_, t)| t.elapsed());
+        if let Some(since) = since_refresh.filter(|s| *s < RECENT_REFRESH) {
+            wait(&app, generation, RECENT_REFRESH - since);
+            continue;
+        }
+
+        if !is_due(&tokens, jitter) {
+            let lead = REFRESH_MARGIN.as_secs() + jitter;
+            let delay = tokens.expires_at.saturating_sub(now_secs() + lead);
+            wait(&app, generation, Duration::from_secs(delay));
+            continue;
+        }
+
+        match refresh(&app, Some(jitter)) {
+            Ok((_, true)) => {
+                backoff = RETRY_BACKOFF_MIN;
+                jitter = jitter_secs();
+            }
+            // Someone else just refreshed, or the tokens changed meanwhile
+            Ok((_, false)) => {
+                wait(&app, generation, RECENT_REFRESH);
+            }
+            Err(RefreshError::Rejected(reason)) => {
+                emit_expired(&app, &reason);
+                expired_generation = Some(generation);
+            }
+            Err(RefreshError::Transient(reason)) => {
+                log::warn!(
+                    "Token refresh failed, retrying in {:?}: {}",
+                    backoff,
+                    reason
+                );
+                if wait(&app, generation, backoff) {
+                    backoff = RETRY_BACKOFF_MIN;
+                } else {
+                    backoff = (backoff * 2).min(RETRY_BACKOFF_MAX);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Serve one request with `status` and `body`, handing back the request
+    /// body it received.
+    fn mock_token_endpoint(
+        status: u16,
+        body: &'static str,
+    ) -> (String, std::thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}{}", listener.local_addr().unwrap(), TOKEN_PATH);
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut request = vec![0; length];
+            reader.read_exact(&mut request).unwrap();
+            let response = format!(
+                "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        (url, handle)
+    }
+
+    fn tokens(expires_at: u64) -> OAuthTokens {
+        OAuthTokens {
+            access_token: "old-access".to_string(),
+            refresh_token: Some("old-refresh".to_string()),
+            expires_at,
+            scopes: vec!["chat".to_string()],
+        }
+    }
+
+    #[test]
+    fn exchange_trades_the_refresh_token() {
+        let (url, server) = mock_token_endpoint(
+            200,
+            r#"{"access_token":"new-access","refresh_token":"new-refresh","expires_in":600}"#,
+        );
+        let refreshed = exchange(&url, false, &tokens(now_secs())).ok().unwrap();
+        let request: serde_json::Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+        assert_eq!(request["grant_type"], "refresh_token");
+        assert_eq!(request["refresh_token"], "old-refresh");
+        assert_eq!(refreshed.access_token, "new-access");
+        assert_eq!(refreshed.refresh_token.as_deref(), Some("new-refresh"));
+        assert_eq!(refreshed.scopes, vec!["chat".to_string()]);
+        assert!(!is_due(&refreshed, MAX_JITTER_SECS));
+    }
+
+    #[test]
+    fn exchange_keeps_a_refresh_token_that_was_not_rotated() {
+        let (url, server) = mock_token_endpoint(200, r#"{"access_token":"new-access"}"#);
+        let refreshed = exchange(&url, false, &tokens(now_secs())).ok().unwrap();
+        server.join().unwrap();
+        assert_eq!(refreshed.refresh_token.as_deref(), Some("old-refresh"));
+    }
+
+    #[test]
+    fn exchange_tells_rejection_from_outage() {
+        let (url, server) = mock_token_endpoint(400, r#"{"error":"invalid_grant"}"#);
+        let result = exchange(&url, false, &tokens(now_secs()));
+        server.join().unwrap();
+        assert!(matches!(result, Err(RefreshError::Rejected(_))));
+
+        let (url, server) = mock_token_endpoint(503, "{}");
+        let result = exchange(&url, false, &tokens(now_secs()));
+        server.join().unwrap();
+        assert!(matches!(result, Err(RefreshError::Transient(_))));
+    }
+
+    #[test]
+    fn due_depends_on_the_jitter() {
+        // Inside the jitter window: due for the refresher's lead time, not
+        // without it, which is why `refresh` is given the same jitter
+        let tokens = tokens(now_secs() + REFRESH_MARGIN.as_secs() + MAX_JITTER_SECS / 2);
+        assert!(is_due(&tokens, MAX_JITTER_SECS));
+        assert!(!is_due(&tokens, 0));
+    }
+}
@@ -0,0 +1,462 @@
+// Storage backends for OAuth credentials.
+//
+// Tokens used to be written to ~/.pocketpaw/client_oauth.json as plaintext
+// JSON. They now go through a `TokenStore`: the OS keyring where one is
+// available (Keychain, Credential Manager, Secret Service), otherwise a file
+// encrypted with a key derived from a per-install secret. The plaintext file is
+// kept as the legacy backend so existing installs migrate on first read.
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const LEGACY_FILE: &str = "client_oauth.json";
+const ENCRYPTED_FILE: &str = "client_oauth.enc";
+#[cfg(desktop)]
+const KEYRING_OVERFLOW_FILE: &str = "client_oauth.keyring.enc";
+const INSTALL_SECRET_FILE: &str = ".install_secret";
+const KEY_CONTEXT: &[u8] = b"pocketpaw-token-store-v1";
+/// Reads of an empty install secret before giving up (it may be mid-write).
+const SECRET_READ_ATTEMPTS: u32 = 20;
+#[cfg(desktop)]
+const KEYRING_SERVICE: &str = "com.pocketpaw.client";
+#[cfg(desktop)]
+const KEYRING_USER: &str = "oauth";
+/// Stored in the keyring entry in place of a payload too big for it. Never
+/// valid JSON, so it can't be mistaken for a payload.
+#[cfg(desktop)]
+const KEYRING_OVERFLOW_MARKER: &str = "@overflow";
+/// Credential Manager rejects blobs over 2560 bytes; the keyring crate stores
+/// passwords there as UTF-16.
+#[cfg(desktop)]
+const KEYRING_MAX_BLOB_BYTES: usize = 2560;
+
+/// Env var to force a backend: `keyring`, `encrypted` or `legacy`.
+const STORE_OVERRIDE_ENV: &str = "POCKETPAW_TOKEN_STORE";
+
+/// A place to keep the serialized OAuth credentials.
+pub trait TokenStore: Send + Sync {
+    /// Short backend name, used in logs.
+    fn name(&self) -> &'static str;
+
+    /// Load the stored payload, or `None` if nothing is stored.
+    fn load(&self) -> Result<Option<String>, String>;
+
+    /// Replace the stored payload.
+    fn save(&self, data: &str) -> Result<(), String>;
+
+    /// Remove the stored payload. Succeeds if nothing is stored.
+    fn clear(&self) -> Result<(), String>;
+}
+
+fn pocketpaw_dir() -> Result<PathBuf, String> {
+    let home = dirs::home_dir().ok_or("Could not determine home directory")?;
+    Ok(home.join(".pocketpaw"))
+}
+
+/// Atomically replace `path` with `data`, creating the parent directory and
+/// restricting the file to the owner (0600) on Unix. The data is written to a
+/// temporary file and renamed over the target, so a crash mid-write leaves
+/// either the old or the new contents, never a truncated file.
+fn write_private(path: &Path, data: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+
+    let parent = path.parent().ok_or("Invalid token file path")?;
+    fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
+
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = parent.join(tmp_name);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let result = options
+        .open(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp_path, path));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(format!("Failed to write tokens: {}", e));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let perms = fs::Permissions::from_mode(0o600);
+        fs::set_permissions(path, perms)
+            .map_err(|e| format!("Failed to set token file permissions: {}", e))?;
+    }
+
+    Ok(())
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), String> {
+    if path.exists() {
+        fs::remove_file(path).map_err(|e| format!("Failed to delete tokens: {}", e))?;
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------
+// Legacy plaintext file
+// ---------------------------------------------------------------------------
+
+/// The original plaintext `client_oauth.json`. Only read during migration
+/// unless selected explicitly.
+pub struct LegacyFileStore {
+    path: PathBuf,
+}
+
+impl LegacyFileStore {
+    pub fn new() -> Result<Self, String> {
+        Ok(Self {
+            path: pocketpaw_dir()?.join(LEGACY_FILE),
+        })
+    }
+}
+
+impl TokenStore for LegacyFileStore {
+    fn name(&self) -> &'static str {
+        "legacy"
+    }
+
+    fn load(&self) -> Result<Option<String>, String> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        fs::read_to_string(&self.path)
+            .map(Some)
+            .map_err(|e| format!("Failed to read tokens: {}", e))
+    }
+
+    fn save(&self, data: &str) -> Result<(), String> {
+        write_private(&self.path, data.as_bytes())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        remove_if_exists(&self.path)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Encrypted file
+// ---------------------------------------------------------------------------
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedPayload {
+    version: u32,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// `client_oauth.enc`, sealed with ChaCha20-Poly1305. The key is derived from
+/// a random per-install secret in `~/.pocketpaw/.install_secret`, so copying
+/// the token file alone to another machine is not enough to read it.
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    secret_path: PathBuf,
+}
+
+impl EncryptedFileStore {
+    pub fn new() -> Result<Self, String> {
+        Self::with_file(ENCRYPTED_FILE)
+    }
+
+    fn with_file(file_name: &str) -> Result<Self, String> {
+        let dir = pocketpaw_dir()?;
+        Ok(Self {
+            path: dir.join(file_name),
+            secret_path: dir.join(INSTALL_SECRET_FILE),
+        })
+    }
+
+    /// Read the install secret, creating it only if it doesn't exist yet.
+    /// Replacing an existing secret would make every token sealed with it
+    /// unreadable, so any other error is returned instead.
+    fn install_secret(&self) -> Result<Vec<u8>, String> {
+        use std::io::{ErrorKind, Write};
+
+        // An empty file is another thread or process between creating the
+        // secret and writing it
+        for _ in 0..SECRET_READ_ATTEMPTS {
+            match fs::read(&self.secret_path) {
+                Ok(secret) if !secret.is_empty() => return Ok(secret),
+                Ok(_) => std::thread::sleep(Duration::from_millis(50)),
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    let parent = self.secret_path.parent().ok_or("Invalid secret path")?;
+                    fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create dir: {}", e))?;
+                    let mut options = fs::OpenOptions::new();
+                    options.write(true).create_new(true);
+                    #[cfg(unix)]
+                    {
+                        use std::os::unix::fs::OpenOptionsExt;
+                        options.mode(0o600);
+                    }
+                    let mut file = match options.open(&self.secret_path) {
+                        Ok(file) => file,
+                        // Lost the race; read what the winner wrote
+                        Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                        Err(e) => return Err(format!("Failed to create install secret: {}", e)),
+                    };
+                    let secret = ChaCha20Poly1305::generate_key(&mut OsRng).to_vec();
+                    file.write_all(&secret)
+                        .and_then(|_| file.sync_all())
+                        .map_err(|e| format!("Failed to write install secret: {}", e))?;
+                    return Ok(secret);
+                }
+                Err(e) => return Err(format!("Failed to read install secret: {}", e)),
+            }
+        }
+        Err("Failed to read install secret: the file is empty".to_string())
+    }
+
+    /// Derive the encryption key, creating the install secret on first use.
+    fn cipher(&self) -> Result<ChaCha20Poly1305, String> {
+        let secret = self.install_secret()?;
+        let mut hasher = Sha256::new();
+        hasher.update(KEY_CONTEXT);
+        hasher.update(&secret);
+        let key = hasher.finalize();
+        Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+    }
+}
+
+impl TokenStore for EncryptedFileStore {
+    fn name(&self) -> &'static str {
+        "encrypted"
+    }
+
+    fn load(&self) -> Result<Option<String>, String> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let data =
+            fs::read_to_string(&self.path).map_err(|e| format!("Failed to read tokens: {}", e))?;
+        let payload: EncryptedPayload =
+            serde_json::from_str(&data).map_err(|e| format!("Failed to parse tokens: {}", e))?;
+        let nonce = B64
+            .decode(&payload.nonce)
+            .map_err(|e| format!("Failed to decode nonce: {}", e))?;
+        let ciphertext = B64
+            .decode(&payload.ciphertext)
+            .map_err(|e| format!("Failed to decode tokens: {}", e))?;
+        if nonce.len() != 12 {
+            return Err("Failed to decrypt tokens: invalid nonce".to_string());
+        }
+        let plaintext = self
+            .cipher()?
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| "Failed to decrypt tokens: key mismatch or corrupt file".to_string())?;
+        String::from_utf8(plaintext)
+            .map(Some)
+            .map_err(|e| format!("Failed to decode tokens: {}", e))
+    }
+
+    fn save(&self, data: &str) -> Result<(), String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()?
+            .encrypt(&nonce, data.as_bytes())
+            .map_err(|_| "Failed to encrypt tokens".to_string())?;
+        let payload = EncryptedPayload {
+            version: 1,
+            nonce: B64.encode(nonce),
+            ciphertext: B64.encode(ciphertext),
+        };
+        let json =
+            serde_json::to_string(&payload).map_err(|e| format!("Failed to serialize: {}", e))?;
+        write_private(&self.path, json.as_bytes())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        remove_if_exists(&self.path)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// OS keyring
+// ---------------------------------------------------------------------------
+
+/// The platform credential store: Keychain on macOS, Credential Manager on
+/// Windows and the Secret Service (GNOME Keyring, KWallet) on Linux. A
+/// payload too big for the keyring (several accounts on Windows) goes to its
+/// own encrypted file instead, with a marker left in the keyring entry.
+#[cfg(desktop)]
+pub struct KeyringStore {
+    entry: keyring::Entry,
+    overflow: EncryptedFileStore,
+}
+
+#[cfg(desktop)]
+impl KeyringStore {
+    pub fn new() -> Result<Self, String> {
+        let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+            .map_err(|e| format!("Failed to open keyring: {}", e))?;
+        let overflow = EncryptedFileStore::with_file(KEYRING_OVERFLOW_FILE)?;
+        Ok(Self { entry, overflow })
+    }
+
+    fn fits(data: &str) -> bool {
+        !cfg!(windows) || data.encode_utf16().count() * 2 <= KEYRING_MAX_BLOB_BYTES
+    }
+
+    /// Whether the keyring can actually be reached. On Linux there may be no
+    /// Secret Service running (e.g. headless or minimal window managers).
+    pub fn is_available(&self) -> bool {
+        matches!(
+            self.entry.get_password(),
+            Ok(_) | Err(keyring::Error::NoEntry)
+        )
+    }
+}
+
+#[cfg(desktop)]
+impl TokenStore for KeyringStore {
+    fn name(&self) -> &'static str {
+        "keyring"
+    }
+
+    fn load(&self) -> Result<Option<String>, String> {
+        match self.entry.get_password() {
+            Ok(data) if data == KEYRING_OVERFLOW_MARKER => self.overflow.load(),
+            Ok(data) => Ok(Some(data)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(format!("Failed to read tokens from keyring: {}", e)),
+        }
+    }
+
+    fn save(&self, data: &str) -> Result<(), String> {
+        let fits = Self::fits(data);
+        if !fits {
+            self.overflow.save(data)?;
+        }
+        self.entry
+            .set_password(if fits { data } else { KEYRING_OVERFLOW_MARKER })
+            .map_err(|e| format!("Failed to write tokens to keyring: {}", e))?;
+        if fits {
+            if let Err(e) = self.overflow.clear() {
+                log::warn!("Failed to remove keyring overflow file: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), String> {
+        match self.entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(format!("Failed to delete tokens from keyring: {}", e)),
+        }
+        self.overflow.clear()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Selection and migration
+// ---------------------------------------------------------------------------
+
+static ACTIVE_STORE: OnceLock<Box<dyn TokenStore>> = OnceLock::new();
+
+fn select_store() -> Result<Box<dyn TokenStore>, String> {
+    let requested = std::env::var(STORE_OVERRIDE_ENV).unwrap_or_default();
+    match requested.as_str() {
+        "legacy" => return Ok(Box::new(LegacyFileStore::new()?)),
+        "encrypted" => return Ok(Box::new(EncryptedFileStore::new()?)),
+        _ => {}
+    }
+
+    #[cfg(desktop)]
+    match KeyringStore::new() {
+        Ok(store) if store.is_available() => return Ok(Box::new(store)),
+        Ok(_) => log::info!("OS keyring unavailable, using encrypted token file"),
+        Err(e) => log::info!("{}, using encrypted token file", e),
+    }
+
+    Ok(Box::new(EncryptedFileStore::new()?))
+}
+
+/// The backend tokens are read from and written to. Chosen once per run.
+pub fn active_store() -> Result<&'static dyn TokenStore, String> {
+    if let Some(store) = ACTIVE_STORE.get() {
+        return Ok(store.as_ref());
+    }
+    let store = select_store()?;
+    log::info!("Using {} token store", store.name());
+    Ok(ACTIVE_STORE.get_or_init(|| store).as_ref())
+}
+
+/// Every backend other than `active`, in the order they are checked for
+/// credentials to migrate.
+fn other_stores(active: &dyn TokenStore) -> Vec<Box<dyn TokenStore>> {
+    let mut stores: Vec<Box<dyn TokenStore>> = Vec::new();
+    if let Ok(s) = EncryptedFileStore::new() {
+        stores.push(Box::new(s));
+    }
+    if let Ok(s) = LegacyFileStore::new() {
+        stores.push(Box::new(s));
+    }
+    stores.retain(|s| s.name() != active.name());
+    stores
+}
+
+/// Load the payload from the active store. If it is empty, credentials left
+/// in another backend (the plaintext file, or the encrypted file after the
+/// keyring became available) are moved into the active store.
+pub fn load() -> Result<Option<String>, String> {
+    let store = active_store()?;
+    if let Some(data) = store.load()? {
+        return Ok(Some(data));
+    }
+
+    for old in other_stores(store) {
+        let data = match old.load() {
+            Ok(Some(data)) => data,
+            Ok(None) => continue,
+            Err(e) => {
+                log::warn!("Skipping {} token migration: {}", old.name(), e);
+                continue;
+            }
+        };
+        store.save(&data)?;
+        if let Err(e) = old.clear() {
+            log::warn!("Failed to remove migrated {} tokens: {}", old.name(), e);
+        }
+        log::info!(
+            "Migrated OAuth tokens from {} to {} store",
+            old.name(),
+            store.name()
+        );
+        return Ok(Some(data));
+    }
+
+    Ok(None)
+}
+
+/// Save the payload to the active store.
+pub fn save(data: &str) -> Result<(), String> {
+    active_store()?.save(data)
+}
+
+/// Remove credentials from every backend, so a sign-out can't leave a copy
+/// behind in a store that is no longer active.
+pub fn clear() -> Result<(), String> {
+    let store = active_store()?;
+    store.clear()?;
+    for old in other_stores(store) {
+        old.clear()?;
+    }
+    Ok(())
+}
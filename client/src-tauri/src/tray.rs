@@ -1,42 +1,497 @@
+use crate::commands;
+use crate::oauth;
 use crate::quick_ask;
 use crate::side_panel;
+use crate::window_attach::{self, AttachMode};
+use crate::ws_bridge::{self, ConnectionState};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
 use tauri::{
     image::Image,
-    menu::{Menu, MenuItem, PredefinedMenuItem},
+    menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
-    AppHandle, Emitter, Manager, Wry,
+    AppHandle, Emitter, Listener, Manager, Wry,
 };
 
-pub fn setup_tray(app: &AppHandle) -> tauri::Result<()> {
-    let open_i = MenuItem::with_id(app, "open", "Open PocketPaw", true, None::<&str>)?;
-    let quick_ask_i = MenuItem::with_id(app, "quick_ask", "Quick Ask...", true, None::<&str>)?;
-    let side_panel_i = MenuItem::with_id(app, "side_panel", "Side Panel", true, None::<&str>)?;
-    let settings_i = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
-    let quit_i = MenuItem::with_id(app, "quit", "Quit PocketPaw", true, None::<&str>)?;
+const TRAY_ID: &str = "main-tray";
+const RECENT_SESSIONS: usize = 5;
+const SESSION_POLL_INTERVAL: Duration = Duration::from_secs(30);
+const SESSION_PREFIX: &str = "session:";
+const STOP_TASK_PREFIX: &str = "task-stop:";
+
+/// Status shown by the tray icon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrayStatus {
+    Idle,
+    Working,
+    Notification,
+    Error,
+}
+
+impl TrayStatus {
+    fn icon_bytes(&self) -> &'static [u8] {
+        match self {
+            TrayStatus::Idle => include_bytes!("../icons/tray/tray-idle.png"),
+            TrayStatus::Working => include_bytes!("../icons/tray/tray-working.png"),
+            TrayStatus::Notification => include_bytes!("../icons/tray/tray-notification.png"),
+            TrayStatus::Error => include_bytes!("../icons/tray/tray-error.png"),
+        }
+    }
+}
+
+/// Payload emitted as `"tray-state-changed"` Tauri event.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrayStatePayload {
+    pub status: TrayStatus,
+    pub text: String,
+}
+
+/// A chat session listed under "Recent Sessions".
+#[derive(Debug, Clone)]
+pub struct RecentSession {
+    pub id: String,
+    pub title: String,
+}
+
+/// Managed state for the tray icon. The status is derived from backend
+/// connectivity, running agent tasks and unread notifications, unless the
+/// frontend has set an explicit override via `set_tray_state`.
+pub struct TrayState {
+    pub backend_up: Mutex<Option<bool>>,
+    /// Running task id → title.
+    pub running_tasks: Mutex<HashMap<String, String>>,
+    pub recent_sessions: Mutex<Vec<RecentSession>>,
+    pub unread: Mutex<u32>,
+    pub manual: Mutex<Option<(TrayStatus, Option<String>)>>,
+    current: Mutex<Option<(TrayStatus, String)>>,
+}
+
+impl Default for TrayState {
+    fn default() -> Self {
+        Self {
+            backend_up: Mutex::new(None),
+            running_tasks: Mutex::new(HashMap::new()),
+            recent_sessions: Mutex::new(Vec::new()),
+            unread: Mutex::new(0),
+            manual: Mutex::new(None),
+            current: Mutex::new(None),
+        }
+    }
+}
+
+/// Set (or clear, with `status: None`) an explicit tray status, e.g. while
+/// the chat agent is streaming a response. `text` overrides the tooltip.
+#[tauri::command]
+pub fn set_tray_state(
+    app: AppHandle,
+    status: Option<TrayStatus>,
+    text: Option<String>,
+) -> Result<(), String> {
+    let state = app.state::<TrayState>();
+    *state.manual.lock().unwrap() = status.map(|s| (s, text));
+    refresh_tray(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_tray_state(app: AppHandle) -> TrayStatePayload {
+    let (status, text) = compute_status(&app);
+    TrayStatePayload { status, text }
+}
+
+/// Derive the tray status: backend down → error, agent running → working,
+/// unread notifications → notification, otherwise idle.
+fn compute_status(app: &AppHandle) -> (TrayStatus, String) {
+    let state = app.state::<TrayState>();
+    if let Some((status, text)) = state.manual.lock().unwrap().clone() {
+        let text = text.unwrap_or_else(|| default_text(status).to_string());
+        return (status, text);
+    }
+
+    if *state.backend_up.lock().unwrap() == Some(false) {
+        return (TrayStatus::Error, "Backend offline".to_string());
+    }
+    let running = state.running_tasks.lock().unwrap().len();
+    if running > 0 {
+        let text = if running == 1 {
+            "Working on 1 task".to_string()
+        } else {
+            format!("Working on {} tasks", running)
+        };
+        return (TrayStatus::Working, text);
+    }
+    let unread = *state.unread.lock().unwrap();
+    if unread > 0 {
+        let text = if unread == 1 {
+            "1 new notification".to_string()
+        } else {
+            format!("{} new notifications", unread)
+        };
+        return (TrayStatus::Notification, text);
+    }
+    (TrayStatus::Idle, default_text(TrayStatus::Idle).to_string())
+}
+
+fn default_text(status: TrayStatus) -> &'static str {
+    match status {
+        TrayStatus::Idle => "Idle",
+        TrayStatus::Working => "Working",
+        TrayStatus::Notification => "New notifications",
+        TrayStatus::Error => "Error",
+    }
+}
+
+/// Recompute the status and update the icon and tooltip if it changed.
+pub fn refresh_tray(app: &AppHandle) {
+    let (status, text) = compute_status(app);
+    let state = app.state::<TrayState>();
+    {
+        let mut current = state.current.lock().unwrap();
+        if current.as_ref() == Some(&(status, text.clone())) {
+            return;
+        }
+        *current = Some((status, text.clone()));
+    }
+
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        match Image::from_bytes(status.icon_bytes()) {
+            Ok(icon) => {
+                let _ = tray.set_icon(Some(icon));
+            }
+            Err(e) => log::warn!("Failed to load tray icon: {}", e),
+        }
+        let _ = tray.set_tooltip(Some(format!("PocketPaw — {}", text)));
+    }
+    let _ = app.emit("tray-state-changed", TrayStatePayload { status, text });
+}
+
+/// Count a notification raised while the user was away.
+pub fn mark_unread(app: &AppHandle) {
+    *app.state::<TrayState>().unread.lock().unwrap() += 1;
+    refresh_tray(app);
+}
+
+/// Clear unread notifications (called when the main window gains focus).
+pub fn clear_unread(app: &AppHandle) {
+    *app.state::<TrayState>().unread.lock().unwrap() = 0;
+    refresh_tray(app);
+}
+
+/// Track backend connectivity from the WebSocket bridge.
+fn on_connection_state(app: &AppHandle, connection: ConnectionState) {
+    let up = match connection {
+        ConnectionState::Connected => true,
+        ConnectionState::Disconnected => false,
+        ConnectionState::Connecting => return,
+    };
+    let state = app.state::<TrayState>();
+    *state.backend_up.lock().unwrap() = Some(up);
+    if !up {
+        // Task events are lost while disconnected; don't show stale work
+        state.running_tasks.lock().unwrap().clear();
+    }
+    refresh_tray(app);
+    rebuild_menu(app);
+    if up {
+        let app_handle = app.clone();
+        std::thread::spawn(move || refresh_sessions(&app_handle));
+    }
+}
+
+/// Track running agent tasks from backend events.
+fn on_backend_event(app: &AppHandle, event: &serde_json::Value) {
+    let task_id = event
+        .get("task_id")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let state = app.state::<TrayState>();
+    match event.get("type").and_then(|v| v.as_str()) {
+        Some("mc_task_started") => {
+            let title = event
+                .get("task_title")
+                .and_then(|v| v.as_str())
+                .unwrap_or("Untitled task")
+                .to_string();
+            state.running_tasks.lock().unwrap().insert(task_id, title);
+        }
+        Some("mc_task_completed") => {
+            state.running_tasks.lock().unwrap().remove(&task_id);
+        }
+        _ => return,
+    }
+    refresh_tray(app);
+    rebuild_menu(app);
+}
+
+/// Build the tray menu from the current sessions, running tasks, backend
+/// status and attach mode.
+fn build_menu(app: &AppHandle) -> tauri::Result<Menu<Wry>> {
+    let state = app.state::<TrayState>();
+    let menu = Menu::new(app)?;
 
-    let sep1 = PredefinedMenuItem::separator(app)?;
-    let sep2 = PredefinedMenuItem::separator(app)?;
-    let sep3 = PredefinedMenuItem::separator(app)?;
+    menu.append(&menu_item(app, "open", "Open PocketPaw", true)?)?;
+    menu.append(&PredefinedMenuItem::separator(app)?)?;
 
-    let menu = Menu::with_items(
-        app,
-        &[&open_i, &sep1, &quick_ask_i, &side_panel_i, &sep2, &settings_i, &sep3, &quit_i],
-    )?;
+    // Recent sessions
+    let sessions = state.recent_sessions.lock().unwrap().clone();
+    let sessions_menu = Submenu::with_id(app, "sessions", "Recent Sessions", true)?;
+    if sessions.is_empty() {
+        sessions_menu.append(&menu_item(
+            app,
+            "sessions_empty",
+            "No recent sessions",
+            false,
+        )?)?;
+    }
+    for session in &sessions {
+        sessions_menu.append(&menu_item(
+            app,
+            format!("{}{}", SESSION_PREFIX, session.id),
+            menu_label(&session.title),
+            true,
+        )?)?;
+    }
+    menu.append(&sessions_menu)?;
 
-    // Use the 32x32 app icon as the tray icon
-    let icon = Image::from_bytes(include_bytes!("../icons/32x32.png"))?;
+    // Running tasks, each with a stop action
+    let mut tasks: Vec<(String, String)> = state
+        .running_tasks
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(id, title)| (id.clone(), title.clone()))
+        .collect();
+    tasks.sort_by(|a, b| a.1.cmp(&b.1));
+    let tasks_label = if tasks.is_empty() {
+        "Running Tasks".to_string()
+    } else {
+        format!("Running Tasks ({})", tasks.len())
+    };
+    let tasks_menu = Submenu::with_id(app, "tasks", tasks_label, true)?;
+    if tasks.is_empty() {
+        tasks_menu.append(&menu_item(app, "tasks_empty", "No running tasks", false)?)?;
+    }
+    for (id, title) in &tasks {
+        tasks_menu.append(&menu_item(
+            app,
+            format!("{}{}", STOP_TASK_PREFIX, id),
+            format!("Stop “{}”", menu_label(title)),
+            true,
+        )?)?;
+    }
+    menu.append(&tasks_menu)?;
+    menu.append(&PredefinedMenuItem::separator(app)?)?;
 
-    let _tray = TrayIconBuilder::with_id("main-tray")
+    menu.append(&menu_item(app, "quick_ask", "Quick Ask...", true)?)?;
+    menu.append(&menu_item(app, "side_panel", "Side Panel", true)?)?;
+
+    // Side panel attach mode
+    let mode = window_attach::get_attach_mode(app.clone());
+    let attach_menu = Submenu::with_id(app, "attach", "Attach Mode", true)?;
+    for (id, label, item_mode) in [
+        ("attach:auto", "Auto", AttachMode::Auto),
+        ("attach:docked", "Docked", AttachMode::Docked),
+        ("attach:disabled", "Disabled", AttachMode::Disabled),
+    ] {
+        attach_menu.append(&CheckMenuItem::with_id(
+            app,
+            id,
+            label,
+            true,
+            mode == item_mode,
+            None::<&str>,
+        )?)?;
+    }
+    menu.append(&attach_menu)?;
+    menu.append(&PredefinedMenuItem::separator(app)?)?;
+
+    // Backend controls
+    let backend_up = *state.backend_up.lock().unwrap();
+    let status_line = match backend_up {
+        Some(true) => "Backend: running",
+        Some(false) => "Backend: offline",
+        None => "Backend: connecting...",
+    };
+    let running = backend_up == Some(true);
+    // Only a backend this app started can be stopped or restarted from here
+    let managed = running && commands::backend_is_managed(app);
+    let backend_menu = Submenu::with_id(app, "backend", "Backend", true)?;
+    backend_menu.append(&menu_item(app, "backend_status", status_line, false)?)?;
+    backend_menu.append(&PredefinedMenuItem::separator(app)?)?;
+    backend_menu.append(&menu_item(app, "backend_start", "Start", !running)?)?;
+    backend_menu.append(&menu_item(app, "backend_stop", "Stop", managed)?)?;
+    backend_menu.append(&menu_item(app, "backend_restart", "Restart", managed)?)?;
+    menu.append(&backend_menu)?;
+    menu.append(&PredefinedMenuItem::separator(app)?)?;
+
+    menu.append(&menu_item(app, "settings", "Settings", true)?)?;
+    menu.append(&PredefinedMenuItem::separator(app)?)?;
+    menu.append(&menu_item(app, "quit", "Quit PocketPaw", true)?)?;
+
+    Ok(menu)
+}
+
+fn menu_item(
+    app: &AppHandle,
+    id: impl Into<tauri::menu::MenuId>,
+    text: impl AsRef<str>,
+    enabled: bool,
+) -> tauri::Result<MenuItem<Wry>> {
+    MenuItem::with_id(app, id, text, enabled, None::<&str>)
+}
+
+/// Truncate long titles so the tray menu stays narrow.
+fn menu_label(title: &str) -> String {
+    const MAX_CHARS: usize = 40;
+    let title = if title.trim().is_empty() {
+        "Untitled"
+    } else {
+        title.trim()
+    };
+    if title.chars().count() > MAX_CHARS {
+        let truncated: String = title.chars().take(MAX_CHARS - 1).collect();
+        format!("{}…", truncated)
+    } else {
+        title.to_string()
+    }
+}
+
+/// Rebuild the tray menu from current state.
+pub fn rebuild_menu(app: &AppHandle) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    match build_menu(app) {
+        Ok(menu) => {
+            let _ = tray.set_menu(Some(menu));
+        }
+        Err(e) => log::warn!("Failed to rebuild tray menu: {}", e),
+    }
+}
+
+/// Fetch the most recent sessions from the backend and rebuild the menu.
+fn refresh_sessions(app: &AppHandle) {
+    let url = format!(
+        "http://127.0.0.1:{}/api/v1/sessions?limit={}",
+        ws_bridge::port(app),
+        RECENT_SESSIONS
+    );
+    let sessions = match oauth::proxy_get(app.clone(), url) {
+        Ok(body) => parse_sessions(&body),
+        Err(e) => {
+            log::debug!("Failed to fetch recent sessions: {}", e);
+            return;
+        }
+    };
+    *app.state::<TrayState>().recent_sessions.lock().unwrap() = sessions;
+    rebuild_menu(app);
+}
+
+fn parse_sessions(body: &str) -> Vec<RecentSession> {
+    let value: serde_json::Value = match serde_json::from_str(body) {
+        Ok(v) => v,
+        Err(_) => return Vec::new(),
+    };
+    value
+        .get("sessions")
+        .and_then(|s| s.as_array())
+        .map(|sessions| {
+            sessions
+                .iter()
+                .take(RECENT_SESSIONS)
+                .filter_map(|s| {
+                    Some(RecentSession {
+                        id: s.get("id")?.as_str()?.to_string(),
+                        title: s
+                            .get("title")
+                            .and_then(|t| t.as_str())
+                            .unwrap_or("Untitled")
+                            .to_string(),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Poll recent sessions in the background while the backend is reachable.
+fn start_session_poll(app: &AppHandle) {
+    let app_handle = app.clone();
+    std::thread::spawn(move || loop {
+        if ws_bridge::connection_state(&app_handle) == ConnectionState::Connected {
+            refresh_sessions(&app_handle);
+        }
+        std::thread::sleep(SESSION_POLL_INTERVAL);
+    });
+}
+
+fn show_main(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+/// Run a blocking backend action off the event loop, then refresh the menu.
+fn run_backend_action<F>(app: &AppHandle, label: &'static str, action: F)
+where
+    F: FnOnce(AppHandle) -> Result<bool, String> + Send + 'static,
+{
+    let app_handle = app.clone();
+    std::thread::spawn(move || {
+        match action(app_handle.clone()) {
+            Ok(true) => {}
+            Ok(false) => log::warn!("Backend {}: nothing to do", label),
+            Err(e) => log::warn!("Backend {} failed: {}", label, e),
+        }
+        // Reconnect the bridge right away instead of waiting out its backoff
+        let _ = ws_bridge::ws_bridge_connect(app_handle.clone(), None);
+        rebuild_menu(&app_handle);
+    });
+}
+
+fn handle_dynamic_menu_event(app: &AppHandle, id: &str) {
+    if let Some(session_id) = id.strip_prefix(SESSION_PREFIX) {
+        show_main(app);
+        let _ = app.emit("tray-open-session", session_id);
+        let _ = app.emit("tray-navigate", "/chat");
+    } else if let Some(task_id) = id.strip_prefix(STOP_TASK_PREFIX) {
+        let url = format!(
+            "http://127.0.0.1:{}/api/mission-control/tasks/{}/stop",
+            ws_bridge::port(app),
+            task_id
+        );
+        let app_handle = app.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = oauth::proxy_post(app_handle, url, "{}".to_string()) {
+                log::warn!("Failed to stop task: {}", e);
+            }
+        });
+    } else if let Some(mode) = id.strip_prefix("attach:") {
+        let mode = match mode {
+            "auto" => AttachMode::Auto,
+            "docked" => AttachMode::Docked,
+            _ => AttachMode::Disabled,
+        };
+        let _ = window_attach::set_attach_mode(app.clone(), mode);
+    }
+}
+
+pub fn setup_tray(app: &AppHandle) -> tauri::Result<()> {
+    let menu = build_menu(app)?;
+
+    let icon = Image::from_bytes(TrayStatus::Idle.icon_bytes())?;
+
+    let _tray = TrayIconBuilder::with_id(TRAY_ID)
         .icon(icon)
         .tooltip("PocketPaw")
         .menu(&menu)
         .on_menu_event(move |app: &AppHandle<Wry>, event| match event.id.as_ref() {
-            "open" => {
-                if let Some(window) = app.get_webview_window("main") {
-                    let _ = window.show();
-                    let _ = window.set_focus();
-                }
-            }
+            "open" => show_main(app),
             "quick_ask" => {
                 let _ = quick_ask::show_quick_ask(app.clone());
             }
@@ -50,10 +505,19 @@ pub fn setup_tray(app: &AppHandle) -> tauri::Result<()> {
                     let _ = app.emit("tray-navigate", "/settings");
                 }
             }
+            "backend_start" => run_backend_action(app, "start", |app| {
+                let port = ws_bridge::port(&app);
+                commands::start_pocketpaw_backend(app, port)
+            }),
+            "backend_stop" => run_backend_action(app, "stop", commands::stop_pocketpaw_backend),
+            "backend_restart" => run_backend_action(app, "restart", |app| {
+                let port = ws_bridge::port(&app);
+                commands::restart_pocketpaw_backend(app, port)
+            }),
             "quit" => {
                 app.exit(0);
             }
-            _ => {}
+            other => handle_dynamic_menu_event(app, other),
         })
         .on_tray_icon_event(|tray: &tauri::tray::TrayIcon<Wry>, event| {
             if let TrayIconEvent::Click {
@@ -75,5 +539,13 @@ pub fn setup_tray(app: &AppHandle) -> tauri::Result<()> {
         })
         .build(app)?;
 
+    ws_bridge::subscribe_state(app, on_connection_state);
+    ws_bridge::subscribe(app, on_backend_event);
+    start_session_poll(app);
+
+    // Keep the attach mode checkmarks in sync when the frontend changes it
+    let app_handle = app.clone();
+    app.listen("attach-changed", move |_| rebuild_menu(&app_handle));
+
     Ok(())
 }
@@ -0,0 +1,414 @@
+// Shared native WebSocket bridge to the backend's /api/v1/ws endpoint.
+//
+// One Rust-side client owns the connection for the whole app: it reconnects
+// with exponential backoff, sends a heartbeat, queues actions while offline,
+// and fans every backend event out to the webview windows via `emit_to`.
+// Because it lives outside the webviews, events keep flowing while every
+// window is hidden or throttled, and the backend sees a single client session.
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+use tungstenite::client::IntoClientRequest;
+use tungstenite::{Message, WebSocket};
+
+use crate::{backend_transport, commands};
+
+/// Event carrying a backend WebSocket message (parsed JSON) to the windows.
+pub const WS_EVENT: &str = "ws-event";
+/// Event carrying the bridge's `ConnectionState` whenever it changes.
+pub const WS_STATE_EVENT: &str = "ws-state";
+
+const BRIDGE_WINDOWS: &[&str] = &["main", "sidepanel", "quickask"];
+const DEFAULT_PORT: u16 = 8888;
+const RECONNECT_BASE_MS: u64 = 1000;
+const RECONNECT_MAX_MS: u64 = 30_000;
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const MAX_PENDING: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+type EventHandler = Box<dyn Fn(&AppHandle, &serde_json::Value) + Send + Sync>;
+type StateHandler = Box<dyn Fn(&AppHandle, ConnectionState) + Send + Sync>;
+
+/// Managed state for the WebSocket bridge.
+pub struct WsBridgeState {
+    port: Mutex<u16>,
+    state: Mutex<ConnectionState>,
+    pending: Mutex<VecDeque<String>>,
+    handlers: Mutex<Vec<EventHandler>>,
+    state_handlers: Mutex<Vec<StateHandler>>,
+    running: AtomicBool,
+    stop: Arc<AtomicBool>,
+    reconnect_now: AtomicBool,
+}
+
+impl Default for WsBridgeState {
+    fn default() -> Self {
+        Self {
+            port: Mutex::new(DEFAULT_PORT),
+            state: Mutex::new(ConnectionState::Disconnected),
+            pending: Mutex::new(VecDeque::new()),
+            handlers: Mutex::new(Vec::new()),
+            state_handlers: Mutex::new(Vec::new()),
+            running: AtomicBool::new(false),
+            stop: Arc::new(AtomicBool::new(false)),
+            reconnect_now: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Register a Rust-side consumer of backend events (notifications, tray, ...).
+/// Handlers run on the bridge thread and must not block.
+pub fn subscribe<F>(app: &AppHandle, handler: F)
+where
+    F: Fn(&AppHandle, &serde_json::Value) + Send + Sync + 'static,
+{
+    let state = app.state::<WsBridgeState>();
+    state.handlers.lock().unwrap().push(Box::new(handler));
+}
+
+/// Register a Rust-side consumer of connection state changes.
+pub fn subscribe_state<F>(app: &AppHandle, handler: F)
+where
+    F: Fn(&AppHandle, ConnectionState) + Send + Sync + 'static,
+{
+    let state = app.state::<WsBridgeState>();
+    state.state_handlers.lock().unwrap().push(Box::new(handler));
+}
+
+/// Current connection state of the bridge.
+pub fn connection_state(app: &AppHandle) -> ConnectionState {
+    let state = app.state::<WsBridgeState>();
+    let current = *state.state.lock().unwrap();
+    current
+}
+
+/// TCP port of the backend the bridge connects to.
+pub fn port(app: &AppHandle) -> u16 {
+    let state = app.state::<WsBridgeState>();
+    let port = *state.port.lock().unwrap();
+    port
+}
+
+/// Start the bridge thread if it is not already running.
+pub fn start(app: &AppHandle) {
+    let state = app.state::<WsBridgeState>();
+    state.stop.store(false, Ordering::SeqCst);
+    if state.running.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let app_handle = app.clone();
+    std::thread::spawn(move || {
+        run_bridge(&app_handle);
+        app_handle
+            .state::<WsBridgeState>()
+            .running
+            .store(false, Ordering::SeqCst);
+    });
+}
+
+// ---------------------------------------------------------------------------
+// Tauri commands
+// ---------------------------------------------------------------------------
+
+/// Connect (or reconnect) the bridge, optionally to a different TCP port.
+#[tauri::command]
+pub fn ws_bridge_connect(app: AppHandle, port: Option<u16>) -> Result<(), String> {
+    let state = app.state::<WsBridgeState>();
+    if let Some(p) = port {
+        *state.port.lock().unwrap() = p;
+    }
+    state.reconnect_now.store(true, Ordering::SeqCst);
+    start(&app);
+    Ok(())
+}
+
+/// Close the bridge connection and stop reconnecting. Pending actions are dropped.
+#[tauri::command]
+pub fn ws_bridge_disconnect(app: AppHandle) -> Result<(), String> {
+    let state = app.state::<WsBridgeState>();
+    state.stop.store(true, Ordering::SeqCst);
+    state.pending.lock().unwrap().clear();
+    Ok(())
+}
+
+/// Send an action (e.g. `{"action": "ping"}`) to the backend. While the bridge
+/// is offline the action is queued (max 50) and flushed on reconnect.
+#[tauri::command]
+pub fn ws_send(app: AppHandle, action: serde_json::Value) -> Result<(), String> {
+    let text = serde_json::to_string(&action).map_err(|e| format!("Failed to serialize: {}", e))?;
+    let state = app.state::<WsBridgeState>();
+    let mut pending = state.pending.lock().unwrap();
+    if pending.len() >= MAX_PENDING {
+        return Err("WebSocket send queue is full".to_string());
+    }
+    pending.push_back(text);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn ws_bridge_state(app: AppHandle) -> ConnectionState {
+    connection_state(&app)
+}
+
+// ---------------------------------------------------------------------------
+// Connection loop
+// ---------------------------------------------------------------------------
+
+type BridgeSocket = WebSocket<BridgeStream>;
+
+fn set_state(app: &AppHandle, new_state: ConnectionState) {
+    let state = app.state::<WsBridgeState>();
+    {
+        let mut current = state.state.lock().unwrap();
+        if *current == new_state {
+            return;
+        }
+        *current = new_state;
+    }
+    for label in BRIDGE_WINDOWS {
+        let _ = app.emit_to(*label, WS_STATE_EVENT, new_state);
+    }
+    let handlers = state.state_handlers.lock().unwrap();
+    for handler in handlers.iter() {
+        handler(app, new_state);
+    }
+}
+
+fn run_bridge(app: &AppHandle) {
+    let stop = app.state::<WsBridgeState>().stop.clone();
+    let mut attempt: u32 = 0;
+
+    while !stop.load(Ordering::SeqCst) {
+        app.state::<WsBridgeState>()
+            .reconnect_now
+            .store(false, Ordering::SeqCst);
+        set_state(app, ConnectionState::Connecting);
+
+        match connect(app) {
+            Ok(mut socket) => {
+                attempt = 0;
+                set_state(app, ConnectionState::Connected);
+                if let Err(e) = run_session(app, &mut socket, &stop) {
+                    log::debug!("WebSocket bridge session ended: {}", e);
+                }
+                let _ = socket.close(None);
+            }
+            Err(e) => log::debug!("WebSocket bridge connect failed: {}", e),
+        }
+
+        set_state(app, ConnectionState::Disconnected);
+
+        // Exponential backoff, interruptible by stop or an explicit reconnect
+        let delay = RECONNECT_BASE_MS
+            .saturating_mul(1u64 << attempt.min(16))
+            .min(RECONNECT_MAX_MS);
+        attempt = attempt.saturating_add(1);
+        let deadline = Instant::now() + Duration::from_millis(delay);
+        while Instant::now() < deadline {
+            let state = app.state::<WsBridgeState>();
+            if stop.load(Ordering::SeqCst) || state.reconnect_now.load(Ordering::SeqCst) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+/// Open the WebSocket over the backend's Unix socket when it is live,
+/// otherwise over TCP. The access token goes in the Authorization header
+/// rather than the query string.
+fn connect(app: &AppHandle) -> Result<BridgeSocket, String> {
+    let port = *app.state::<WsBridgeState>().port.lock().unwrap();
+
+    let mut request = format!("ws://localhost:{}/api/v1/ws", port)
+        .into_client_request()
+        .map_err(|e| format!("Invalid WebSocket request: {}", e))?;
+    if let Ok(token) = commands::read_access_token() {
+        if !token.is_empty() {
+            let value = format!("Bearer {}", token)
+                .parse()
+                .map_err(|e| format!("Invalid token header: {}", e))?;
+            request.headers_mut().insert("Authorization", value);
+        }
+    }
+
+    let stream = open_stream(port)?;
+    stream
+        .set_read_timeout(CONNECTION_TIMEOUT)
+        .map_err(|e| e.to_string())?;
+    let (socket, _) = tungstenite::client(request, stream)
+        .map_err(|e| format!("WebSocket handshake failed: {}", e))?;
+
+    // Short read timeout from here on so the session loop can poll the send
+    // queue and heartbeat between incoming messages.
+    socket
+        .get_ref()
+        .set_read_timeout(READ_POLL_INTERVAL)
+        .map_err(|e| e.to_string())?;
+    Ok(socket)
+}
+
+fn open_stream(port: u16) -> Result<BridgeStream, String> {
+    #[cfg(unix)]
+    {
+        if backend_transport::socket_alive() {
+            let path = backend_transport::socket_path()?;
+            let stream = std::os::unix::net::UnixStream::connect(&path)
+                .map_err(|e| format!("Failed to connect to backend socket: {}", e))?;
+            return Ok(BridgeStream::Unix(stream));
+        }
+    }
+
+    let addr = format!("127.0.0.1:{}", port)
+        .parse()
+        .map_err(|e| format!("Invalid address: {}", e))?;
+    let stream = TcpStream::connect_timeout(&addr, CONNECTION_TIMEOUT)
+        .map_err(|e| format!("Failed to connect: {}", e))?;
+    let _ = stream.set_nodelay(true);
+    Ok(BridgeStream::Tcp(stream))
+}
+
+/// The bridge's transport: TCP, or the backend's Unix socket when available.
+enum BridgeStream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(std::os::unix::net::UnixStream),
+}
+
+impl BridgeStream {
+    fn set_read_timeout(&self, timeout: Duration) -> std::io::Result<()> {
+        match self {
+            BridgeStream::Tcp(s) => s.set_read_timeout(Some(timeout)),
+            #[cfg(unix)]
+            BridgeStream::Unix(s) => s.set_read_timeout(Some(timeout)),
+        }
+    }
+}
+
+impl Read for BridgeStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            BridgeStream::Tcp(s) => s.read(buf),
+            #[cfg(unix)]
+            BridgeStream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for BridgeStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            BridgeStream::Tcp(s) => s.write(buf),
+            #[cfg(unix)]
+            BridgeStream::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            BridgeStream::Tcp(s) => s.flush(),
+            #[cfg(unix)]
+            BridgeStream::Unix(s) => s.flush(),
+        }
+    }
+}
+
+fn is_timeout(e: &tungstenite::Error) -> bool {
+    matches!(
+        e,
+        tungstenite::Error::Io(io) if io.kind() == std::io::ErrorKind::WouldBlock
+            || io.kind() == std::io::ErrorKind::TimedOut
+    )
+}
+
+/// Drive one connected session: flush queued actions, heartbeat, and dispatch
+/// incoming messages until the socket closes or the bridge is stopped.
+fn run_session(
+    app: &AppHandle,
+    socket: &mut BridgeSocket,
+    stop: &AtomicBool,
+) -> Result<(), String> {
+    let mut last_ping = Instant::now();
+
+    loop {
+        if stop.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        if app
+            .state::<WsBridgeState>()
+            .reconnect_now
+            .load(Ordering::SeqCst)
+        {
+            return Ok(());
+        }
+
+        // Flush queued actions
+        loop {
+            let next = app
+                .state::<WsBridgeState>()
+                .pending
+                .lock()
+                .unwrap()
+                .pop_front();
+            let Some(text) = next else { break };
+            if let Err(e) = socket.send(Message::text(text.clone())) {
+                // Put it back so it is retried after reconnecting
+                app.state::<WsBridgeState>()
+                    .pending
+                    .lock()
+                    .unwrap()
+                    .push_front(text);
+                return Err(format!("Failed to send: {}", e));
+            }
+        }
+
+        if last_ping.elapsed() >= HEARTBEAT_INTERVAL {
+            socket
+                .send(Message::text(r#"{"action":"ping"}"#))
+                .map_err(|e| format!("Heartbeat failed: {}", e))?;
+            last_ping = Instant::now();
+        }
+
+        match socket.read() {
+            Ok(Message::Text(text)) => dispatch(app, &text),
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(ref e) if is_timeout(e) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
+
+fn dispatch(app: &AppHandle, text: &str) {
+    let value: serde_json::Value = match serde_json::from_str(text) {
+        Ok(v) => v,
+        Err(_) => return, // ignore unparseable messages
+    };
+
+    for label in BRIDGE_WINDOWS {
+        let _ = app.emit_to(*label, WS_EVENT, &value);
+    }
+
+    let state = app.state::<WsBridgeState>();
+    let handlers = state.handlers.lock().unwrap();
+    for handler in handlers.iter() {
+        handler(app, &value);
+    }
+}
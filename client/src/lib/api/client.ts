@@ -87,11 +87,6 @@ export class PocketPawClient {
     return this.apiBase;
   }
 
-  /** Returns the WebSocket URL derived from the base URL */
-  getWsUrl(): string {
-    return this.baseUrl.replace(/^http/, "ws") + `${API_PREFIX}/ws`;
-  }
-
   // ---------------------------------------------------------------------------
   // Internal helpers
   // ---------------------------------------------------------------------------
@@ -255,8 +250,7 @@ export class PocketPawClient {
 
   /**
    * Call the login endpoint with `credentials: "include"` so the browser
-   * stores the session cookie for the backend origin.  The WebSocket handler
-   * validates this cookie, avoiding the need to pass tokens in the URL.
+   * stores the session cookie for the backend origin.
    */
   async loginForSession(token: string): Promise<void> {
     const url = `${this.apiBase}/auth/login`;
@@ -5,33 +5,28 @@ export type ConnectionState = "connecting" | "connected" | "disconnected";
 type EventType = WSEvent["type"] | "*";
 type EventHandler = (event: WSEvent) => void;
 
-import { BACKEND_URL, API_PREFIX } from "./config";
-
-const DEFAULT_WS_URL = BACKEND_URL.replace(/^http/, "ws") + `${API_PREFIX}/ws`;
-const RECONNECT_BASE_MS = 1000;
-const RECONNECT_MAX_MS = 30_000;
-const HEARTBEAT_INTERVAL_MS = 30_000;
-const CONNECTION_TIMEOUT_MS = 10_000;
-
+import { BACKEND_URL } from "./config";
+
+/**
+ * Window-side view of the backend WebSocket. The connection itself lives in
+ * the Rust bridge (ws_bridge.rs), shared by every window: backend messages
+ * arrive as `ws-event`, state changes as `ws-state`, and actions go out
+ * through `ws_send`.
+ */
 export class PocketPawWebSocket {
-  private url: string;
-  private token: string | null;
-  private ws: WebSocket | null = null;
+  private port: number;
   private listeners = new Map<EventType, Set<EventHandler>>();
-  private reconnectAttempt = 0;
-  private reconnectTimer: ReturnType<typeof setTimeout> | null = null;
-  private heartbeatTimer: ReturnType<typeof setInterval> | null = null;
-  private connectionTimer: ReturnType<typeof setTimeout> | null = null;
-  private intentionalClose = false;
-  private pendingQueue: WSAction[] = [];
+  private unlisteners: (() => void)[] = [];
+  private attached = false;
+  private generation = 0;
 
   state: ConnectionState = "disconnected";
 
   private stateListeners = new Set<(state: ConnectionState) => void>();
 
-  constructor(url?: string, token?: string) {
-    this.url = url ?? DEFAULT_WS_URL;
-    this.token = token ?? null;
+  constructor(backendUrl?: string) {
+    const url = new URL(backendUrl ?? BACKEND_URL);
+    this.port = Number(url.port) || 8888;
   }
 
   // ---------------------------------------------------------------------------
@@ -39,90 +34,33 @@ export class PocketPawWebSocket {
   // ---------------------------------------------------------------------------
 
   connect(): void {
-    if (this.ws && (this.ws.readyState === WebSocket.OPEN || this.ws.readyState === WebSocket.CONNECTING)) {
-      return;
-    }
-
-    this.intentionalClose = false;
-    this.setState("connecting");
-
-    const wsUrl = this.token ? `${this.url}?token=${encodeURIComponent(this.token)}` : this.url;
-    this.ws = new WebSocket(wsUrl);
-
-    // Connection timeout: if we don't get onopen within 10s, close and retry
-    this.connectionTimer = setTimeout(() => {
-      this.connectionTimer = null;
-      if (this.ws && this.ws.readyState === WebSocket.CONNECTING) {
-        this.ws.close();
-      }
-    }, CONNECTION_TIMEOUT_MS);
-
-    this.ws.onopen = () => {
-      if (this.connectionTimer) {
-        clearTimeout(this.connectionTimer);
-        this.connectionTimer = null;
-      }
-      this.reconnectAttempt = 0;
-      this.setState("connected");
-      this.startHeartbeat();
-      this.flushPendingQueue();
-    };
-
-    this.ws.onmessage = (event) => {
-      try {
-        const data = JSON.parse(event.data) as WSEvent;
-        this.handleEvent(data);
-      } catch {
-        // ignore unparseable messages
-      }
-    };
-
-    this.ws.onclose = () => {
-      this.cleanup();
-      this.setState("disconnected");
-      if (!this.intentionalClose) {
-        this.scheduleReconnect();
-      }
-    };
-
-    this.ws.onerror = () => {
-      // onclose will fire after onerror, so reconnect is handled there
-    };
+    if (this.attached) return;
+    this.attached = true;
+    this.attach(++this.generation).catch((err) => {
+      console.error("[PocketPawWS] Failed to attach to the WebSocket bridge:", err);
+    });
   }
 
   disconnect(): void {
-    this.intentionalClose = true;
-    this.cancelReconnect();
-    this.cleanup();
-    this.pendingQueue = [];
-    if (this.ws) {
-      this.ws.close();
-      this.ws = null;
-    }
+    // Only detaches this window; the bridge keeps serving the others.
+    this.attached = false;
+    this.generation++;
+    this.detach();
     this.setState("disconnected");
   }
 
   send(action: WSAction): void {
-    if (!this.ws || this.ws.readyState !== WebSocket.OPEN) {
-      // Queue the message to send when reconnected (max 50 to prevent memory bloat)
-      if (this.pendingQueue.length < 50) {
-        this.pendingQueue.push(action);
-      }
-      return;
-    }
-    this.ws.send(JSON.stringify(action));
+    // The bridge queues actions while it is reconnecting (max 50).
+    import("@tauri-apps/api/core")
+      .then(({ invoke }) => invoke("ws_send", { action }))
+      .catch((err) => console.warn("[PocketPawWS] Failed to send action:", err));
   }
 
-  private flushPendingQueue(): void {
-    if (this.pendingQueue.length === 0 || !this.ws || this.ws.readyState !== WebSocket.OPEN) return;
-    const queue = this.pendingQueue.splice(0);
-    for (const action of queue) {
-      try {
-        this.ws.send(JSON.stringify(action));
-      } catch {
-        // Drop on send failure
-      }
-    }
+  /** Ask the bridge to reconnect now instead of waiting out its backoff. */
+  reconnect(): void {
+    import("@tauri-apps/api/core")
+      .then(({ invoke }) => invoke("ws_bridge_connect", { port: this.port }))
+      .catch((err) => console.warn("[PocketPawWS] Failed to reconnect:", err));
   }
 
   // ---------------------------------------------------------------------------
@@ -150,10 +88,10 @@ export class PocketPawWebSocket {
     };
   }
 
-  reconnectWithToken(token: string): void {
-    this.token = token;
-    this.disconnect();
-    this.connect();
+  reconnectWithToken(_token: string): void {
+    // The bridge reads the stored token on every connect, so a live
+    // connection can stay; only a dropped one needs a nudge.
+    if (this.state !== "connected") this.reconnect();
   }
 
   // ---------------------------------------------------------------------------
@@ -198,49 +136,32 @@ export class PocketPawWebSocket {
     }
   }
 
-  private startHeartbeat(): void {
-    this.stopHeartbeat();
-    this.heartbeatTimer = setInterval(() => {
-      if (this.ws && this.ws.readyState === WebSocket.OPEN) {
-        // Send a small ping message to keep the connection alive.
-        // The backend ignores unknown actions gracefully.
-        this.ws.send(JSON.stringify({ action: "ping" }));
-      }
-    }, HEARTBEAT_INTERVAL_MS);
-  }
-
-  private stopHeartbeat(): void {
-    if (this.heartbeatTimer) {
-      clearInterval(this.heartbeatTimer);
-      this.heartbeatTimer = null;
+  private async attach(generation: number): Promise<void> {
+    const [{ invoke }, { listen }] = await Promise.all([
+      import("@tauri-apps/api/core"),
+      import("@tauri-apps/api/event"),
+    ]);
+    const unlisteners = await Promise.all([
+      listen<WSEvent>("ws-event", (event) => this.handleEvent(event.payload)),
+      listen<ConnectionState>("ws-state", (event) => this.setState(event.payload)),
+    ]);
+    if (generation !== this.generation) {
+      // disconnect() ran while the listeners were being registered.
+      for (const unlisten of unlisteners) unlisten();
+      return;
     }
-  }
+    this.unlisteners = unlisteners;
 
-  private cleanup(): void {
-    this.stopHeartbeat();
-    if (this.connectionTimer) {
-      clearTimeout(this.connectionTimer);
-      this.connectionTimer = null;
+    const state = await invoke<ConnectionState>("ws_bridge_state");
+    if (generation !== this.generation) return;
+    this.setState(state);
+    if (this.state !== "connected") {
+      await invoke("ws_bridge_connect", { port: this.port });
     }
   }
 
-  private scheduleReconnect(): void {
-    this.cancelReconnect();
-    const delay = Math.min(
-      RECONNECT_BASE_MS * Math.pow(2, this.reconnectAttempt),
-      RECONNECT_MAX_MS,
-    );
-    this.reconnectAttempt++;
-    this.reconnectTimer = setTimeout(() => {
-      this.reconnectTimer = null;
-      this.connect();
-    }, delay);
-  }
-
-  private cancelReconnect(): void {
-    if (this.reconnectTimer) {
-      clearTimeout(this.reconnectTimer);
-      this.reconnectTimer = null;
-    }
+  private detach(): void {
+    for (const unlisten of this.unlisteners) unlisten();
+    this.unlisteners = [];
   }
 }
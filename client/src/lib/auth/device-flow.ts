@@ -0,0 +1,55 @@
+// Device-code login (RFC 8628) for sessions without a local browser, e.g. over
+// SSH or on kiosks. Polling and token storage happen in Rust. Only servers
+// with a device authorization endpoint support it; the local PocketPaw backend
+// does not, so a server URL is always required.
+
+import type { OAuthTokens } from "./token-store";
+import type { OAuthResult } from "./oauth-flow";
+
+export interface DeviceCode {
+  user_code: string;
+  verification_uri: string;
+  verification_uri_complete: string | null;
+  expires_in: number;
+}
+
+/**
+ * Start device login. `onCode` receives the code to show the user; the
+ * returned promise resolves once the user approves, denies or the code expires.
+ */
+export async function startDeviceFlow(
+  onCode: (code: DeviceCode) => void,
+  serverUrl: string,
+): Promise<OAuthResult> {
+  const { listen } = await import("@tauri-apps/api/event");
+  const { invoke } = await import("@tauri-apps/api/core");
+
+  const unlisten: (() => void)[] = [];
+  const cleanup = () => unlisten.forEach((u) => u());
+
+  const done = new Promise<OAuthResult>((resolve) => {
+    listen<OAuthTokens>("oauth-device-complete", (event) => {
+      cleanup();
+      resolve({ success: true, tokens: event.payload });
+    }).then((u) => unlisten.push(u));
+    listen<{ error: string; message: string }>("oauth-device-error", (event) => {
+      cleanup();
+      resolve({ success: false, error: event.payload.message });
+    }).then((u) => unlisten.push(u));
+  });
+
+  try {
+    const code = await invoke<DeviceCode>("start_device_login", { serverUrl });
+    onCode(code);
+  } catch (err) {
+    cleanup();
+    return { success: false, error: `Failed to start device login: ${err}` };
+  }
+
+  return done;
+}
+
+export async function cancelDeviceFlow(): Promise<void> {
+  const { invoke } = await import("@tauri-apps/api/core");
+  await invoke("cancel_device_login").catch(() => {});
+}
@@ -1,6 +1,16 @@
 export { generateCodeVerifier, generateCodeChallenge, generateState } from "./pkce";
-export { readTokens, saveTokens, clearTokens, type OAuthTokens } from "./token-store";
+export {
+  readTokens,
+  saveTokens,
+  clearTokens,
+  listAccounts,
+  switchAccount,
+  removeAccount,
+  type OAuthTokens,
+  type OAuthAccount,
+} from "./token-store";
 export { startOAuthFlow, revokeTokens, type OAuthResult } from "./oauth-flow";
+export { startDeviceFlow, cancelDeviceFlow, type DeviceCode } from "./device-flow";
 export {
   refreshAccessToken,
   scheduleTokenRefresh,
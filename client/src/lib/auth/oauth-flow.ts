@@ -22,10 +22,11 @@ export async function startOAuthFlow(): Promise<OAuthResult> {
   const { invoke } = await import("@tauri-apps/api/core");
   const { openUrl } = await import("@tauri-apps/plugin-opener");
 
-  // Start the temporary localhost server and get the port
+  // Start the temporary localhost server and get the port. The server only
+  // accepts the callback carrying this flow's `state`.
   let port: number;
   try {
-    port = await invoke<number>("start_oauth_server");
+    port = await invoke<number>("start_oauth_server", { state });
   } catch (err) {
     return { success: false, error: `Failed to start OAuth server: ${err}` };
   }
@@ -49,6 +50,8 @@ export async function startOAuthFlow(): Promise<OAuthResult> {
     function cleanup() {
       unlistenRedirect?.();
       if (timeoutId) clearTimeout(timeoutId);
+      // No-op if the server already stopped after the callback
+      invoke("cancel_oauth_server", { port }).catch(() => {});
     }
 
     function settle(result: OAuthResult) {
@@ -87,8 +90,8 @@ export async function startOAuthFlow(): Promise<OAuthResult> {
         settled = true;
         cleanup();
 
-        const tokens = await exchangeCodeForTokens(code, verifier, redirectUri);
-        await saveTokens(tokens);
+        const { tokens, accountId } = await exchangeCodeForTokens(code, verifier, redirectUri);
+        await saveTokens(tokens, accountId);
         resolve({ success: true, tokens });
       } catch (err) {
         settle({ success: false, error: `Token exchange failed: ${err}` });
@@ -110,7 +113,7 @@ async function exchangeCodeForTokens(
   code: string,
   codeVerifier: string,
   redirectUri: string,
-): Promise<OAuthTokens> {
+): Promise<{ tokens: OAuthTokens; accountId: string }> {
   const url = `${API_BASE}/oauth/token`;
   const payload = JSON.stringify({
     grant_type: "authorization_code",
@@ -143,10 +146,14 @@ async function exchangeCodeForTokens(
   const data = JSON.parse(responseText);
 
   return {
-    access_token: data.access_token,
-    refresh_token: data.refresh_token ?? null,
-    expires_at: Math.floor(Date.now() / 1000) + (data.expires_in ?? 3600),
-    scopes: data.scope ? data.scope.split(" ") : [],
+    tokens: {
+      access_token: data.access_token,
+      refresh_token: data.refresh_token ?? null,
+      expires_at: Math.floor(Date.now() / 1000) + (data.expires_in ?? 3600),
+      scopes: data.scope ? data.scope.split(" ") : [],
+    },
+    // Servers without a token subject are one account per host
+    accountId: data.sub ?? new URL(API_BASE).host,
   };
 }
 
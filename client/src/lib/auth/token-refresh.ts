@@ -1,4 +1,6 @@
-// Token refresh logic with deduplication and scheduled auto-refresh
+// Token refresh logic with deduplication and scheduled auto-refresh.
+// Inside Tauri the Rust side owns refresh (it keeps running while webviews
+// sleep); these helpers delegate to it and relay its events.
 
 import { saveTokens, type OAuthTokens } from "./token-store";
 import { API_BASE } from "$lib/api/config";
@@ -6,18 +8,28 @@ const REFRESH_MARGIN_S = 5 * 60; // refresh 5 minutes before expiry
 
 let refreshPromise: Promise<OAuthTokens> | null = null;
 let scheduledTimer: ReturnType<typeof setTimeout> | null = null;
+let eventUnlisten: (() => void)[] = [];
+
+function inTauri(): boolean {
+  return typeof window !== "undefined" && !!(window as any).__TAURI_INTERNALS__;
+}
 
 export async function refreshAccessToken(tokens: OAuthTokens): Promise<OAuthTokens> {
   // Deduplicate concurrent refresh calls
   if (refreshPromise) return refreshPromise;
 
-  refreshPromise = doRefresh(tokens).finally(() => {
+  refreshPromise = (inTauri() ? doRustRefresh() : doRefresh(tokens)).finally(() => {
     refreshPromise = null;
   });
 
   return refreshPromise;
 }
 
+async function doRustRefresh(): Promise<OAuthTokens> {
+  const { invoke } = await import("@tauri-apps/api/core");
+  return await invoke<OAuthTokens>("refresh_oauth_tokens");
+}
+
 async function doRefresh(tokens: OAuthTokens): Promise<OAuthTokens> {
   if (!tokens.refresh_token) {
     throw new Error("No refresh token available");
@@ -58,6 +70,11 @@ export function scheduleTokenRefresh(
 ): void {
   cancelScheduledRefresh();
 
+  if (inTauri()) {
+    listenForRustRefresh(onRefreshed, onFailed);
+    return;
+  }
+
   const nowS = Math.floor(Date.now() / 1000);
   const delayS = Math.max(tokens.expires_at - nowS - REFRESH_MARGIN_S, 10);
   const delayMs = delayS * 1000;
@@ -74,9 +91,36 @@ export function scheduleTokenRefresh(
   }, delayMs);
 }
 
+function listenForRustRefresh(
+  onRefreshed: (newTokens: OAuthTokens) => void,
+  onFailed: (error: Error) => void,
+): void {
+  const pending: (() => void)[] = [];
+  eventUnlisten = pending;
+  import("@tauri-apps/api/event")
+    .then(async ({ listen }) => {
+      const u1 = await listen<OAuthTokens>("oauth-tokens-refreshed", (event) =>
+        onRefreshed(event.payload),
+      );
+      const u2 = await listen<{ reason: string }>("oauth-session-expired", (event) =>
+        onFailed(new Error(event.payload.reason)),
+      );
+      // Cancelled while the listeners were being registered
+      if (eventUnlisten !== pending) {
+        u1();
+        u2();
+        return;
+      }
+      pending.push(u1, u2);
+    })
+    .catch((err) => console.warn("[Auth] Failed to listen for token refresh:", err));
+}
+
 export function cancelScheduledRefresh(): void {
   if (scheduledTimer) {
     clearTimeout(scheduledTimer);
     scheduledTimer = null;
   }
+  for (const u of eventUnlisten) u();
+  eventUnlisten = [];
 }
@@ -16,11 +16,43 @@ export async function readTokens(): Promise<OAuthTokens | null> {
   }
 }
 
-export async function saveTokens(tokens: OAuthTokens): Promise<void> {
+export interface OAuthAccount {
+  id: string;
+  label: string | null;
+  active: boolean;
+  expires_at: number;
+  scopes: string[];
+}
+
+/**
+ * Save tokens for the active account, or for `accountId` (which is added and
+ * made active if it's new).
+ */
+export async function saveTokens(
+  tokens: OAuthTokens,
+  accountId?: string,
+  label?: string,
+): Promise<void> {
+  const { invoke } = await import("@tauri-apps/api/core");
+  await invoke("save_oauth_tokens", { tokens, accountId, label });
+}
+
+export async function listAccounts(): Promise<OAuthAccount[]> {
+  const { invoke } = await import("@tauri-apps/api/core");
+  return await invoke<OAuthAccount[]>("list_oauth_accounts");
+}
+
+export async function switchAccount(accountId: string): Promise<OAuthTokens> {
+  const { invoke } = await import("@tauri-apps/api/core");
+  return await invoke<OAuthTokens>("switch_oauth_account", { accountId });
+}
+
+export async function removeAccount(accountId: string): Promise<boolean> {
   const { invoke } = await import("@tauri-apps/api/core");
-  await invoke("save_oauth_tokens", { tokens });
+  return await invoke<boolean>("remove_oauth_account", { accountId });
 }
 
+/** Sign out of the active account. */
 export async function clearTokens(): Promise<void> {
   const { invoke } = await import("@tauri-apps/api/core");
   await invoke("clear_oauth_tokens");
@@ -1,5 +1,5 @@
 export { LocalFileSystem } from "./local";
-export type { FileStatExtended } from "./local";
+export type { FileStatExtended, TrashEntry, HistoryEntry, DecodedText, LineWindow, GrepOptions, GrepMatch, GrepFileResult, GrepSummary, GrepHandle, WalkOptions, ConflictPolicy, TransferRequest, TransferProgress, TransferConflict, TransferReport, TransferHandle, ArchiveProgress, ArchiveReport, ArchiveHandle } from "./local";
 export type { FileEntry, DefaultDirs, FileChangeEvent, FileSystemProvider, RecursiveSearchResult } from "./types";
 export {
   getThumbnail,
@@ -18,7 +18,12 @@ export {
   normalizeSeparators,
   getExtension,
   getFileName,
+  isArchivePath,
+  archiveStem,
+  splitArchivePath,
 } from "./paths";
+export { getScope, setScope, isPermissionDenied } from "./scope";
+export type { ScopeConfig } from "./scope";
 
 import { LocalFileSystem } from "./local";
 
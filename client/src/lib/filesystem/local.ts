@@ -36,6 +36,371 @@ interface RawFileStatExtended {
   is_symlink: boolean;
 }
 
+/** Item in the system trash, returned by fs_list_trash */
+export interface TrashEntry {
+  id: string;
+  name: string;
+  originalPath: string;
+  deletedAt: number;
+}
+
+/** Undoable file operation, returned by fs_undo / fs_redo / fs_history */
+export interface HistoryEntry {
+  id: number;
+  kind: "write" | "append" | "copy" | "rename" | "create_dir" | "copy_dir" | "trash" | "restore";
+  paths: string[];
+  time: number;
+}
+
+/** Modification time (seconds) and SHA-256 of a file's content */
+export interface FileVersion {
+  mtime: number;
+  sha256: string;
+}
+
+export interface SaveOptions {
+  /** Only write if the file still has this mtime (ignored when a hash is given) */
+  expectedMtime?: number;
+  /** Only write if the file still has this content hash */
+  expectedSha256?: string;
+  /** Keep the previous content as `<name>.bak` */
+  backup?: boolean;
+}
+
+/** Result of saveFile: the new version, or the version found on disk if it changed */
+export type SaveResult =
+  | { status: "written"; version: FileVersion }
+  | { status: "conflict"; current: FileVersion | null };
+
+type RawWriteResult =
+  | { status: "written"; mtime: number; sha256: string }
+  | { status: "conflict"; current_mtime: number | null; current_hash: string | null };
+
+/** A whole file decoded with its detected (or a chosen) encoding */
+export interface DecodedText {
+  text: string;
+  encoding: string;
+  hasBom: boolean;
+  /** Some bytes were invalid in the encoding and shown as U+FFFD */
+  malformed: boolean;
+}
+
+/** A window of lines from fs_read_lines */
+export interface LineWindow {
+  startLine: number;
+  lines: string[];
+  encoding: string;
+  /** Known once the file has been scanned to its end */
+  totalLines: number | null;
+  atEnd: boolean;
+}
+
+interface RawDecodedText {
+  text: string;
+  encoding: string;
+  has_bom: boolean;
+  malformed: boolean;
+}
+
+interface RawLineWindow {
+  start_line: number;
+  lines: string[];
+  encoding: string;
+  total_lines: number | null;
+  at_end: boolean;
+}
+
+export interface WatchOptions {
+  /** Also report changes in subdirectories (default false) */
+  recursive?: boolean;
+  /** Quiet time before a burst of changes is delivered (default 150) */
+  debounceMs?: number;
+  /** Rescan interval when changes must be polled for (default 2000) */
+  pollIntervalMs?: number;
+}
+
+/** How a watch() subscription learns about changes */
+export interface WatchInfo {
+  /** "poll" rescans periodically, so changes show up with a delay */
+  backend: "native" | "poll";
+  pollIntervalMs: number | null;
+  /** Why polling was chosen, e.g. a network filesystem */
+  reason: string | null;
+}
+
+/** Returned by watch(): call it to stop watching */
+export interface WatchSubscription extends WatchInfo {
+  (): Promise<void>;
+}
+
+/** How recursive searches walk directories */
+export interface WalkOptions {
+  /** Skip what .gitignore, .ignore and git excludes ignore (default true) */
+  respectIgnore?: boolean;
+  /** Include dot files and dot directories (default false) */
+  includeHidden?: boolean;
+}
+
+export interface GrepOptions extends WalkOptions {
+  rootPath: string;
+  query: string;
+  regex?: boolean;
+  caseSensitive?: boolean;
+  wholeWord?: boolean;
+  /** Globs relative to the root; a pattern without "/" matches file names */
+  include?: string[];
+  exclude?: string[];
+  maxFileSize?: number;
+  maxResults?: number;
+  /** Lines of context around each match (at most 10) */
+  context?: number;
+}
+
+export interface GrepMatch {
+  lineNumber: number;
+  line: string;
+  /** [start, end) offsets into `line` */
+  ranges: [number, number][];
+  contextBefore: string[];
+  contextAfter: string[];
+}
+
+export interface GrepFileResult {
+  path: string;
+  matches: GrepMatch[];
+}
+
+export interface GrepSummary {
+  filesSearched: number;
+  filesMatched: number;
+  matches: number;
+  truncated: boolean;
+  cancelled: boolean;
+}
+
+/** A running content search started by grep() */
+export interface GrepHandle {
+  searchId: string;
+  cancel(): Promise<void>;
+  /** Resolves when the search finishes or is cancelled */
+  done: Promise<GrepSummary>;
+}
+
+interface RawGrepFileResult {
+  search_id: string;
+  path: string;
+  matches: {
+    line_number: number;
+    line: string;
+    ranges: [number, number][];
+    context_before: string[];
+    context_after: string[];
+  }[];
+}
+
+interface RawGrepDone {
+  search_id: string;
+  files_searched: number;
+  files_matched: number;
+  matches: number;
+  truncated: boolean;
+  cancelled: boolean;
+}
+
+export type ConflictPolicy = "skip" | "overwrite" | "rename" | "ask";
+
+export interface TransferRequest {
+  mode: "copy" | "move";
+  sources: string[];
+  /** Folder the sources are copied or moved into */
+  destDir: string;
+  /** What to do when an entry already exists (default "ask") */
+  conflict?: ConflictPolicy;
+  /** Keep modification times and ownership (default true) */
+  preserveMetadata?: boolean;
+}
+
+export interface TransferProgress {
+  bytesDone: number;
+  bytesTotal: number;
+  filesDone: number;
+  filesTotal: number;
+  currentPath: string;
+}
+
+/** An existing destination the "ask" policy needs a decision on */
+export interface TransferConflict {
+  source: string;
+  dest: string;
+  sourceIsDir: boolean;
+  destIsDir: boolean;
+  sourceSize: number;
+  destSize: number;
+  sourceModified: number;
+  destModified: number;
+}
+
+export interface TransferReport {
+  filesDone: number;
+  bytesDone: number;
+  skipped: number;
+  failures: { path: string; error: string }[];
+  cancelled: boolean;
+  /** Where the sources ended up */
+  destinations: string[];
+}
+
+/** A running copy or move started by transfer() */
+export interface TransferHandle {
+  jobId: string;
+  cancel(): Promise<void>;
+  /** Answer the current conflict; "ask" counts as "skip" */
+  resolve(action: ConflictPolicy, applyToAll?: boolean): Promise<void>;
+  /** Resolves when the transfer finishes or is cancelled */
+  done: Promise<TransferReport>;
+}
+
+interface RawTransferProgress {
+  job_id: string;
+  bytes_done: number;
+  bytes_total: number;
+  files_done: number;
+  files_total: number;
+  current_path: string;
+}
+
+interface RawTransferConflict {
+  job_id: string;
+  source: string;
+  dest: string;
+  source_is_dir: boolean;
+  dest_is_dir: boolean;
+  source_size: number;
+  dest_size: number;
+  source_modified: number;
+  dest_modified: number;
+}
+
+interface RawTransferReport {
+  job_id: string;
+  files_done: number;
+  bytes_done: number;
+  skipped: number;
+  failures: { path: string; error: string }[];
+  cancelled: boolean;
+  destinations: string[];
+}
+
+export interface ArchiveProgress {
+  /** For tarball extraction, compressed bytes read */
+  bytesDone: number;
+  bytesTotal: number;
+  entriesDone: number;
+  currentPath: string;
+}
+
+export interface ArchiveReport {
+  entriesDone: number;
+  bytesDone: number;
+  /** Entries not extracted because something already existed there */
+  skipped: number;
+  failures: { path: string; error: string }[];
+  cancelled: boolean;
+  /** Why the job stopped early, if it did */
+  error: string | null;
+  /** The folder extracted into, or the archive created */
+  destination: string;
+}
+
+/** A running extraction or archive creation */
+export interface ArchiveHandle {
+  jobId: string;
+  cancel(): Promise<void>;
+  /** Resolves when the job finishes or is cancelled */
+  done: Promise<ArchiveReport>;
+}
+
+interface RawArchiveProgress {
+  job_id: string;
+  bytes_done: number;
+  bytes_total: number;
+  entries_done: number;
+  current_path: string;
+}
+
+interface RawArchiveReport {
+  job_id: string;
+  entries_done: number;
+  bytes_done: number;
+  skipped: number;
+  failures: { path: string; error: string }[];
+  cancelled: boolean;
+  error: string | null;
+  destination: string;
+}
+
+/** A file index query result */
+export interface IndexHit extends FileEntry {
+  root: string;
+  relativePath: string;
+  score: number;
+  /** Matched positions in `relativePath`, in UTF-16 code units */
+  indices: number[];
+}
+
+export interface IndexRootStatus {
+  root: string;
+  state: "building" | "ready" | "error";
+  entries: number;
+  /** Unix seconds of the last completed scan, 0 if none yet */
+  scannedAt: number;
+  error: string | null;
+}
+
+interface RawIndexHit {
+  path: string;
+  relative_path: string;
+  root: string;
+  name: string;
+  extension: string;
+  is_dir: boolean;
+  size: number;
+  modified: number;
+  score: number;
+  indices: number[];
+}
+
+interface RawIndexStatus {
+  root: string;
+  state: "building" | "ready" | "error";
+  entries: number;
+  scanned_at: number;
+  error: string | null;
+}
+
+/** Bytes per fs_write_chunk call in writeBytes */
+const WRITE_CHUNK_SIZE = 4 * 1024 * 1024;
+
+function toSaveResult(raw: RawWriteResult): SaveResult {
+  if (raw.status === "written") {
+    return { status: "written", version: { mtime: raw.mtime, sha256: raw.sha256 } };
+  }
+  return {
+    status: "conflict",
+    current:
+      raw.current_mtime !== null && raw.current_hash !== null
+        ? { mtime: raw.current_mtime, sha256: raw.current_hash }
+        : null,
+  };
+}
+
+interface RawTrashEntry {
+  id: string;
+  name: string;
+  original_path: string;
+  deleted_at: number;
+}
+
 function isTauri(): boolean {
   return typeof window !== "undefined" && "__TAURI_INTERNALS__" in window;
 }
@@ -74,12 +439,446 @@ export class LocalFileSystem implements FileSystemProvider {
     await invoke("fs_write_file", { path, content });
   }
 
+  /** Write a file unless it changed on disk since the version the caller last saw */
+  async saveFile(path: string, content: string, options: SaveOptions = {}): Promise<SaveResult> {
+    if (!isTauri()) return { status: "written", version: { mtime: 0, sha256: "" } };
+    const { invoke } = await import("@tauri-apps/api/core");
+    const raw: RawWriteResult = await invoke("fs_write_file", {
+      path,
+      content,
+      expectedMtime: options.expectedMtime ?? null,
+      expectedSha256: options.expectedSha256 ?? null,
+      backup: options.backup ?? null,
+    });
+    return toSaveResult(raw);
+  }
+
+  /** Write binary content in one call (sent as base64) */
+  async writeFileBase64(path: string, data: string, options: SaveOptions = {}): Promise<SaveResult> {
+    if (!isTauri()) return { status: "written", version: { mtime: 0, sha256: "" } };
+    const { invoke } = await import("@tauri-apps/api/core");
+    const raw: RawWriteResult = await invoke("fs_write_file_base64", {
+      path,
+      data,
+      expectedMtime: options.expectedMtime ?? null,
+      expectedSha256: options.expectedSha256 ?? null,
+      backup: options.backup ?? null,
+    });
+    return toSaveResult(raw);
+  }
+
+  /**
+   * Write binary content in chunks sent as raw IPC bytes. The file is only
+   * replaced once every chunk has arrived; with `append` the data is added to
+   * the existing content.
+   */
+  async writeBytes(
+    path: string,
+    data: Blob | Uint8Array,
+    options: SaveOptions & { append?: boolean; onProgress?: (written: number, total: number) => void } = {},
+  ): Promise<SaveResult> {
+    if (!isTauri()) return { status: "written", version: { mtime: 0, sha256: "" } };
+    const { invoke } = await import("@tauri-apps/api/core");
+    const blob = data instanceof Blob ? data : new Blob([data]);
+    const session: number = await invoke("fs_write_begin", {
+      path,
+      append: options.append ?? null,
+      expectedMtime: options.expectedMtime ?? null,
+      expectedSha256: options.expectedSha256 ?? null,
+      backup: options.backup ?? null,
+    });
+    try {
+      for (let offset = 0; offset < blob.size; offset += WRITE_CHUNK_SIZE) {
+        const chunk = new Uint8Array(await blob.slice(offset, offset + WRITE_CHUNK_SIZE).arrayBuffer());
+        await invoke("fs_write_chunk", chunk, { headers: { "x-write-session": String(session) } });
+        options.onProgress?.(Math.min(offset + WRITE_CHUNK_SIZE, blob.size), blob.size);
+      }
+      const raw: RawWriteResult = await invoke("fs_write_commit", { session });
+      return toSaveResult(raw);
+    } catch (e) {
+      await invoke("fs_write_abort", { session }).catch(() => {});
+      throw e;
+    }
+  }
+
+  /** Read a file as text, reporting the encoding it was decoded with */
+  async readFileDecoded(path: string, encoding?: string): Promise<DecodedText> {
+    if (!isTauri()) return { text: "", encoding: "UTF-8", hasBom: false, malformed: false };
+    const { invoke } = await import("@tauri-apps/api/core");
+    const raw: RawDecodedText = await invoke("fs_read_file_decoded", { path, encoding: encoding ?? null });
+    return { text: raw.text, encoding: raw.encoding, hasBom: raw.has_bom, malformed: raw.malformed };
+  }
+
+  /** Read up to `length` bytes at `offset` */
+  async readRange(path: string, offset: number, length: number): Promise<Uint8Array> {
+    if (!isTauri()) return new Uint8Array();
+    const { invoke } = await import("@tauri-apps/api/core");
+    const buf: ArrayBuffer = await invoke("fs_read_range", { path, offset, length });
+    return new Uint8Array(buf);
+  }
+
+  /** Read `count` lines starting at zero-based `startLine`, for files too big to load whole */
+  async readLines(path: string, startLine: number, count: number, encoding?: string): Promise<LineWindow> {
+    if (!isTauri()) return { startLine, lines: [], encoding: "UTF-8", totalLines: null, atEnd: true };
+    const { invoke } = await import("@tauri-apps/api/core");
+    const raw: RawLineWindow = await invoke("fs_read_lines", {
+      path,
+      startLine,
+      count,
+      encoding: encoding ?? null,
+    });
+    return {
+      startLine: raw.start_line,
+      lines: raw.lines,
+      encoding: raw.encoding,
+      totalLines: raw.total_lines,
+      atEnd: raw.at_end,
+    };
+  }
+
+  /**
+   * Search file contents under `options.rootPath`. `onResult` is called for
+   * each file with matches as the search finds them.
+   */
+  async grep(options: GrepOptions, onResult: (result: GrepFileResult) => void): Promise<GrepHandle> {
+    const empty = { filesSearched: 0, filesMatched: 0, matches: 0, truncated: false, cancelled: false };
+    if (!isTauri()) return { searchId: "", cancel: async () => {}, done: Promise.resolve(empty) };
+    const { invoke } = await import("@tauri-apps/api/core");
+    const { listen } = await import("@tauri-apps/api/event");
+
+    const searchId = crypto.randomUUID();
+    let finish: (summary: GrepSummary) => void = () => {};
+    const done = new Promise<GrepSummary>((resolve) => { finish = resolve; });
+
+    // Listen before starting so no early results are missed
+    const unlistenResult = await listen<RawGrepFileResult>("fs-grep-result", (e) => {
+      if (e.payload.search_id !== searchId) return;
+      onResult({
+        path: e.payload.path,
+        matches: e.payload.matches.map((m) => ({
+          lineNumber: m.line_number,
+          line: m.line,
+          ranges: m.ranges,
+          contextBefore: m.context_before,
+          contextAfter: m.context_after,
+        })),
+      });
+    });
+    const unlistenDone = await listen<RawGrepDone>("fs-grep-done", (e) => {
+      if (e.payload.search_id !== searchId) return;
+      unlistenResult();
+      unlistenDone();
+      finish({
+        filesSearched: e.payload.files_searched,
+        filesMatched: e.payload.files_matched,
+        matches: e.payload.matches,
+        truncated: e.payload.truncated,
+        cancelled: e.payload.cancelled,
+      });
+    });
+
+    try {
+      await invoke("fs_grep", {
+        searchId,
+        options: {
+          root_path: options.rootPath,
+          query: options.query,
+          regex: options.regex ?? false,
+          case_sensitive: options.caseSensitive ?? false,
+          whole_word: options.wholeWord ?? false,
+          include: options.include ?? [],
+          exclude: options.exclude ?? [],
+          respect_ignore: options.respectIgnore ?? true,
+          include_hidden: options.includeHidden ?? false,
+          ...(options.maxFileSize !== undefined && { max_file_size: options.maxFileSize }),
+          ...(options.maxResults !== undefined && { max_results: options.maxResults }),
+          context: options.context ?? 0,
+        },
+      });
+    } catch (e) {
+      unlistenResult();
+      unlistenDone();
+      throw e;
+    }
+
+    return {
+      searchId,
+      cancel: async () => {
+        await invoke("fs_grep_cancel", { searchId });
+      },
+      done,
+    };
+  }
+
+  /**
+   * Copy or move `request.sources` into `request.destDir` in the background.
+   * Conflicts under the "ask" policy pause the job until `resolve()` is called.
+   */
+  async transfer(
+    request: TransferRequest,
+    handlers: {
+      onProgress?: (progress: TransferProgress) => void;
+      onConflict?: (conflict: TransferConflict) => void;
+    } = {},
+  ): Promise<TransferHandle> {
+    const empty = { filesDone: 0, bytesDone: 0, skipped: 0, failures: [], cancelled: false, destinations: [] };
+    if (!isTauri()) {
+      return { jobId: "", cancel: async () => {}, resolve: async () => {}, done: Promise.resolve(empty) };
+    }
+    const { invoke } = await import("@tauri-apps/api/core");
+    const { listen } = await import("@tauri-apps/api/event");
+
+    const jobId = crypto.randomUUID();
+    let finish: (report: TransferReport) => void = () => {};
+    const done = new Promise<TransferReport>((resolve) => { finish = resolve; });
+
+    // Listen before starting so no early events are missed
+    const unlistenProgress = await listen<RawTransferProgress>("fs-transfer-progress", (e) => {
+      if (e.payload.job_id !== jobId) return;
+      handlers.onProgress?.({
+        bytesDone: e.payload.bytes_done,
+        bytesTotal: e.payload.bytes_total,
+        filesDone: e.payload.files_done,
+        filesTotal: e.payload.files_total,
+        currentPath: e.payload.current_path,
+      });
+    });
+    const unlistenConflict = await listen<RawTransferConflict>("fs-transfer-conflict", (e) => {
+      if (e.payload.job_id !== jobId) return;
+      handlers.onConflict?.({
+        source: e.payload.source,
+        dest: e.payload.dest,
+        sourceIsDir: e.payload.source_is_dir,
+        destIsDir: e.payload.dest_is_dir,
+        sourceSize: e.payload.source_size,
+        destSize: e.payload.dest_size,
+        sourceModified: e.payload.source_modified,
+        destModified: e.payload.dest_modified,
+      });
+    });
+    const unlistenDone = await listen<RawTransferReport>("fs-transfer-done", (e) => {
+      if (e.payload.job_id !== jobId) return;
+      unlistenProgress();
+      unlistenConflict();
+      unlistenDone();
+      finish({
+        filesDone: e.payload.files_done,
+        bytesDone: e.payload.bytes_done,
+        skipped: e.payload.skipped,
+        failures: e.payload.failures,
+        cancelled: e.payload.cancelled,
+        destinations: e.payload.destinations,
+      });
+    });
+
+    try {
+      await invoke("fs_transfer", {
+        jobId,
+        request: {
+          mode: request.mode,
+          sources: request.sources,
+          dest_dir: request.destDir,
+          conflict: request.conflict ?? "ask",
+          preserve_metadata: request.preserveMetadata ?? true,
+        },
+      });
+    } catch (e) {
+      unlistenProgress();
+      unlistenConflict();
+      unlistenDone();
+      throw e;
+    }
+
+    return {
+      jobId,
+      cancel: async () => {
+        await invoke("fs_transfer_cancel", { jobId });
+      },
+      resolve: async (action, applyToAll = false) => {
+        await invoke("fs_transfer_resolve", { jobId, answer: { action, apply_to_all: applyToAll } });
+      },
+      done,
+    };
+  }
+
+  /**
+   * List the entries directly inside `dir` of an archive ("" for the top
+   * level). Their paths continue below the archive's path.
+   */
+  async archiveList(path: string, dir = ""): Promise<FileEntry[]> {
+    if (!isTauri()) return [];
+    const { invoke } = await import("@tauri-apps/api/core");
+    const raw: RawFileEntry[] = await invoke("fs_archive_list", { path, dir });
+    return raw.map(mapEntry);
+  }
+
+  /**
+   * Extract an archive into `destDir`, which is created if needed. `entries`
+   * limits extraction to those paths inside the archive, which then land
+   * directly in `destDir`.
+   */
+  async extractArchive(
+    path: string,
+    destDir: string,
+    options: { entries?: string[]; overwrite?: boolean } = {},
+    onProgress?: (progress: ArchiveProgress) => void,
+  ): Promise<ArchiveHandle> {
+    return this.archiveJob(
+      (invoke, jobId) =>
+        invoke("fs_archive_extract", {
+          jobId,
+          path,
+          destDir,
+          entries: options.entries ?? null,
+          overwrite: options.overwrite ?? false,
+        }),
+      onProgress,
+    );
+  }
+
+  /** Pack `sources` into a new archive; `dest`'s extension picks the format */
+  async createArchive(
+    sources: string[],
+    dest: string,
+    onProgress?: (progress: ArchiveProgress) => void,
+  ): Promise<ArchiveHandle> {
+    return this.archiveJob(
+      (invoke, jobId) => invoke("fs_archive_create", { jobId, sources, dest }),
+      onProgress,
+    );
+  }
+
+  private async archiveJob(
+    start: (invoke: typeof import("@tauri-apps/api/core").invoke, jobId: string) => Promise<unknown>,
+    onProgress?: (progress: ArchiveProgress) => void,
+  ): Promise<ArchiveHandle> {
+    const empty = {
+      entriesDone: 0, bytesDone: 0, skipped: 0, failures: [], cancelled: false, error: null, destination: "",
+    };
+    if (!isTauri()) return { jobId: "", cancel: async () => {}, done: Promise.resolve(empty) };
+    const { invoke } = await import("@tauri-apps/api/core");
+    const { listen } = await import("@tauri-apps/api/event");
+
+    const jobId = crypto.randomUUID();
+    let finish: (report: ArchiveReport) => void = () => {};
+    const done = new Promise<ArchiveReport>((resolve) => { finish = resolve; });
+
+    // Listen before starting so no early events are missed
+    const unlistenProgress = await listen<RawArchiveProgress>("fs-archive-progress", (e) => {
+      if (e.payload.job_id !== jobId) return;
+      onProgress?.({
+        bytesDone: e.payload.bytes_done,
+        bytesTotal: e.payload.bytes_total,
+        entriesDone: e.payload.entries_done,
+        currentPath: e.payload.current_path,
+      });
+    });
+    const unlistenDone = await listen<RawArchiveReport>("fs-archive-done", (e) => {
+      if (e.payload.job_id !== jobId) return;
+      unlistenProgress();
+      unlistenDone();
+      finish({
+        entriesDone: e.payload.entries_done,
+        bytesDone: e.payload.bytes_done,
+        skipped: e.payload.skipped,
+        failures: e.payload.failures,
+        cancelled: e.payload.cancelled,
+        error: e.payload.error,
+        destination: e.payload.destination,
+      });
+    });
+
+    try {
+      await start(invoke, jobId);
+    } catch (e) {
+      unlistenProgress();
+      unlistenDone();
+      throw e;
+    }
+
+    return {
+      jobId,
+      cancel: async () => {
+        await invoke("fs_archive_cancel", { jobId });
+      },
+      done,
+    };
+  }
+
+  async appendFile(path: string, content: string): Promise<void> {
+    if (!isTauri()) return;
+    const { invoke } = await import("@tauri-apps/api/core");
+    await invoke("fs_append_file", { path, content });
+  }
+
+  /** Append binary content (sent as base64) */
+  async appendFileBase64(path: string, data: string): Promise<void> {
+    if (!isTauri()) return;
+    const { invoke } = await import("@tauri-apps/api/core");
+    await invoke("fs_append_file_base64", { path, data });
+  }
+
+  async fileVersion(path: string): Promise<FileVersion | null> {
+    if (!isTauri()) return null;
+    const { invoke } = await import("@tauri-apps/api/core");
+    return invoke("fs_file_version", { path });
+  }
+
+  /** Move a file or directory to the system trash */
   async deleteFile(path: string, recursive = false): Promise<void> {
     if (!isTauri()) return;
     const { invoke } = await import("@tauri-apps/api/core");
     await invoke("fs_delete", { path, recursive });
   }
 
+  /** Delete without going through the trash. Cannot be undone. */
+  async deletePermanently(path: string, recursive = false): Promise<void> {
+    if (!isTauri()) return;
+    const { invoke } = await import("@tauri-apps/api/core");
+    await invoke("fs_delete_permanently", { path, recursive });
+  }
+
+  /** Items in the system trash, most recently deleted first */
+  async listTrash(): Promise<TrashEntry[]> {
+    if (!isTauri()) return [];
+    const { invoke } = await import("@tauri-apps/api/core");
+    const raw: RawTrashEntry[] = await invoke("fs_list_trash");
+    return raw.map((e) => ({
+      id: e.id,
+      name: e.name,
+      originalPath: e.original_path,
+      deletedAt: e.deleted_at,
+    }));
+  }
+
+  /** Undo the last file operation. Returns null if there was nothing to undo. */
+  async undo(): Promise<HistoryEntry | null> {
+    if (!isTauri()) return null;
+    const { invoke } = await import("@tauri-apps/api/core");
+    return invoke("fs_undo");
+  }
+
+  /** Redo the last undone file operation. Returns null if there was nothing to redo. */
+  async redo(): Promise<HistoryEntry | null> {
+    if (!isTauri()) return null;
+    const { invoke } = await import("@tauri-apps/api/core");
+    return invoke("fs_redo");
+  }
+
+  /** Undo and redo stacks, most recent first */
+  async history(): Promise<{ undo: HistoryEntry[]; redo: HistoryEntry[] }> {
+    if (!isTauri()) return { undo: [], redo: [] };
+    const { invoke } = await import("@tauri-apps/api/core");
+    return invoke("fs_history");
+  }
+
+  /** Restore trashed items (by id from listTrash) to their original location */
+  async restoreFromTrash(ids: string[]): Promise<void> {
+    if (!isTauri()) return;
+    const { invoke } = await import("@tauri-apps/api/core");
+    await invoke("fs_restore_from_trash", { ids });
+  }
+
   async rename(oldPath: string, newPath: string): Promise<void> {
     if (!isTauri()) return;
     const { invoke } = await import("@tauri-apps/api/core");
@@ -107,25 +906,58 @@ export class LocalFileSystem implements FileSystemProvider {
     return invoke("fs_exists", { path });
   }
 
-  async watch(path: string, callback: (event: FileChangeEvent) => void): Promise<() => void> {
-    if (!isTauri()) return () => {};
+  async watch(
+    path: string,
+    callback: (event: FileChangeEvent) => void,
+    options: WatchOptions = {},
+  ): Promise<WatchSubscription> {
+    if (!isTauri()) {
+      return Object.assign(async () => {}, { backend: "native" as const, pollIntervalMs: null, reason: null });
+    }
     const { invoke } = await import("@tauri-apps/api/core");
     const { listen } = await import("@tauri-apps/api/event");
 
-    await invoke("fs_watch", { path });
-
-    const unlisten = await listen<FileChangeEvent>("fs-change", (e) => {
-      callback(e.payload);
-    });
+    // Other subscriptions of this window arrive on the same event
+    let subscription: number | null = null;
+    const unlisten = await listen<{ subscription: number; changes: FileChangeEvent[] }>(
+      "fs-change",
+      (e) => {
+        if (e.payload.subscription !== subscription) return;
+        for (const change of e.payload.changes) callback(change);
+      },
+    );
+    let info: {
+      subscription: number;
+      backend: "native" | "poll";
+      poll_interval_ms: number | null;
+      reason: string | null;
+    };
+    try {
+      info = await invoke("fs_watch", {
+        path,
+        recursive: options.recursive ?? null,
+        debounceMs: options.debounceMs ?? null,
+        pollIntervalMs: options.pollIntervalMs ?? null,
+      });
+    } catch (e) {
+      unlisten();
+      throw e;
+    }
+    subscription = info.subscription;
 
-    return async () => {
+    const unwatch = async () => {
       unlisten();
       try {
-        await invoke("fs_unwatch");
+        await invoke("fs_unwatch", { subscription });
       } catch {
         // Ignore cleanup errors
       }
     };
+    return Object.assign(unwatch, {
+      backend: info.backend,
+      pollIntervalMs: info.poll_interval_ms,
+      reason: info.reason,
+    });
   }
 
   async getDefaultDirs(): Promise<DefaultDirs> {
@@ -176,11 +1008,11 @@ export class LocalFileSystem implements FileSystemProvider {
     await invoke("fs_copy_dir", { src, dest });
   }
 
-  /** Move a file or directory (uses rename under the hood) */
+  /** Move a file or directory, copying then deleting across filesystems */
   async moveFile(src: string, dest: string): Promise<void> {
     if (!isTauri()) return;
     const { invoke } = await import("@tauri-apps/api/core");
-    await invoke("fs_rename", { oldPath: src, newPath: dest });
+    await invoke("fs_move", { oldPath: src, newPath: dest });
   }
 
   /** Get extended stat info for a file */
@@ -219,15 +1051,82 @@ export class LocalFileSystem implements FileSystemProvider {
     query: string,
     maxResults = 500,
     maxDepth = 10,
+    options: WalkOptions = {},
   ): Promise<RecursiveSearchResult> {
     if (!isTauri()) return { entries: [], totalScanned: 0, truncated: false };
     const { invoke } = await import("@tauri-apps/api/core");
     const raw: { entries: RawFileEntry[]; total_scanned: number; truncated: boolean } =
-      await invoke("fs_search_recursive", { rootPath, query, maxResults, maxDepth });
+      await invoke("fs_search_recursive", {
+        rootPath,
+        query,
+        maxResults,
+        maxDepth,
+        respectIgnore: options.respectIgnore ?? null,
+        includeHidden: options.includeHidden ?? null,
+      });
     return {
       entries: raw.entries.map(mapEntry),
       totalScanned: raw.total_scanned,
       truncated: raw.truncated,
     };
   }
+
+  /**
+   * Fuzzy-search the file index. `within` limits results to one directory;
+   * an empty query returns recently opened files.
+   */
+  async indexQuery(query: string, limit = 50, within?: string): Promise<IndexHit[]> {
+    if (!isTauri()) return [];
+    const { invoke } = await import("@tauri-apps/api/core");
+    const raw: RawIndexHit[] = await invoke("fs_index_query", {
+      query,
+      limit,
+      within: within ?? null,
+    });
+    return raw.map((hit) => ({
+      name: hit.name,
+      path: hit.path,
+      isDir: hit.is_dir,
+      size: hit.size,
+      modified: hit.modified,
+      extension: hit.extension,
+      source: "local",
+      root: hit.root,
+      relativePath: hit.relative_path,
+      score: hit.score,
+      indices: hit.indices,
+    }));
+  }
+
+  /** Note that a file was opened, so it ranks higher in indexQuery */
+  async recordOpen(path: string): Promise<void> {
+    if (!isTauri()) return;
+    const { invoke } = await import("@tauri-apps/api/core");
+    await invoke("fs_index_record_open", { path });
+  }
+
+  async indexRoots(): Promise<IndexRootStatus[]> {
+    if (!isTauri()) return [];
+    const { invoke } = await import("@tauri-apps/api/core");
+    const raw: RawIndexStatus[] = await invoke("fs_index_roots");
+    return raw.map((status) => ({
+      root: status.root,
+      state: status.state,
+      entries: status.entries,
+      scannedAt: status.scanned_at,
+      error: status.error,
+    }));
+  }
+
+  async addIndexRoot(path: string): Promise<void> {
+    if (!isTauri()) return;
+    const { invoke } = await import("@tauri-apps/api/core");
+    await invoke("fs_index_add_root", { path });
+  }
+
+  async removeIndexRoot(path: string): Promise<void> {
+    if (!isTauri()) return;
+    const { invoke } = await import("@tauri-apps/api/core");
+    await invoke("fs_index_remove_root", { path });
+  }
 }
@@ -98,3 +98,36 @@ export function getExtension(filePath: string): string {
 export function getFileName(filePath: string): string {
   return filePath.split(/[\\/]/).pop() ?? "";
 }
+
+const ARCHIVE_SUFFIXES = [".zip", ".tar", ".tar.gz", ".tgz", ".tar.zst", ".tzst"];
+
+/** Whether a file is an archive the explorer can browse into and extract */
+export function isArchivePath(filePath: string): boolean {
+  const name = getFileName(filePath).toLowerCase();
+  return ARCHIVE_SUFFIXES.some((suffix) => name.length > suffix.length && name.endsWith(suffix));
+}
+
+/** A file name without its archive suffix ("project.tar.gz" → "project") */
+export function archiveStem(filePath: string): string {
+  const name = getFileName(filePath);
+  const suffix = ARCHIVE_SUFFIXES.find((s) => name.toLowerCase().endsWith(s));
+  return suffix ? name.slice(0, -suffix.length) : name;
+}
+
+/**
+ * Split a path that leads into an archive ("/x/project.zip/src") into the
+ * archive and the "/"-separated path inside it ("src"). Returns null for
+ * paths that don't go through an archive-like name.
+ */
+export function splitArchivePath(path: string): { archive: string; inner: string } | null {
+  const parts = normalizeSeparators(path).split("/");
+  let end = 0;
+  for (let i = 0; i < parts.length; i++) {
+    end += parts[i].length;
+    if (i > 0 && isArchivePath(parts[i])) {
+      return { archive: path.slice(0, end), inner: parts.slice(i + 1).filter(Boolean).join("/") };
+    }
+    end += 1;
+  }
+  return null;
+}
@@ -0,0 +1,29 @@
+/** Scope config returned by fs_get_scope (snake_case from Rust) */
+interface RawScopeConfig {
+  include_home: boolean;
+  workspaces: string[];
+}
+
+/** Folders the fs_* commands may access. ~/.ssh, ~/.gnupg and the token store are always denied. */
+export interface ScopeConfig {
+  includeHome: boolean;
+  workspaces: string[];
+}
+
+/** Whether an error thrown by an fs_* command was a scope violation. */
+export function isPermissionDenied(err: unknown): boolean {
+  return String(err).startsWith("PermissionDenied:");
+}
+
+export async function getScope(): Promise<ScopeConfig> {
+  const { invoke } = await import("@tauri-apps/api/core");
+  const raw: RawScopeConfig = await invoke("fs_get_scope");
+  return { includeHome: raw.include_home, workspaces: raw.workspaces };
+}
+
+export async function setScope(config: ScopeConfig): Promise<void> {
+  const { invoke } = await import("@tauri-apps/api/core");
+  await invoke("fs_set_scope", {
+    config: { include_home: config.includeHome, workspaces: config.workspaces },
+  });
+}
@@ -16,9 +16,13 @@ export interface DefaultDirs {
 }
 
 export interface FileChangeEvent {
+  /** For renames, the new path */
   path: string;
-  kind: "create" | "modify" | "delete";
+  kind: "create" | "modify" | "delete" | "rename";
   is_dir: boolean;
+  /** Old and new path of a rename */
+  from: string | null;
+  to: string | null;
 }
 
 export interface RecursiveSearchResult {
@@ -37,8 +37,6 @@ class ConnectionStore {
     this.client = new PocketPawClient(url, token);
 
     // Exchange the token for a session cookie via the login endpoint.
-    // The WebSocket handler validates this cookie, avoiding the need to
-    // pass tokens in the URL (which the HTTP auth middleware would reject).
     const effectiveWsToken = wsToken ?? token;
     try {
       await this.client.loginForSession(effectiveWsToken);
@@ -47,8 +45,9 @@ class ConnectionStore {
       logger.warn("[Connection] loginForSession failed (non-fatal):", e);
     }
 
-    // Create WebSocket client (no token in URL — rely on session cookie)
-    this.ws = new PocketPawWebSocket(this.client.getWsUrl());
+    // Attach to the app's shared WebSocket bridge, which authenticates with
+    // the stored OAuth token itself
+    this.ws = new PocketPawWebSocket(url);
 
     // Mirror WS connection state into this store
     this.unsubState = this.ws.onStateChange((state) => {
@@ -88,7 +87,7 @@ class ConnectionStore {
       toast.success("Network connection restored");
       // Trigger reconnect if WS is disconnected
       if (this.ws && this.status === "disconnected") {
-        this.ws.connect();
+        this.ws.reconnect();
       }
     };
     this.offlineHandler = () => {
@@ -140,7 +139,7 @@ class ConnectionStore {
   async updateToken(newToken: string): Promise<void> {
     this.token = newToken;
     this.client?.setToken(newToken);
-    // Refresh session cookie before reconnecting WebSocket
+    // Refresh the session cookie; the bridge picks up the new token itself
     try {
       await this.client?.loginForSession(newToken);
     } catch {
@@ -1,7 +1,25 @@
 import type { FileEntry, DefaultDirs, FileChangeEvent } from "$lib/filesystem";
-import { localFs, joinPath, getFileName, parentDir, invalidateThumbnail, isImageFile } from "$lib/filesystem";
+import type {
+  WatchInfo,
+  TransferConflict,
+  TransferHandle,
+  TransferProgress,
+  ArchiveHandle,
+  ArchiveProgress,
+} from "$lib/filesystem/local";
+import {
+  localFs,
+  joinPath,
+  getFileName,
+  parentDir,
+  invalidateThumbnail,
+  isImageFile,
+  archiveStem,
+  splitArchivePath,
+} from "$lib/filesystem";
 import type { WSOpenPath } from "$lib/api/types";
 import { connectionStore } from "./connection.svelte";
+import { toast } from "svelte-sonner";
 
 export interface Breadcrumb {
   name: string;
@@ -14,6 +32,17 @@ export interface PinnedFolder {
   source: string;
 }
 
+/** A copy, move or archive job running in the background */
+export interface TransferJob {
+  id: string;
+  mode: "copy" | "move" | "extract" | "compress";
+  sources: string[];
+  destDir: string;
+  progress: TransferProgress | null;
+  /** Waiting for the user to decide about an existing destination */
+  conflict: TransferConflict | null;
+}
+
 export type FileTypeCategory =
   | "images"
   | "documents"
@@ -102,7 +131,11 @@ class ExplorerStore {
   clipboardMode = $state<"copy" | "cut" | null>(null);
   openFileChangedOnDisk = $state(0);
   webPreviewUrl = $state<string | null>(null);
+  /** How the current folder is watched; polling means delayed updates */
+  watchInfo = $state<WatchInfo | null>(null);
+  transfers = $state<TransferJob[]>([]);
 
+  private transferHandles = new Map<string, TransferHandle | ArchiveHandle>();
   private unwatchFn: (() => void) | null = null;
   private debounceTimer: ReturnType<typeof setTimeout> | null = null;
   private recursiveSearchTimer: ReturnType<typeof setTimeout> | null = null;
@@ -319,7 +352,7 @@ class ExplorerStore {
       // Auto-refresh when switching tabs
       try {
         tab.isLoading = true;
-        tab.files = await localFs.readDir(tab.path);
+        tab.files = await this.listDir(tab.path);
         tab.isLoading = false;
       } catch {
         tab.isLoading = false;
@@ -334,7 +367,7 @@ class ExplorerStore {
     tab.isLoading = true;
     tab.error = null;
     try {
-      tab.files = await localFs.readDir(path);
+      tab.files = await this.listDir(path);
       if (tabId === this.activeTabId) {
         await this.startWatching(path);
       }
@@ -457,7 +490,7 @@ class ExplorerStore {
 
     try {
       const s = source ?? this.currentSource;
-      const entries = await localFs.readDir(path);
+      const entries = await this.listDir(path);
       this.updateActiveTab((tab) => {
         tab.files = entries;
         tab.path = path;
@@ -488,7 +521,7 @@ class ExplorerStore {
     tab.isLoading = true;
     tab.error = null;
     try {
-      tab.files = await localFs.readDir(tab.path);
+      tab.files = await this.listDir(tab.path);
     } catch (e) {
       tab.error = e instanceof Error ? e.message : String(e);
     } finally {
@@ -531,9 +564,37 @@ class ExplorerStore {
   }
 
   openFileDetail(file: FileEntry): void {
+    const inArchive = file.source === "local" && !file.isDir ? splitArchivePath(file.path) : null;
+    // Archives open like folders
+    if (inArchive && !inArchive.inner) {
+      this.navigateTo(file.path);
+      return;
+    }
+    // Files inside one can't be read in place; a real folder may have an
+    // archive-like name though
+    if (inArchive) {
+      localFs.exists(file.path).then((exists) => {
+        if (exists) {
+          this.showFileDetail(file);
+        } else {
+          toast.info(`${file.name} is inside an archive`, {
+            action: { label: "Extract", onClick: () => this.extractArchive(file.path) },
+          });
+        }
+      });
+      return;
+    }
+    this.showFileDetail(file);
+  }
+
+  private showFileDetail(file: FileEntry): void {
     this.updateActiveTab((tab) => {
       tab.openFile = file;
     });
+    // Ranks the file higher in fuzzy file search
+    if (file.source === "local" && !file.isDir) {
+      localFs.recordOpen(file.path).catch(() => {});
+    }
   }
 
   closeDetail(): void {
@@ -584,29 +645,154 @@ class ExplorerStore {
 
   async paste(): Promise<void> {
     if (this.clipboardFiles.size === 0 || !this.clipboardMode || !this.currentPath) return;
+    const mode = this.clipboardMode === "copy" ? "copy" : "move";
+    const sources = [...this.clipboardFiles];
+    if (this.clipboardMode === "cut") {
+      this.clipboardFiles = new Set();
+      this.clipboardMode = null;
+    }
+    await this.transfer(mode, sources, this.currentPath);
+  }
+
+  /**
+   * Copy or move `sources` into `destDir` as a background job. Progress and
+   * conflicts show up in `transfers`; failures are reported when it ends.
+   */
+  async transfer(mode: "copy" | "move", sources: string[], destDir: string): Promise<void> {
+    if (sources.length === 0) return;
+    const id = crypto.randomUUID();
+    this.transfers = [...this.transfers, { id, mode, sources, destDir, progress: null, conflict: null }];
+    const update = (patch: Partial<TransferJob>) => {
+      this.transfers = this.transfers.map((job) => (job.id === id ? { ...job, ...patch } : job));
+    };
+
     try {
-      for (const srcPath of this.clipboardFiles) {
-        const name = getFileName(srcPath);
-        const destPath = joinPath(this.currentPath, name);
-        const stat = await localFs.stat(srcPath);
-        if (this.clipboardMode === "copy") {
-          if (stat.isDir) {
-            await localFs.copyDir(srcPath, destPath);
-          } else {
-            await localFs.copyFile(srcPath, destPath);
-          }
-        } else {
-          await localFs.moveFile(srcPath, destPath);
-        }
+      const handle = await localFs.transfer(
+        { mode, sources, destDir },
+        {
+          onProgress: (progress) => update({ progress }),
+          onConflict: (conflict) => update({ conflict }),
+        },
+      );
+      this.transferHandles.set(id, handle);
+      const report = await handle.done;
+
+      if (report.failures.length > 0) {
+        const first = report.failures[0];
+        toast.error(
+          report.failures.length === 1
+            ? `Failed to ${mode} ${getFileName(first.path)}`
+            : `Failed to ${mode} ${report.failures.length} items`,
+          { description: first.error },
+        );
       }
-      if (this.clipboardMode === "cut") {
-        this.clipboardFiles = new Set();
-        this.clipboardMode = null;
+    } catch (e) {
+      toast.error(`${mode === "copy" ? "Copy" : "Move"} failed`, { description: String(e) });
+    } finally {
+      this.transferHandles.delete(id);
+      this.transfers = this.transfers.filter((job) => job.id !== id);
+    }
+    await this.refresh();
+  }
+
+  async resolveTransferConflict(
+    id: string,
+    action: "skip" | "overwrite" | "rename",
+    applyToAll = false,
+  ): Promise<void> {
+    const handle = this.transferHandles.get(id);
+    this.transfers = this.transfers.map((job) => (job.id === id ? { ...job, conflict: null } : job));
+    if (handle && "resolve" in handle) await handle.resolve(action, applyToAll);
+  }
+
+  async cancelTransfer(id: string): Promise<void> {
+    await this.transferHandles.get(id)?.cancel();
+  }
+
+  /**
+   * Extract an archive into a new folder next to it, or, for a path inside
+   * an archive, just that entry into the folder holding the archive.
+   */
+  async extractArchive(path: string): Promise<void> {
+    const inArchive = splitArchivePath(path);
+    if (!inArchive) return;
+    const { archive, inner } = inArchive;
+    const destDir = inner
+      ? parentDir(archive)
+      : await this.freePath(parentDir(archive), archiveStem(archive), "");
+    await this.runArchiveJob("extract", [path], destDir, (onProgress) =>
+      localFs.extractArchive(archive, destDir, { entries: inner ? [inner] : undefined }, onProgress),
+    );
+  }
+
+  /** Pack `paths` into a new zip next to them */
+  async compress(paths: string[]): Promise<void> {
+    if (paths.length === 0) return;
+    const dir = parentDir(paths[0]);
+    const stem = paths.length === 1 ? getFileName(paths[0]) : "Archive";
+    const dest = await this.freePath(dir, stem, ".zip");
+    await this.runArchiveJob("compress", paths, dir, (onProgress) =>
+      localFs.createArchive(paths, dest, onProgress),
+    );
+  }
+
+  /** `dir/stem+suffix`, or the first free "stem (n)+suffix" */
+  private async freePath(dir: string, stem: string, suffix: string): Promise<string> {
+    let candidate = joinPath(dir, stem + suffix);
+    for (let n = 2; await localFs.exists(candidate); n++) {
+      candidate = joinPath(dir, `${stem} (${n})${suffix}`);
+    }
+    return candidate;
+  }
+
+  private async runArchiveJob(
+    mode: "extract" | "compress",
+    sources: string[],
+    destDir: string,
+    start: (onProgress: (progress: ArchiveProgress) => void) => Promise<ArchiveHandle>,
+  ): Promise<void> {
+    const id = crypto.randomUUID();
+    this.transfers = [...this.transfers, { id, mode, sources, destDir, progress: null, conflict: null }];
+    const verb = mode === "extract" ? "Extract" : "Compress";
+
+    try {
+      const handle = await start((progress) => {
+        this.transfers = this.transfers.map((job) =>
+          job.id === id
+            ? {
+                ...job,
+                progress: {
+                  bytesDone: progress.bytesDone,
+                  bytesTotal: progress.bytesTotal,
+                  filesDone: progress.entriesDone,
+                  filesTotal: 0,
+                  currentPath: progress.currentPath,
+                },
+              }
+            : job,
+        );
+      });
+      this.transferHandles.set(id, handle);
+      const report = await handle.done;
+
+      if (report.error) {
+        toast.error(`${verb} failed`, { description: report.error });
+      } else if (report.failures.length > 0) {
+        const first = report.failures[0];
+        toast.error(
+          report.failures.length === 1
+            ? `Failed to extract ${first.path}`
+            : `Failed to extract ${report.failures.length} entries`,
+          { description: first.error },
+        );
       }
-      await this.refresh();
     } catch (e) {
-      console.error("Paste failed:", e);
+      toast.error(`${verb} failed`, { description: String(e) });
+    } finally {
+      this.transferHandles.delete(id);
+      this.transfers = this.transfers.filter((job) => job.id !== id);
     }
+    await this.refresh();
   }
 
   moveFocus(delta: number): void {
@@ -803,7 +989,7 @@ class ExplorerStore {
       tab.typeFilters = new Set();
     });
     try {
-      const entries = await localFs.readDir(path);
+      const entries = await this.listDir(path);
       this.updateActiveTab((tab) => {
         tab.files = entries;
         tab.path = path;
@@ -818,6 +1004,17 @@ class ExplorerStore {
     }
   }
 
+  /** List a folder, or a folder inside an archive */
+  private async listDir(path: string): Promise<FileEntry[]> {
+    try {
+      return await localFs.readDir(path);
+    } catch (e) {
+      const inArchive = splitArchivePath(path);
+      if (!inArchive) throw e;
+      return localFs.archiveList(inArchive.archive, inArchive.inner);
+    }
+  }
+
   private debouncedRefresh(): void {
     if (this.debounceTimer) clearTimeout(this.debounceTimer);
     this.debounceTimer = setTimeout(() => {
@@ -829,20 +1026,21 @@ class ExplorerStore {
   private async startWatching(path: string): Promise<void> {
     this.stopWatching();
     try {
-      this.unwatchFn = await localFs.watch(path, (event: FileChangeEvent) => {
+      const subscription = await localFs.watch(path, (event: FileChangeEvent) => {
         const eventPath = event.path.replace(/\\/g, "/");
 
-        // On image modify, invalidate its thumbnail
-        if (event.kind === "modify" && !event.is_dir) {
+        // On image modify or replace, invalidate its thumbnail
+        if (event.kind !== "delete" && !event.is_dir) {
           const ext = eventPath.split(".").pop()?.toLowerCase() ?? "";
           if (isImageFile(ext)) {
             invalidateThumbnail(event.path);
           }
         }
 
-        // If the open file was modified, signal the viewer
+        // If the open file was modified or replaced (atomic saves arrive as
+        // a create or rename onto it), signal the viewer
         const tab = this.activeTab;
-        if (event.kind === "modify" && tab?.openFile) {
+        if (event.kind !== "delete" && tab?.openFile) {
           const openPath = tab.openFile.path.replace(/\\/g, "/");
           if (eventPath === openPath) {
             this.openFileChangedOnDisk++;
@@ -852,6 +1050,12 @@ class ExplorerStore {
         // Always debounce-refresh the directory listing
         this.debouncedRefresh();
       });
+      this.unwatchFn = subscription;
+      this.watchInfo = {
+        backend: subscription.backend,
+        pollIntervalMs: subscription.pollIntervalMs,
+        reason: subscription.reason,
+      };
     } catch {
       // Watching not available
     }
@@ -866,6 +1070,7 @@ class ExplorerStore {
       this.unwatchFn();
       this.unwatchFn = null;
     }
+    this.watchInfo = null;
   }
 }
 
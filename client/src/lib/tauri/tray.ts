@@ -18,6 +18,7 @@ async function getListen() {
 
 export interface TrayEventHandlers {
   onNavigate?: (path: string) => void;
+  onOpenSession?: (sessionId: string) => void;
 }
 
 const unlisten: (() => void)[] = [];
@@ -32,6 +33,11 @@ export async function setupTrayListeners(handlers: TrayEventHandlers): Promise<v
       const u = await listen<string>("tray-navigate", (event) => cb(event.payload));
       unlisten.push(u);
     }
+    if (handlers.onOpenSession) {
+      const cb = handlers.onOpenSession;
+      const u = await listen<string>("tray-open-session", (event) => cb(event.payload));
+      unlisten.push(u);
+    }
   } catch (err) {
     console.warn("[Tray] Failed to setup listeners:", err);
   }
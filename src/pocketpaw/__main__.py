@@ -259,6 +259,12 @@ Examples:
         help="Host to bind web server (default: auto-detect; 0.0.0.0 on headless servers)",
     )
     parser.add_argument("--dev", action="store_true", help="Development mode with auto-reload")
+    parser.add_argument(
+        "--uds",
+        type=str,
+        default=None,
+        help="Unix socket for 'serve' to listen on in addition to TCP",
+    )
     parser.add_argument(
         "--check-ollama",
         action="store_true",
@@ -536,7 +542,11 @@ def main() -> None:
         if args.command == "serve":
             from pocketpaw.api.serve import run_api_server
 
-            _serve(run_api_server, host=host, port=args.port, dev=args.dev)
+            if args.uds:
+                # The app that asked for the socket expects this exact port too
+                run_api_server(host=host, port=args.port, dev=args.dev, uds=args.uds)
+            else:
+                _serve(run_api_server, host=host, port=args.port, dev=args.dev)
         elif args.command == "status":
             from pocketpaw.cli.status import run_status
 
@@ -14,6 +14,7 @@ from datetime import UTC, datetime, timedelta
 
 from pocketpaw.api.oauth2.models import AuthorizationCode, OAuthToken
 from pocketpaw.api.oauth2.storage import OAuthStorage
+from pocketpaw.config import get_instance_id
 
 logger = logging.getLogger(__name__)
 
@@ -143,6 +144,7 @@ class AuthorizationServer:
             "token_type": "Bearer",
             "expires_in": int(ACCESS_TOKEN_TTL.total_seconds()),
             "scope": auth_code.scope,
+            "sub": get_instance_id(),
         }, None
 
     def refresh(self, refresh_token: str) -> tuple[dict | None, str | None]:
@@ -189,6 +191,7 @@ class AuthorizationServer:
             "token_type": "Bearer",
             "expires_in": int(ACCESS_TOKEN_TTL.total_seconds()),
             "scope": old_token.scope,
+            "sub": get_instance_id(),
         }, None
 
     def revoke(self, token: str) -> bool:
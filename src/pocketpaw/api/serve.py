@@ -153,18 +153,69 @@ def create_api_app():
     return app
 
 
+def _prepare_uds(path: str) -> None:
+    """Make the folder holding the Unix socket owner-only (0700).
+
+    The socket is only made owner-only after it is bound, so the folder is
+    what keeps other local users out from the start.
+    """
+    import os
+
+    from pocketpaw.dashboard_state import _UDS_ENV
+
+    folder = os.path.dirname(os.path.abspath(path))
+    os.makedirs(folder, mode=0o700, exist_ok=True)
+    if os.stat(folder).st_uid == os.getuid():
+        os.chmod(folder, 0o700)
+    else:
+        logger.warning("Socket folder %s is not ours; other users may reach the API", folder)
+    os.environ[_UDS_ENV] = path
+
+
+def _bind_sockets(host: str, port: int, uds: str) -> list:
+    """Bind the TCP listener and the Unix socket for one server.
+
+    The desktop app's webview talks to the API over TCP while the app itself
+    prefers the socket, so a backend started with ``uds`` serves on both.
+    """
+    import os
+    import socket
+
+    tcp = socket.socket(socket.AF_INET, socket.SOCK_STREAM)
+    tcp.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+    tcp.bind((host, port))
+
+    if os.path.exists(uds):
+        os.unlink(uds)
+    unix = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+    unix.bind(uds)
+    os.chmod(uds, 0o600)
+    return [tcp, unix]
+
+
 def run_api_server(
     host: str = "127.0.0.1",
     port: int = 8888,
     dev: bool = False,
+    uds: str | None = None,
 ) -> None:
-    """Start the API-only server (no dashboard)."""
+    """Start the API-only server (no dashboard).
+
+    With ``uds`` the server also listens on that Unix socket, next to TCP.
+    """
     import uvicorn
 
     print("\n" + "=" * 50)
     print("\U0001f43e POCKETPAW API SERVER")
     print("=" * 50)
 
+    if uds and dev:
+        # The reloader binds its own listener; it can't share one with a socket
+        logger.warning("--uds is ignored with --dev; listening on TCP only")
+        uds = None
+    if uds:
+        _prepare_uds(uds)
+        print(f"\n\U0001f310 API listening on Unix socket {uds}")
     if host == "0.0.0.0":
         import socket
 
@@ -193,6 +244,9 @@ def run_api_server(
             reload_includes=["*.py"],
             log_level="debug",
         )
+    elif uds:
+        server = uvicorn.Server(uvicorn.Config(create_api_app(), host=host, port=port))
+        server.run(sockets=_bind_sockets(host, port, uds))
     else:
         app = create_api_app()
         uvicorn.run(app, host=host, port=port)
@@ -1100,6 +1100,25 @@ def regenerate_token() -> str:
     return token
 
 
+def get_instance_id() -> str:
+    """
+    Get the stable id of this PocketPaw instance, generating it on first use.
+    OAuth clients use it as the subject of the tokens they are issued.
+    """
+    import uuid
+
+    path = get_config_dir() / "instance_id"
+    if path.exists():
+        instance_id = path.read_text().strip()
+        if instance_id:
+            return instance_id
+
+    instance_id = uuid.uuid4().hex
+    path.write_text(instance_id)
+    _chmod_safe(path, 0o600)
+    return instance_id
+
+
 # Flag file to avoid re-running migration on every load
 _MIGRATION_DONE_PATH: Path | None = None
 
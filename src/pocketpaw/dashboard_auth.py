@@ -11,12 +11,13 @@ Extracted from dashboard.py — contains:
 import hmac
 import io
 import logging
+import os
 
 from fastapi import APIRouter, Query, Request
 from fastapi.responses import JSONResponse, Response, StreamingResponse
 
 from pocketpaw.config import Settings, get_access_token, regenerate_token
-from pocketpaw.dashboard_state import _LOCALHOST_ADDRS, _PROXY_HEADERS
+from pocketpaw.dashboard_state import _LOCALHOST_ADDRS, _PROXY_HEADERS, _UDS_ENV
 from pocketpaw.http_utils import is_request_secure
 from pocketpaw.security.rate_limiter import api_limiter, auth_limiter
 from pocketpaw.security.session_tokens import create_session_token, verify_session_token
@@ -77,6 +78,9 @@ def _is_genuine_localhost(request_or_ws) -> bool:
         return False
 
     client_host = request_or_ws.client.host if request_or_ws.client else None
+    if client_host is None and os.environ.get(_UDS_ENV):
+        # Unix socket peers have no address; the socket's folder is owner-only
+        client_host = "127.0.0.1"
     if client_host not in _LOCALHOST_ADDRS:
         return False
 
@@ -181,6 +181,8 @@ _OAUTH_SCOPES: dict[str, list[str]] = {
 
 _LOCALHOST_ADDRS = {"127.0.0.1", "localhost", "::1"}
 _PROXY_HEADERS = ("cf-connecting-ip", "x-forwarded-for")
+# Set to the socket path while ``pocketpaw serve --uds`` is serving
+_UDS_ENV = "POCKETPAW_UDS"
 
 
 # ── Helper functions ────────────────────────────────────────────────────────
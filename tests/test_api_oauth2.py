@@ -29,7 +29,8 @@ def storage():
 
 
 @pytest.fixture
-def server(storage):
+def server(storage, monkeypatch):
+    monkeypatch.setattr("pocketpaw.api.oauth2.server.get_instance_id", lambda: "instance-1")
     return AuthorizationServer(storage)
 
 
@@ -181,6 +182,21 @@ class TestAuthorizationServer:
         assert new_tokens["access_token"] != tokens["access_token"]
         assert new_tokens["refresh_token"] != tokens["refresh_token"]
 
+    def test_tokens_carry_the_instance_subject(self, server):
+        verifier, challenge = _make_pkce_pair()
+        code, _ = server.authorize(
+            client_id="pocketpaw-desktop",
+            redirect_uri="tauri://oauth-callback",
+            scope="chat",
+            code_challenge=challenge,
+        )
+        tokens, _ = server.exchange(
+            code=code, client_id="pocketpaw-desktop", code_verifier=verifier
+        )
+        new_tokens, _ = server.refresh(tokens["refresh_token"])
+        assert tokens["sub"] == "instance-1"
+        assert new_tokens["sub"] == "instance-1"
+
     def test_refresh_invalid_token(self, server):
         result, error = server.refresh("invalid-refresh")
         assert error == "invalid_refresh_token"
@@ -1,6 +1,8 @@
 """Tests for the ``pocketpaw serve`` API-only server."""
 
 import socket
+import stat
+import sys
 from unittest.mock import MagicMock, patch
 
 import pytest
@@ -179,6 +181,58 @@ class TestServeCommand:
         assert args.host == "0.0.0.0"
         assert args.port == 9000
 
+    def test_serve_accepts_uds(self):
+        """The real parser should accept --uds for 'serve'."""
+        from pocketpaw.__main__ import _build_parser
+
+        args = _build_parser().parse_args(["serve", "--uds", "/tmp/pocketpaw.sock"])
+        assert args.command == "serve"
+        assert args.uds == "/tmp/pocketpaw.sock"
+
+    @pytest.mark.skipif(sys.platform == "win32", reason="Unix sockets only")
+    def test_serve_binds_uds_and_tcp(self, tmp_path, monkeypatch):
+        """With uds, one server listens on both TCP (for the webview) and the
+        socket, which sits in an owner-only folder."""
+        import socket
+
+        from pocketpaw.dashboard_state import _UDS_ENV
+
+        monkeypatch.delenv(_UDS_ENV, raising=False)
+        socket_path = tmp_path / "run" / "backend.sock"
+        with (
+            patch("uvicorn.Server.run") as mock_run,
+            patch("pocketpaw.api.serve.create_api_app"),
+        ):
+            from pocketpaw.api.serve import run_api_server
+
+            run_api_server(port=0, uds=str(socket_path))
+
+        sockets = mock_run.call_args.kwargs["sockets"]
+        try:
+            assert {s.family for s in sockets} == {socket.AF_INET, socket.AF_UNIX}
+            assert stat.S_IMODE((tmp_path / "run").stat().st_mode) == 0o700
+            assert stat.S_IMODE(socket_path.stat().st_mode) == 0o600
+        finally:
+            for s in sockets:
+                s.close()
+        monkeypatch.delenv(_UDS_ENV, raising=False)
+
+    def test_uds_peer_counts_as_localhost(self, monkeypatch):
+        """Unix socket peers have no address; they count as local only while
+        serving on a socket."""
+        from pocketpaw.dashboard_auth import _is_genuine_localhost
+        from pocketpaw.dashboard_state import _UDS_ENV
+
+        request = MagicMock()
+        request.client = None
+        request.headers = {}
+        with patch("pocketpaw.dashboard_auth.Settings.load") as load:
+            load.return_value.localhost_auth_bypass = True
+            monkeypatch.delenv(_UDS_ENV, raising=False)
+            assert not _is_genuine_localhost(request)
+            monkeypatch.setenv(_UDS_ENV, "/tmp/pocketpaw.sock")
+            assert _is_genuine_localhost(request)
+
 
 # ---------------------------------------------------------------------------
 # Socket resource safety (issue #608)